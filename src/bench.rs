@@ -0,0 +1,66 @@
+use crate::{
+    editor::prelude::TileMaterial,
+    level::{autotile::affected_positions, load_asset_json, materials::MaterialSet, Grid},
+};
+use bevy_ecs_tilemap::prelude::TilePos;
+use std::time::Instant;
+
+const BENCH_MAP_SIZE: u32 = 512;
+const BENCH_FRAMES: u32 = 60;
+
+/**
+ * Compare redrawing a large map every frame against redrawing only the tiles a brush stroke
+ * touched, which is what the editor board does
+ */
+pub fn board_update() -> Result<(), String> {
+    let material_set: MaterialSet =
+        load_asset_json("tiles.materials.json").map_err(|err| err.to_string())?;
+
+    let mut tiles = Grid::new(BENCH_MAP_SIZE, BENCH_MAP_SIZE, TileMaterial::Floor);
+    for y in 0..BENCH_MAP_SIZE {
+        for x in 0..BENCH_MAP_SIZE {
+            if (x * 7 + y * 13) % 5 == 0 {
+                tiles.set(&TilePos { x, y }, TileMaterial::Wall);
+            }
+        }
+    }
+    let mut indices = Grid::new(BENCH_MAP_SIZE, BENCH_MAP_SIZE, 0);
+
+    let start = Instant::now();
+    for _ in 0..BENCH_FRAMES {
+        for y in 0..BENCH_MAP_SIZE {
+            for x in 0..BENCH_MAP_SIZE {
+                let pos = TilePos { x, y };
+                indices.set(&pos, material_set.grid_tile_index(&tiles, &pos));
+            }
+        }
+    }
+    let full = start.elapsed() / BENCH_FRAMES;
+
+    let start = Instant::now();
+    for frame in 0..BENCH_FRAMES {
+        // A 3x3 brush dragged across the middle of the map, one stamp per frame
+        let cx = 1 + (frame * 4) % (BENCH_MAP_SIZE - 2);
+        let cy = BENCH_MAP_SIZE / 2;
+        let changed: Vec<TilePos> = (cy - 1..=cy + 1)
+            .flat_map(|y| (cx - 1..=cx + 1).map(move |x| TilePos { x, y }))
+            .collect();
+        for pos in changed.iter() {
+            tiles.set(pos, TileMaterial::Wall);
+        }
+
+        for pos in affected_positions(changed.iter(), &tiles.size()) {
+            indices.set(&pos, material_set.grid_tile_index(&tiles, &pos));
+        }
+    }
+    let dirty = start.elapsed() / BENCH_FRAMES;
+
+    println!(
+        "{}x{} map, average over {} frames",
+        BENCH_MAP_SIZE, BENCH_MAP_SIZE, BENCH_FRAMES
+    );
+    println!("full redraw:  {:?} per frame", full);
+    println!("dirty redraw: {:?} per frame", dirty);
+
+    Ok(())
+}
@@ -1,12 +1,50 @@
+use crate::level::ASSET_ROOT;
 use bevy::prelude::Resource;
+use std::path::Path;
 
 #[derive(Resource)]
 pub struct Debug(pub bool);
 #[derive(Default)]
 pub struct ProgramConfig {
     pub debug: bool,
+    pub bench_board: bool,
+    /// Asset path of a dungeon to play instead of starting in the editor
+    pub dungeon: Option<String>,
+    /// Level tooling to run without opening a window
+    pub command: Option<Command>,
 }
 
+/// Headless level tooling. Paths are regular file paths, not asset paths.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    /// Check level files, or every level file in a folder, and fail if any has errors
+    Validate { paths: Vec<String> },
+    /// Draw a level's tiles to a PNG with the tile atlas
+    Render {
+        input: String,
+        output: String,
+        scale: u32,
+    },
+    /// Rewrite a level in the format of the output's extension: `.json`, `.txt`, `.tmx` or `.tmj`
+    Convert { input: String, output: String },
+    /// Write a generated level
+    Generate {
+        generator: String,
+        width: u32,
+        height: u32,
+        seed: u64,
+        output: String,
+    },
+}
+
+pub const USAGE: &str = "usage:
+    rog-man [--debug] [--dungeon <asset path>] [--bench-board]
+    rog-man validate <level or folder>...
+    rog-man render <level> <png> [--scale <n>]
+    rog-man convert <level> <level>
+    rog-man generate <Bsp|Cave|Walk|Wfc> <width> <height> <seed> <level>
+    rog-man --check <level asset path>    (deprecated, use validate)";
+
 impl ProgramConfig {
     pub fn build(args: &[String]) -> Result<ProgramConfig, &'static str> {
         let mut cfg = ProgramConfig::default();
@@ -14,11 +52,31 @@ impl ProgramConfig {
             return Ok(cfg);
         }
 
-        for arg in args {
+        let mut args = args.iter();
+        if let Some(command) = Command::build(args.clone())? {
+            cfg.command = Some(command);
+            return Ok(cfg);
+        }
+
+        while let Some(arg) = args.next() {
             match arg.as_ref() {
                 "-d" | "--debug" => {
                     cfg.debug = true;
                 }
+                "--bench-board" => {
+                    cfg.bench_board = true;
+                }
+                "--dungeon" => {
+                    cfg.dungeon = Some(args.next().ok_or("--dungeon needs a path")?.clone());
+                }
+                // Kept for scripts written before `validate`, which takes file paths instead
+                "--check" => {
+                    let path =
+                        Path::new(ASSET_ROOT).join(args.next().ok_or("--check needs a path")?);
+                    let path = path.to_string_lossy().to_string();
+                    println!("--check is deprecated, use `validate {}`", path);
+                    cfg.command = Some(Command::Validate { paths: vec![path] });
+                }
                 _ => return Err("unknown argument"),
             }
         }
@@ -26,3 +84,58 @@ impl ProgramConfig {
         Ok(cfg)
     }
 }
+
+impl Command {
+    /**
+     * Parse a subcommand, or nothing if the first argument isn't one
+     */
+    fn build<'a>(
+        mut args: impl Iterator<Item = &'a String>,
+    ) -> Result<Option<Command>, &'static str> {
+        let mut next = |missing: &'static str| args.next().cloned().ok_or(missing);
+        let command = match next("")?.as_ref() {
+            "validate" => {
+                let mut paths = vec![next("validate needs a level")?];
+                while let Ok(path) = next("") {
+                    paths.push(path);
+                }
+                Command::Validate { paths }
+            }
+            "render" => {
+                let input = next("render needs a level")?;
+                let output = next("render needs a png to write")?;
+                let scale = match next("").ok().as_deref() {
+                    None => 1,
+                    Some("--scale") => parse(&next("--scale needs a number")?)?,
+                    Some(_) => return Err("unknown argument"),
+                };
+                Command::Render {
+                    input,
+                    output,
+                    scale,
+                }
+            }
+            "convert" => Command::Convert {
+                input: next("convert needs a level")?,
+                output: next("convert needs a level to write")?,
+            },
+            "generate" => Command::Generate {
+                generator: next("generate needs a generator")?,
+                width: parse(&next("generate needs a width")?)?,
+                height: parse(&next("generate needs a height")?)?,
+                seed: parse(&next("generate needs a seed")?)?,
+                output: next("generate needs a level to write")?,
+            },
+            _ => return Ok(None),
+        };
+
+        match next("") {
+            Ok(_) => Err("too many arguments"),
+            Err(_) => Ok(Some(command)),
+        }
+    }
+}
+
+fn parse<T: std::str::FromStr>(arg: &str) -> Result<T, &'static str> {
+    arg.parse().map_err(|_| "expected a number")
+}
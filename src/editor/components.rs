@@ -1,21 +1,322 @@
-use bevy::prelude::{Component, Event, Resource, Vec2};
-use bevy_ecs_tilemap::tiles::TilePos;
+use super::shapes::{Footprint, Shape};
+use crate::level::{
+    objects::{ObjectPlacement, TileObject},
+    validate::Problem,
+    Anchor, Grid, Level, Placement, ASSET_ROOT,
+};
+use bevy::{
+    prelude::{Component, Event, Handle, Resource, Vec2},
+    time::{Timer, TimerMode},
+};
+use bevy_ecs_tilemap::{map::TilemapSize, tiles::TilePos};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::Path,
+};
 
 pub enum Cleanup {
     CleanupInEditorExit,
 }
 
-#[derive(Component, Clone, Debug, PartialEq)]
+/// Materials are stored by name so levels can use materials defined in data
+#[derive(Component, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
 pub enum TileMaterial {
     Wall,
     Floor,
     PlayerSpawn,
+    /// Lead to the next and previous floor of a dungeon
+    StairsDown,
+    StairsUp,
+    /// A material only known from the material definitions
+    Custom(String),
 }
 
+impl TileMaterial {
+    pub fn name(&self) -> &str {
+        match self {
+            TileMaterial::Wall => "Wall",
+            TileMaterial::Floor => "Floor",
+            TileMaterial::PlayerSpawn => "PlayerSpawn",
+            TileMaterial::StairsDown => "StairsDown",
+            TileMaterial::StairsUp => "StairsUp",
+            TileMaterial::Custom(name) => name,
+        }
+    }
+}
+
+impl From<String> for TileMaterial {
+    fn from(name: String) -> Self {
+        match name.as_str() {
+            "Wall" => TileMaterial::Wall,
+            "Floor" => TileMaterial::Floor,
+            "PlayerSpawn" => TileMaterial::PlayerSpawn,
+            "StairsDown" => TileMaterial::StairsDown,
+            "StairsUp" => TileMaterial::StairsUp,
+            _ => TileMaterial::Custom(name),
+        }
+    }
+}
+
+impl From<TileMaterial> for String {
+    fn from(material: TileMaterial) -> Self {
+        material.name().to_string()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BrushMode {
+    Paint,
+    Fill,
+    Shape(Shape),
+    Select,
+    Paste,
+    /// Place the named monster or item
+    Place(PlacementLayer, String),
+    /// Place copies of a configured door or chest
+    Object(TileObject),
+}
+
+/// Which neighbors count as connected when filling a region
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Connectivity {
+    Four,
+    Eight,
+}
+
+/// The tilemap being edited
+#[derive(Component)]
+pub struct EditorTilemap;
+
+/// Play the map being edited
+#[derive(Event, Clone, Debug, PartialEq)]
+pub struct PlayTestEvent;
+
+/// A message shown to the user for a little while, e.g. why play testing was refused
+#[derive(Resource)]
+pub struct StatusMessage {
+    pub text: Option<String>,
+    pub timer: Timer,
+}
+
+impl Default for StatusMessage {
+    fn default() -> Self {
+        Self {
+            text: None,
+            timer: Timer::from_seconds(3.0, TimerMode::Once),
+        }
+    }
+}
+
+impl StatusMessage {
+    pub fn show(&mut self, text: &str) {
+        self.text = Some(text.to_string());
+        self.timer.reset();
+    }
+}
+
+/// What the validator found wrong with the map being edited
+#[derive(Resource, Default)]
+pub struct LintReport(pub Vec<Problem>);
+
+/// Positions whose material changed since the board was last drawn
+#[derive(Resource, Default)]
+pub struct DirtyTiles {
+    pub all: bool,
+    /// The tiles were respawned this frame, so `TileStorage` is stale until commands are applied
+    pub replaced: bool,
+    pub positions: HashSet<TilePos>,
+}
+
+impl DirtyTiles {
+    pub fn mark(&mut self, tile_pos: TilePos) {
+        self.positions.insert(tile_pos);
+    }
+
+    pub fn mark_all(&mut self) {
+        self.all = true;
+    }
+}
+
+/// The tile under the cursor, if any
+#[derive(Resource, Default)]
+pub struct HoveredTile(pub Option<TilePos>);
+
+/// The tiles a shape is being dragged between
+#[derive(Resource, Default)]
+pub struct ShapeDrag {
+    pub start: Option<TilePos>,
+    pub end: Option<TilePos>,
+}
+
+/// Marquee selection over the tilemap
+#[derive(Resource, Default)]
+pub struct Selection {
+    pub start: Option<TilePos>,
+    pub end: Option<TilePos>,
+}
+
+impl Selection {
+    /**
+     * Bottom left corner and size of the selection
+     */
+    pub fn bounds(&self) -> Option<(TilePos, u32, u32)> {
+        let (start, end) = (self.start?, self.end?);
+        let origin = TilePos {
+            x: start.x.min(end.x),
+            y: start.y.min(end.y),
+        };
+
+        Some((
+            origin,
+            start.x.max(end.x) - origin.x + 1,
+            start.y.max(end.y) - origin.y + 1,
+        ))
+    }
+}
+
+/// The kinds of things placed on top of the tiles
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PlacementLayer {
+    Monster,
+    Item,
+}
+
+/// Monsters, items and objects placed on the level being edited, by position
+#[derive(Resource, Default, Debug)]
+pub struct Placements {
+    pub monsters: HashMap<TilePos, String>,
+    pub items: HashMap<TilePos, String>,
+    pub objects: HashMap<TilePos, TileObject>,
+}
+
+impl Placements {
+    pub fn from_level(level: &Level) -> Self {
+        let by_position = |placements: &[Placement]| {
+            placements
+                .iter()
+                .map(|placement| (placement.tile_pos(), placement.name.clone()))
+                .collect()
+        };
+
+        Self {
+            monsters: by_position(&level.monsters),
+            items: by_position(&level.items),
+            objects: level
+                .objects
+                .iter()
+                .map(|placement| (placement.tile_pos(), placement.object.clone()))
+                .collect(),
+        }
+    }
+
+    pub fn layer(&self, layer: PlacementLayer) -> &HashMap<TilePos, String> {
+        match layer {
+            PlacementLayer::Monster => &self.monsters,
+            PlacementLayer::Item => &self.items,
+        }
+    }
+
+    pub fn layer_mut(&mut self, layer: PlacementLayer) -> &mut HashMap<TilePos, String> {
+        match layer {
+            PlacementLayer::Monster => &mut self.monsters,
+            PlacementLayer::Item => &mut self.items,
+        }
+    }
+
+    /**
+     * The placements of a layer inside a map of the given size, in row order so saved levels
+     * are stable
+     */
+    pub fn placements(&self, layer: PlacementLayer, size: &TilemapSize) -> Vec<Placement> {
+        let mut placements: Vec<Placement> = self
+            .layer(layer)
+            .iter()
+            .filter(|(tile_pos, _)| tile_pos.x < size.x && tile_pos.y < size.y)
+            .map(|(tile_pos, name)| Placement::new(name, tile_pos))
+            .collect();
+        placements.sort_by_key(|placement| (placement.y, placement.x));
+        placements
+    }
+
+    /**
+     * The objects inside a map of the given size, in row order
+     */
+    pub fn object_placements(&self, size: &TilemapSize) -> Vec<ObjectPlacement> {
+        let mut placements: Vec<ObjectPlacement> = self
+            .objects
+            .iter()
+            .filter(|(tile_pos, _)| tile_pos.x < size.x && tile_pos.y < size.y)
+            .map(|(tile_pos, object)| ObjectPlacement::new(object, tile_pos))
+            .collect();
+        placements.sort_by_key(|placement| (placement.y, placement.x));
+        placements
+    }
+
+    /**
+     * A level from the tiles and everything placed on them
+     */
+    pub fn level(&self, name: &str, author: &str, tiles: Grid<TileMaterial>) -> Level {
+        let size = tiles.size();
+        Level {
+            name: name.to_string(),
+            author: author.to_string(),
+            monsters: self.placements(PlacementLayer::Monster, &size),
+            items: self.placements(PlacementLayer::Item, &size),
+            objects: self.object_placements(&size),
+            tiles,
+        }
+    }
+}
+
+/// The sprite of a placed monster, item or object on the editor board
+#[derive(Component)]
+pub struct PlacementMarker;
+
+/// Tiles that have been copied or cut and can be pasted
+#[derive(Resource, Default)]
+pub struct Clipboard(pub Option<Grid<TileMaterial>>);
+
+#[derive(Event, Clone, Debug, PartialEq)]
+pub enum ClipboardEvent {
+    Copy,
+    Cut,
+    Paste,
+    Rotate,
+    Mirror,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Stamp {
+    pub name: String,
+    pub tiles: Grid<TileMaterial>,
+}
+
+/// Named stamps shared between levels
+#[derive(Resource, Serialize, Deserialize, Default)]
+pub struct StampLibrary {
+    pub stamps: Vec<Stamp>,
+}
+
+impl StampLibrary {
+    pub fn save(&self, asset_path: &str) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        fs::write(Path::new(ASSET_ROOT).join(asset_path), json)
+    }
+}
+
+pub const MAX_BRUSH_SIZE: usize = 16;
+
 #[derive(Resource)]
 pub struct RogBrush {
     pub material: TileMaterial,
     pub size: usize,
+    pub footprint: Footprint,
+    pub mode: BrushMode,
+    pub connectivity: Connectivity,
 }
 
 impl RogBrush {
@@ -23,6 +324,9 @@ impl RogBrush {
         Self {
             material: TileMaterial::Wall,
             size: 1,
+            footprint: Footprint::Square,
+            mode: BrushMode::Paint,
+            connectivity: Connectivity::Four,
         }
     }
 }
@@ -45,6 +349,21 @@ pub enum EditAction {
         tile_pos: TilePos,
         material: TileMaterial,
         size: usize,
+        footprint: Footprint,
+    },
+    /// Replace the whole map, possibly with one of a different size. Sent as an event of its
+    /// own, as edits after it in the same frame wait for the respawned tiles.
+    ReplaceTiles { tiles: Grid<TileMaterial> },
+    /// Put a monster or item on a tile, or clear it with `None`
+    Place {
+        layer: PlacementLayer,
+        tile_pos: TilePos,
+        name: Option<String>,
+    },
+    /// Put a door or chest on a tile, or clear it with `None`
+    PlaceObject {
+        tile_pos: TilePos,
+        object: Option<TileObject>,
     },
 }
 #[derive(Event, Clone, Debug, PartialEq)]
@@ -55,6 +374,57 @@ pub struct UndoEditEvent;
 #[derive(Event, Clone, Debug, PartialEq)]
 pub struct RedoEditEvent;
 
+/// Save to the current path, or to the given asset path ("Save As")
+#[derive(Event, Clone, Debug, PartialEq)]
+pub enum SaveLevelEvent {
+    Save,
+    SaveAs(String),
+}
+
+/// Load the level at the given asset path
+#[derive(Event, Clone, Debug, PartialEq)]
+pub struct OpenLevelEvent(pub String);
+
+/// Start over with a blank level of the given size
+#[derive(Event, Clone, Debug, PartialEq)]
+pub struct NewLevelEvent {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Grow or shrink the canvas, keeping existing tiles fixed to the anchored edges
+#[derive(Event, Clone, Debug, PartialEq)]
+pub struct ResizeLevelEvent {
+    pub width: u32,
+    pub height: u32,
+    pub anchor_x: Anchor,
+    pub anchor_y: Anchor,
+}
+
+/// Metadata for the level currently open in the editor
+#[derive(Resource)]
+pub struct CurrentLevel {
+    pub name: String,
+    pub author: String,
+    pub path: Option<String>,
+    pub path_input: String,
+}
+
+impl CurrentLevel {
+    pub fn default() -> Self {
+        Self {
+            name: "Untitled".to_string(),
+            author: String::new(),
+            path: None,
+            path_input: "levels/untitled.level.json".to_string(),
+        }
+    }
+}
+
+/// A level that has been requested from the asset server but not yet applied to the tilemap
+#[derive(Resource, Default)]
+pub struct PendingLevel(pub Option<(String, Handle<Level>)>);
+
 #[derive(Resource)]
 pub struct ActionStack {
     cursor: usize,
@@ -106,28 +476,3 @@ impl ActionStack {
         }
     }
 }
-
-// Walls are denoted by which neighbors they connect to. Annotation is done clockwise beginning
-// at north.
-// E.g. XOXX is T piece connecting to north, south and west
-#[derive(Clone, Debug, PartialEq)]
-pub enum TileMapIndex {
-    WallOXXX = 0,
-    WallXOXX = 1,
-    WallOXXO = 2,
-    WallOXOX = 3,
-    WallOOXX = 4,
-    WallXXXX = 5,
-    WallXXXO = 6,
-    WallXXOX = 7,
-    WallXXOO = 8,
-    WallOOOX = 9,
-    WallXOXO = 10,
-    Floor = 11,
-    WallXOOO = 12,
-    WallOOXO = 13,
-    WallOXOO = 14,
-    WallXOOX = 16,
-    QuestionMark = 17,
-    PlayerSpawn = 18,
-}
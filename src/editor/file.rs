@@ -0,0 +1,185 @@
+use super::{
+    components::{
+        ActionStack, CurrentLevel, OpenLevelEvent, PendingLevel, Placements, SaveLevelEvent,
+    },
+    spawn_editor_level,
+};
+use crate::{
+    editor::prelude::TileMaterial,
+    get_single, get_some,
+    level::{
+        format::LevelFormat,
+        load_asset_json,
+        tiled::{TiledTable, TILED_TABLE_PATH},
+        tilemap::despawn_level,
+        Grid, Level, ASSET_ROOT,
+    },
+    ImageAssets,
+};
+use bevy::{asset::LoadState, prelude::*};
+use bevy_ecs_tilemap::prelude::*;
+use std::{fs, path::Path};
+
+pub fn load_tiled_table(mut commands: Commands) {
+    let table = load_asset_json(TILED_TABLE_PATH).unwrap_or_else(|err| {
+        info!("No Tiled table loaded: {}", err);
+        TiledTable::default()
+    });
+    commands.insert_resource(table);
+}
+
+/**
+ * Read the current tilemap back into a level
+ */
+pub fn snapshot_level(
+    storage: &TileStorage,
+    size: &TilemapSize,
+    tile_query: &Query<&TileMaterial>,
+) -> Grid<TileMaterial> {
+    let mut tiles = Grid::new(size.x, size.y, TileMaterial::Floor);
+    for x in 0..size.x {
+        for y in 0..size.y {
+            let tile_pos = TilePos { x, y };
+            if let Some(material) = storage
+                .get(&tile_pos)
+                .and_then(|entity| tile_query.get(entity).ok())
+            {
+                tiles.set(&tile_pos, material.clone());
+            }
+        }
+    }
+
+    tiles
+}
+
+pub fn save_level(
+    mut save_reader: EventReader<SaveLevelEvent>,
+    mut current: ResMut<CurrentLevel>,
+    placements: Res<Placements>,
+    table: Res<TiledTable>,
+    tilemap_q: Query<(&TileStorage, &TilemapSize)>,
+    tile_query: Query<&TileMaterial>,
+) {
+    for event in save_reader.iter() {
+        let path = match event {
+            SaveLevelEvent::Save => current
+                .path
+                .clone()
+                .unwrap_or_else(|| current.path_input.clone()),
+            SaveLevelEvent::SaveAs(path) => path.clone(),
+        };
+
+        let (storage, size) = get_single!(tilemap_q);
+        let level = placements.level(
+            &current.name,
+            &current.author,
+            snapshot_level(storage, size, &tile_query),
+        );
+
+        match write_level(&level, &path, &table) {
+            Ok(_) => {
+                info!("Saved level to {}", path);
+                current.path_input = path.clone();
+                current.path = Some(path);
+            }
+            Err(err) => error!("Could not save level to {}: {}", path, err),
+        }
+    }
+}
+
+/**
+ * Save a level to an asset path, in the format of its extension. Anything but the editor's own
+ * format is written directly rather than through [`Level::save`].
+ */
+fn write_level(level: &Level, asset_path: &str, table: &TiledTable) -> Result<(), String> {
+    let format = match LevelFormat::from_path(asset_path) {
+        Some(LevelFormat::Json) | None => {
+            return level.save(asset_path).map_err(|err| err.to_string())
+        }
+        Some(format) => format,
+    };
+    if !format.keeps_placements() {
+        warn!("Monsters, items and objects aren't saved to {}", asset_path);
+    }
+
+    let contents = format.write(level, None, table)?;
+    let path = Path::new(ASSET_ROOT).join(asset_path);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+    }
+    fs::write(path, contents).map_err(|err| err.to_string())
+}
+
+/**
+ * Start loading a level. Level files go through the asset server, other formats are read right
+ * away and handed over as a ready asset.
+ */
+pub fn open_level(
+    mut open_reader: EventReader<OpenLevelEvent>,
+    asset_server: Res<AssetServer>,
+    table: Res<TiledTable>,
+    mut levels: ResMut<Assets<Level>>,
+    mut pending: ResMut<PendingLevel>,
+) {
+    for OpenLevelEvent(path) in open_reader.iter() {
+        let format = match LevelFormat::from_path(path) {
+            Some(LevelFormat::Json) | None => {
+                pending.0 = Some((path.clone(), asset_server.load(path.as_str())));
+                continue;
+            }
+            Some(format) => format,
+        };
+
+        let level = fs::read_to_string(Path::new(ASSET_ROOT).join(path))
+            .map_err(|err| err.to_string())
+            .and_then(|contents| format.read(&contents, &table));
+        match level {
+            Ok(level) => pending.0 = Some((path.clone(), levels.add(level))),
+            Err(err) => error!("Could not load level {}: {}", path, err),
+        }
+    }
+}
+
+/**
+ * Replace the tilemap once a requested level has finished loading
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn apply_loaded_level(
+    mut commands: Commands,
+    images: Res<ImageAssets>,
+    asset_server: Res<AssetServer>,
+    levels: Res<Assets<Level>>,
+    mut pending: ResMut<PendingLevel>,
+    mut current: ResMut<CurrentLevel>,
+    mut action_stack: ResMut<ActionStack>,
+    mut placements: ResMut<Placements>,
+    tilemap_q: Query<(Entity, &TileStorage)>,
+) {
+    let (path, handle) = get_some!(pending.0.clone());
+
+    if asset_server.get_load_state(&handle) == LoadState::Failed {
+        error!("Could not load level {}", path);
+        pending.0 = None;
+        return;
+    }
+
+    let level = get_some!(levels.get(&handle));
+    pending.0 = None;
+
+    if !level.tiles.is_valid() {
+        error!("Level {} has a tile count that doesn't match its size", path);
+        return;
+    }
+
+    for (tilemap_entity, storage) in tilemap_q.iter() {
+        despawn_level(&mut commands, tilemap_entity, storage);
+    }
+    spawn_editor_level(&mut commands, &images, level);
+
+    *action_stack = ActionStack::default();
+    *placements = Placements::from_level(level);
+    current.name = level.name.clone();
+    current.author = level.author.clone();
+    current.path_input = path.clone();
+    current.path = Some(path);
+}
@@ -0,0 +1,31 @@
+use super::{
+    components::{LintReport, Placements},
+    file::snapshot_level,
+};
+use crate::{
+    editor::prelude::TileMaterial,
+    get_single,
+    level::{materials::Materials, validate::validate},
+};
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+/**
+ * Validate the map again whenever its tiles, size or placements change
+ */
+pub fn lint_level(
+    mut report: ResMut<LintReport>,
+    placements: Res<Placements>,
+    materials: Materials,
+    tilemap_q: Query<(&TileStorage, Ref<TilemapSize>)>,
+    changed_q: Query<(), Changed<TileMaterial>>,
+    tile_query: Query<&TileMaterial>,
+) {
+    let (storage, size) = get_single!(tilemap_q);
+    if !size.is_changed() && !placements.is_changed() && changed_q.is_empty() {
+        return;
+    }
+
+    let level = placements.level("", "", snapshot_level(storage, &size, &tile_query));
+    report.0 = validate(&level, materials.set());
+}
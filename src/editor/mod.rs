@@ -1,45 +1,113 @@
-use crate::{game::prelude::MainCamera, get_some, GameState, ImageAssets};
-use bevy::{math::Vec4Swizzles, prelude::*, reflect::Tuple, transform::commands};
-use bevy_ecs_tilemap::{
-    helpers::square_grid::neighbors::{self, Neighbors, SquareDirection},
-    prelude::*,
+use crate::{
+    game::prelude::{Dungeon, MainCamera},
+    get_single, get_some,
+    level::{
+        autotile::affected_positions,
+        materials::{MaterialSet, Materials},
+        tilemap::{despawn_level, respawn_tiles, spawn_level},
+        Level,
+    },
+    GameState, ImageAssets, LIGHT,
 };
+use bevy::{math::Vec4Swizzles, prelude::*, reflect::Tuple, transform::commands};
+use bevy_ecs_tilemap::prelude::*;
+use bevy_egui::EguiContexts;
+use std::collections::VecDeque;
 
 pub struct EditorPlugin;
 
 mod components;
 use components::*;
+mod file;
+use file::*;
+mod lint;
+use lint::*;
+mod placements;
+pub mod prelude;
+use placements::*;
+mod selection;
+use selection::*;
+mod shapes;
+use shapes::Footprint;
 mod ui;
 use ui::*;
 
 impl Plugin for EditorPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(GameState::InEditor), setup_blank_level)
+        app.add_systems(Startup, (load_stamp_library, load_tiled_table))
+            .add_systems(OnEnter(GameState::InEditor), setup_level)
             .add_event::<EditEvent>()
             .add_event::<UndoEditEvent>()
             .add_event::<RedoEditEvent>()
+            .add_event::<SaveLevelEvent>()
+            .add_event::<OpenLevelEvent>()
+            .add_event::<ClipboardEvent>()
+            .add_event::<NewLevelEvent>()
+            .add_event::<ResizeLevelEvent>()
+            .add_event::<PlayTestEvent>()
             .add_systems(
                 Update,
                 (
+                    hover_tile,
                     key_input,
+                    clipboard_keys,
                     toggle_game_mode,
                     tile_click,
+                    shape_tool,
+                    selection_tool,
+                    clipboard_action,
+                    paste_tool,
+                    placement_tool,
+                    object_tool,
+                    resize_level,
                     add_edit_actions,
                     undo_edit_action,
                     redo_edit_action,
+                    // Material changes must be applied before the board is redrawn
+                    apply_deferred,
                     update_board,
+                    draw_placements,
+                    lint_level,
                 )
                     .chain()
                     .run_if(in_state(GameState::InEditor)),
             )
             .add_systems(
                 Update,
-                (brush_panel_ui, editor_indicator_ui, brush_mode_ui)
+                (save_level, open_level, apply_loaded_level, new_level)
+                    .chain()
+                    .run_if(in_state(GameState::InEditor)),
+            )
+            .add_systems(
+                Update,
+                (
+                    brush_panel_ui,
+                    editor_indicator_ui,
+                    brush_mode_ui,
+                    level_file_ui,
+                    shape_preview,
+                    selection_preview,
+                    stamp_library_ui,
+                    map_size_ui,
+                    status_message_ui,
+                    generate_ui,
+                    lint_ui,
+                )
                     .run_if(in_state(GameState::InEditor)),
             )
             .add_systems(OnExit(GameState::InEditor), teardown)
             .insert_resource(RogBrush::default())
-            .insert_resource(ActionStack::default());
+            .insert_resource(ActionStack::default())
+            .insert_resource(CurrentLevel::default())
+            .init_resource::<DirtyTiles>()
+            .init_resource::<StatusMessage>()
+            .init_resource::<HoveredTile>()
+            .init_resource::<ShapeDrag>()
+            .init_resource::<Selection>()
+            .init_resource::<Clipboard>()
+            .init_resource::<Placements>()
+            .init_resource::<LintReport>()
+            .init_resource::<PendingLevel>();
     }
 }
 
@@ -47,7 +115,15 @@ pub fn key_input(
     keyboard: Res<Input<KeyCode>>,
     mut undo_edit_action: EventWriter<UndoEditEvent>,
     mut redo_edit_action: EventWriter<RedoEditEvent>,
+    mut brush: ResMut<RogBrush>,
 ) {
+    if keyboard.just_pressed(KeyCode::BracketLeft) {
+        brush.size = brush.size.saturating_sub(1).max(1);
+    }
+    if keyboard.just_pressed(KeyCode::BracketRight) {
+        brush.size = (brush.size + 1).min(MAX_BRUSH_SIZE);
+    }
+
     if keyboard.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]) {
         if keyboard.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
             if keyboard.just_released(KeyCode::Z) {
@@ -61,189 +137,293 @@ pub fn key_input(
     }
 }
 
+/**
+ * Play test the map as it is. The map is only played if it has exactly one player spawn.
+ */
+#[allow(clippy::too_many_arguments)]
 pub fn toggle_game_mode(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
     mut next_state: ResMut<NextState<GameState>>,
+    mut play_test_reader: EventReader<PlayTestEvent>,
+    mut status: ResMut<StatusMessage>,
     keyboard: Res<Input<KeyCode>>,
+    current: Res<CurrentLevel>,
+    placements: Res<Placements>,
+    tilemap_q: Query<(&TileStorage, &TilemapSize), With<EditorTilemap>>,
+    tile_query: Query<&TileMaterial>,
 ) {
-    if keyboard.just_released(KeyCode::Space) {
-        // next_state.set(GameState::InGame);
+    let typing = contexts.ctx_mut().wants_keyboard_input();
+    let play_test = play_test_reader.iter().count() > 0;
+    if !play_test && (typing || !keyboard.just_released(KeyCode::Space)) {
+        return;
+    }
+
+    let (storage, size) = get_single!(tilemap_q);
+    let level = placements.level(
+        &current.name,
+        &current.author,
+        snapshot_level(storage, size, &tile_query),
+    );
+
+    match level.player_spawn() {
+        Ok(_) => {
+            commands.insert_resource(Dungeon::single(level));
+            next_state.set(GameState::InGame);
+        }
+        Err(err) => status.show(err),
     }
 }
 
+/**
+ * Apply an action to the tilemap and return the actions that revert it, one per changed tile
+ */
 fn commit_action(
     commands: &mut Commands,
+    dirty: &mut DirtyTiles,
+    placements: &mut Placements,
+    tilemap_entity: Entity,
     storage: &TileStorage,
     tile_query: &Query<&TileMaterial>,
     action: &EditAction,
-) -> EditAction {
-    let undo: EditAction = match action {
+) -> Vec<EditAction> {
+    match action {
         EditAction::PlaceTile {
-            material, tile_pos, ..
-        } => {
-            storage.get(&tile_pos).map_or(action.clone(), |entity| {
+            material,
+            tile_pos,
+            size,
+            footprint,
+        } => footprint
+            .tiles(tile_pos, *size, &storage.size)
+            .into_iter()
+            .filter_map(|tile_pos| {
+                let entity = storage.get(&tile_pos)?;
                 match tile_query.get(entity) {
                     Ok(current_material) => {
-                        let undo = EditAction::PlaceTile {
-                            tile_pos: *tile_pos,
-                            material: current_material.clone(),
-                            size: 1,
-                        };
-
-                        // Mutation
-                        // index.0 = material_to_index(&material);
                         // We insert a material component on the entity. It will be consumed by the draw system
                         // and the texture index will be updated.
                         commands.entity(entity).insert(material.clone());
-                        undo
+                        dirty.mark(tile_pos);
+
+                        Some(EditAction::PlaceTile {
+                            tile_pos,
+                            material: current_material.clone(),
+                            size: 1,
+                            footprint: Footprint::Square,
+                        })
                     }
                     Err(err) => {
                         println!("Entity does not exist: {}", err);
 
-                        action.clone()
+                        None
                     }
                 }
             })
+            .collect(),
+        EditAction::ReplaceTiles { tiles } => {
+            let undo = EditAction::ReplaceTiles {
+                tiles: snapshot_level(storage, &storage.size, tile_query),
+            };
+            respawn_tiles(commands, tilemap_entity, storage, tiles, None);
+            dirty.mark_all();
+            dirty.replaced = true;
+
+            vec![undo]
         }
-        _ => action.clone(),
-    };
-
-    undo
+        EditAction::Place {
+            layer,
+            tile_pos,
+            name,
+        } => {
+            let layer_placements = placements.layer_mut(*layer);
+            let previous = match name {
+                Some(name) => layer_placements.insert(*tile_pos, name.clone()),
+                None => layer_placements.remove(tile_pos),
+            };
+
+            vec![EditAction::Place {
+                layer: *layer,
+                tile_pos: *tile_pos,
+                name: previous,
+            }]
+        }
+        EditAction::PlaceObject { tile_pos, object } => {
+            let previous = match object {
+                Some(object) => placements.objects.insert(*tile_pos, object.clone()),
+                None => placements.objects.remove(tile_pos),
+            };
+
+            vec![EditAction::PlaceObject {
+                tile_pos: *tile_pos,
+                object: previous,
+            }]
+        }
+    }
 }
 
+/**
+ * Redraw the tiles that changed and their neighbors. The whole map is redrawn when the tilemap
+ * is (re)spawned or the material definitions change.
+ */
 fn update_board(
-    tilemap_storage: Query<(&TileStorage, &TilemapSize)>,
-    mut tiles: Query<(Entity, &mut TileTextureIndex, &TilePos, &TileMaterial)>,
+    tilemap_storage: Query<Ref<TileStorage>>,
+    tile_query: Query<&TileMaterial>,
+    mut tiles: Query<(&mut TileTextureIndex, &mut TileColor)>,
+    mut dirty: ResMut<DirtyTiles>,
+    mut material_events: EventReader<AssetEvent<MaterialSet>>,
+    materials: Materials,
 ) {
-    let (map_storage, map_size) = tilemap_storage.single();
-
-    let mut tiles_to_update: Vec<TileMapIndex> = Vec::new();
-
-    for (_, _, tile_pos, material) in tiles.iter() {
-        match material {
-            TileMaterial::Wall => {
-                let neighbor_positions =
-                    Neighbors::get_square_neighboring_positions(&tile_pos, &map_size, false);
-                let neighbor_entities = neighbor_positions.entities(&map_storage);
-                let dirs = &[
-                    SquareDirection::North,
-                    SquareDirection::East,
-                    SquareDirection::South,
-                    SquareDirection::West,
-                ];
-                let dirs = dirs
-                    .iter()
-                    .map(|dir| {
-                        if let Some(entity) = neighbor_entities.get(dir.clone()) {
-                            if let Ok((_, _, _, material)) = tiles.get(*entity) {
-                                return match material {
-                                    TileMaterial::Wall => true,
-                                    TileMaterial::Floor => false,
-                                    TileMaterial::PlayerSpawn => false,
-                                };
-                            }
-                        }
-
-                        return false;
-                    })
-                    .collect::<Vec<_>>();
-
-                let tile_map_index = match dirs[..] {
-                    [false, true, true, true] => TileMapIndex::WallOXXX,
-                    [true, false, true, true] => TileMapIndex::WallXOXX,
-                    [false, true, true, false] => TileMapIndex::WallOXXO,
-                    [false, true, false, true] => TileMapIndex::WallOXOX,
-                    [false, false, true, true] => TileMapIndex::WallOOXX,
-                    [true, true, true, true] => TileMapIndex::WallXXXX,
-                    [true, true, true, false] => TileMapIndex::WallXXXO,
-                    [true, true, false, true] => TileMapIndex::WallXXOX,
-                    [false, false, false, true] => TileMapIndex::WallOOOX,
-                    [true, false, true, false] => TileMapIndex::WallXOXO,
-                    [false, false, false, false] => TileMapIndex::WallOXOX, // no connections
-                    [true, false, false, false] => TileMapIndex::WallXOOO,
-                    [false, false, true, false] => TileMapIndex::WallOOXO,
-                    [true, true, false, false] => TileMapIndex::WallXXOO,
-                    [false, true, false, false] => TileMapIndex::WallOXOO,
-                    [true, false, false, true] => TileMapIndex::WallXOOX,
-                    _ => TileMapIndex::Floor,
-                };
-
-                tiles_to_update.push(tile_map_index.clone());
-            }
-            TileMaterial::Floor => {
-                tiles_to_update.push(TileMapIndex::Floor);
-            }
-            TileMaterial::PlayerSpawn => {
-                tiles_to_update.push(TileMapIndex::PlayerSpawn);
-            }
-        }
+    // Commands were applied before this system, so the tile entities can be found again
+    dirty.replaced = false;
+    let map_storage = get_single!(tilemap_storage);
+    let material_set = get_some!(materials.set());
+
+    if map_storage.is_changed() || material_events.iter().count() > 0 {
+        dirty.mark_all();
     }
 
-    tiles
-        .iter_mut()
-        .enumerate()
-        .for_each(|(idx, (_, mut index, ..))| {
-            index.0 = tiles_to_update[idx].clone() as u32;
+    let positions: Vec<TilePos> = if dirty.all {
+        (0..map_storage.size.y)
+            .flat_map(|y| (0..map_storage.size.x).map(move |x| TilePos { x, y }))
+            .collect()
+    } else {
+        affected_positions(dirty.positions.iter(), &map_storage.size)
+            .into_iter()
+            .collect()
+    };
+    dirty.all = false;
+    dirty.positions.clear();
+
+    for tile_pos in positions {
+        let entity = match map_storage.checked_get(&tile_pos) {
+            Some(entity) => entity,
+            None => continue,
+        };
+        let material = match tile_query.get(entity) {
+            Ok(material) => material,
+            Err(_) => continue,
+        };
+
+        let index = material_set.tile_index(material, |dx, dy| {
+            let (x, y) = (tile_pos.x as i32 + dx, tile_pos.y as i32 + dy);
+            if x < 0 || y < 0 {
+                return None;
+            }
+
+            map_storage
+                .checked_get(&TilePos {
+                    x: x as u32,
+                    y: y as u32,
+                })
+                .and_then(|entity| tile_query.get(entity).ok())
         });
+
+        if let Ok((mut texture_index, mut color)) = tiles.get_mut(entity) {
+            texture_index.0 = index;
+            color.0 = material_set.tint(material);
+        }
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn redo_edit_action(
     mut commands: Commands,
     mut action_stack: ResMut<ActionStack>,
     mut redo_action_reader: EventReader<RedoEditEvent>,
-    tilemap_storage: Query<&TileStorage>,
+    mut dirty: ResMut<DirtyTiles>,
+    mut placements: ResMut<Placements>,
+    mut held: Local<usize>,
+    tilemap_storage: Query<(Entity, &TileStorage)>,
     tile_query: Query<&TileMaterial>,
 ) {
-    redo_action_reader.iter().for_each(|_| {
+    let mut redos = *held + redo_action_reader.iter().count();
+    // Redos after a tile replace wait until the respawned tiles can be found next frame
+    while redos > 0 && !dirty.replaced {
+        redos -= 1;
         if let Some(actions) = action_stack.redo() {
-            let storage = tilemap_storage.single();
+            let (tilemap_entity, storage) = tilemap_storage.single();
             actions.iter().for_each(|action| {
-                let _ = commit_action(&mut commands, storage, &tile_query, &action);
+                let _ = commit_action(
+                    &mut commands,
+                    &mut dirty,
+                    &mut placements,
+                    tilemap_entity,
+                    storage,
+                    &tile_query,
+                    &action,
+                );
             })
         }
-    });
+    }
+    *held = redos;
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn undo_edit_action(
     mut commands: Commands,
     mut action_stack: ResMut<ActionStack>,
     mut undo_action_reader: EventReader<UndoEditEvent>,
-    mut tilemap_storage: Query<&TileStorage>,
-    mut tile_query: Query<&TileMaterial>,
+    mut dirty: ResMut<DirtyTiles>,
+    mut placements: ResMut<Placements>,
+    mut held: Local<usize>,
+    tilemap_storage: Query<(Entity, &TileStorage)>,
+    tile_query: Query<&TileMaterial>,
 ) {
-    undo_action_reader.iter().for_each(|_| {
+    let mut undos = *held + undo_action_reader.iter().count();
+    // Undos after a tile replace wait until the respawned tiles can be found next frame
+    while undos > 0 && !dirty.replaced {
+        undos -= 1;
         if let Some(actions) = action_stack.undo() {
-            let storage = tilemap_storage.single_mut();
+            let (tilemap_entity, storage) = tilemap_storage.single();
             actions.iter().for_each(|action| {
-                let _ = commit_action(&mut commands, storage, &tile_query, &action);
+                let _ = commit_action(
+                    &mut commands,
+                    &mut dirty,
+                    &mut placements,
+                    tilemap_entity,
+                    storage,
+                    &tile_query,
+                    &action,
+                );
             });
         }
-    });
+    }
+    *held = undos;
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn add_edit_actions(
     mut commands: Commands,
     mut action_stack: ResMut<ActionStack>,
     mut add_action_reader: EventReader<EditEvent>,
-    tilemap_storage: Query<&TileStorage>,
+    mut dirty: ResMut<DirtyTiles>,
+    mut placements: ResMut<Placements>,
+    mut held: Local<VecDeque<EditEvent>>,
+    tilemap_storage: Query<(Entity, &TileStorage)>,
     tile_query: Query<&TileMaterial>,
 ) {
-    add_action_reader.iter().for_each(|EditEvent(actions)| {
-        let undos: Vec<EditAction> =
-            actions
-                .iter()
-                .fold(Vec::new(), |mut undos, action| match action {
-                    EditAction::PlaceTile { .. } => {
-                        let storage = tilemap_storage.single();
-                        let undo = commit_action(&mut commands, storage, &tile_query, &action);
-
-                        undos.push(undo);
-
-                        undos
-                    }
-                    _ => undos,
-                });
-        action_stack.push(actions.clone(), undos);
-    });
+    held.extend(add_action_reader.iter().cloned());
+    // Edits after a tile replace wait until the respawned tiles can be found next frame
+    while !dirty.replaced {
+        let EditEvent(actions) = get_some!(held.pop_front());
+        let (tilemap_entity, storage) = tilemap_storage.single();
+        let undos: Vec<EditAction> = actions.iter().fold(Vec::new(), |mut undos, action| {
+            let undo = commit_action(
+                &mut commands,
+                &mut dirty,
+                &mut placements,
+                tilemap_entity,
+                storage,
+                &tile_query,
+                &action,
+            );
+
+            undos.extend(undo);
+
+            undos
+        });
+        action_stack.push(actions, undos);
+    }
 }
 
 pub fn tile_click(
@@ -258,6 +438,7 @@ pub fn tile_click(
         &TileStorage,
         &Transform,
     )>,
+    tile_query: Query<&TileMaterial>,
     mut add_edit_action: EventWriter<EditEvent>,
     stack: Res<ActionStack>,
     brush: Res<RogBrush>,
@@ -269,6 +450,12 @@ pub fn tile_click(
         return;
     }
 
+    // Shapes, selections and pasting are handled by their own tools
+    match brush.mode {
+        BrushMode::Paint | BrushMode::Fill => {}
+        _ => return,
+    }
+
     let world_position = get_some!(window
         .cursor_position()
         .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor)));
@@ -277,26 +464,38 @@ pub fn tile_click(
 
     // Do this for each tilemap. Might have more in the future (like a minimap)
     for (size, grid_size, map_type, storage, transform) in tilemap_q.iter() {
-        let cursor_in_map_pos: Vec2 = {
-            let cursor_pos = Vec4::from((cursor_pos, 1.0));
-            let cursor_in_map_pos = transform.compute_matrix().inverse() * cursor_pos;
-            cursor_in_map_pos.xyz()
-        }
-        .truncate();
-
-        let tile_pos = get_some!(TilePos::from_world_pos(
-            &cursor_in_map_pos,
-            size,
-            grid_size,
-            map_type
+        let tile_pos = get_some!(cursor_to_tile_pos(
+            cursor_pos, size, grid_size, map_type, transform
         ));
 
+        // Replace the connected region of the clicked material
+        if brush.mode == BrushMode::Fill {
+            let tiles = snapshot_level(storage, size, &tile_query);
+            if tiles.get(&tile_pos) == Some(&brush.material) {
+                return;
+            }
+
+            let actions = tiles
+                .region(&tile_pos, brush.connectivity == Connectivity::Eight)
+                .into_iter()
+                .map(|tile_pos| EditAction::PlaceTile {
+                    material: brush.material.clone(),
+                    tile_pos,
+                    size: 1,
+                    footprint: Footprint::Square,
+                })
+                .collect::<Vec<_>>();
+            add_edit_action.send(EditEvent(actions));
+            return;
+        }
+
         // Single tile placement
         if !keyboard.pressed(KeyCode::ShiftLeft) {
             add_edit_action.send(EditEvent(vec![EditAction::PlaceTile {
                 material: brush.material.clone(),
                 tile_pos,
                 size: brush.size,
+                footprint: brush.footprint,
             }]));
             return;
         }
@@ -323,6 +522,7 @@ pub fn tile_click(
                     material: brush.material.clone(),
                     tile_pos,
                     size: brush.size,
+                    footprint: brush.footprint,
                 });
             }
             if ltile_pos.x > tile_pos.x {
@@ -335,6 +535,7 @@ pub fn tile_click(
                     material: brush.material.clone(),
                     tile_pos,
                     size: brush.size,
+                    footprint: brush.footprint,
                 });
             }
             if ltile_pos.y > tile_pos.y {
@@ -346,51 +547,212 @@ pub fn tile_click(
     }
 }
 
-pub fn setup_blank_level(mut commands: Commands, images: Res<ImageAssets>) {
-    let map_size = TilemapSize { x: 32, y: 32 };
-
-    let mut tile_storage = TileStorage::empty(map_size);
-    let tilemap_entity = commands.spawn_empty().id();
-
-    let tile_size = TilemapTileSize { x: 8.0, y: 8.0 };
-    let grid_size = tile_size.into();
-    let map_type = TilemapType::default();
-
-    for x in 0..map_size.x {
-        for y in 0..map_size.y {
-            let tile_pos = TilePos { x, y };
-            let tile_entity = commands
-                .spawn((
-                    Name::new("Tile"),
-                    TileBundle {
-                        position: tile_pos,
-                        tilemap_id: TilemapId(tilemap_entity),
-                        texture_index: TileTextureIndex(32),
-                        ..default()
-                    },
-                    TileMaterial::Floor,
-                ))
-                .id();
-            tile_storage.set(&tile_pos, tile_entity);
+/**
+ * Keep track of which tile the cursor is over so tools don't each have to work it out
+ */
+pub fn hover_tile(
+    windows: Query<&Window>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    tilemap_q: Query<(&TilemapSize, &TilemapGridSize, &TilemapType, &Transform)>,
+    mut hovered: ResMut<HoveredTile>,
+) {
+    let window = windows.single();
+    let (camera, camera_transform) = camera_q.single();
+
+    hovered.0 = tilemap_q
+        .get_single()
+        .ok()
+        .and_then(|(size, grid_size, map_type, transform)| {
+            window
+                .cursor_position()
+                .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+                .and_then(|world_position| {
+                    cursor_to_tile_pos(
+                        world_position.extend(1.0),
+                        size,
+                        grid_size,
+                        map_type,
+                        transform,
+                    )
+                })
+        });
+}
+
+fn cursor_to_tile_pos(
+    cursor_pos: Vec3,
+    size: &TilemapSize,
+    grid_size: &TilemapGridSize,
+    map_type: &TilemapType,
+    transform: &Transform,
+) -> Option<TilePos> {
+    let cursor_in_map_pos: Vec2 = {
+        let cursor_pos = Vec4::from((cursor_pos, 1.0));
+        let cursor_in_map_pos = transform.compute_matrix().inverse() * cursor_pos;
+        cursor_in_map_pos.xyz()
+    }
+    .truncate();
+
+    TilePos::from_world_pos(&cursor_in_map_pos, size, grid_size, map_type)
+}
+
+/**
+ * Drag out a shape while the mouse is held and commit it as a single edit on release
+ */
+pub fn shape_tool(
+    mouse_btn: Res<Input<MouseButton>>,
+    hovered: Res<HoveredTile>,
+    mut drag: ResMut<ShapeDrag>,
+    mut add_edit_action: EventWriter<EditEvent>,
+    brush: Res<RogBrush>,
+) {
+    let shape = match brush.mode {
+        BrushMode::Shape(shape) => shape,
+        _ => {
+            drag.start = None;
+            drag.end = None;
+            return;
+        }
+    };
+
+    let tile_pos = hovered.0;
+    if mouse_btn.just_pressed(MouseButton::Left) {
+        drag.start = tile_pos;
+    }
+    if mouse_btn.pressed(MouseButton::Left) && tile_pos.is_some() {
+        drag.end = tile_pos;
+    }
+
+    if mouse_btn.just_released(MouseButton::Left) {
+        if let (Some(start), Some(end)) = (drag.start.take(), drag.end.take()) {
+            let actions = shape
+                .tiles(&start, &end)
+                .into_iter()
+                .map(|tile_pos| EditAction::PlaceTile {
+                    material: brush.material.clone(),
+                    tile_pos,
+                    size: brush.size,
+                    footprint: brush.footprint,
+                })
+                .collect();
+            add_edit_action.send(EditEvent(actions));
         }
     }
+}
 
-    commands.entity(tilemap_entity).insert((
-        Name::new("Tilemap"),
-        TilemapBundle {
-            grid_size,
-            map_type,
-            size: map_size,
-            storage: tile_storage,
-            texture: TilemapTexture::Single(images.tilemap_image.clone()),
-            tile_size,
-            transform: get_tilemap_center_transform(&map_size, &grid_size, &map_type, 0.0),
-            ..Default::default()
-        },
-    ));
+/**
+ * Outline the tiles of the shape being dragged
+ */
+pub fn shape_preview(
+    mut gizmos: Gizmos,
+    drag: Res<ShapeDrag>,
+    brush: Res<RogBrush>,
+    tilemap_q: Query<(&TilemapGridSize, &TilemapType, &Transform)>,
+) {
+    let shape = match brush.mode {
+        BrushMode::Shape(shape) => shape,
+        _ => return,
+    };
+    let (start, end) = match (drag.start, drag.end) {
+        (Some(start), Some(end)) => (start, end),
+        _ => return,
+    };
+    let (grid_size, map_type, transform) = get_single!(tilemap_q);
+
+    for tile_pos in shape.tiles(&start, &end) {
+        let center = transform
+            .transform_point(tile_pos.center_in_world(grid_size, map_type).extend(0.0))
+            .truncate();
+        gizmos.rect_2d(
+            center,
+            0.0,
+            Vec2::new(grid_size.x, grid_size.y) * transform.scale.truncate(),
+            LIGHT,
+        );
+    }
 }
 
-pub fn teardown() {}
+/**
+ * Show the map that was being edited before play testing, or start with a blank one
+ */
+pub fn setup_level(
+    mut commands: Commands,
+    images: Res<ImageAssets>,
+    mut placements: ResMut<Placements>,
+    mut tilemap_q: Query<&mut Visibility, With<EditorTilemap>>,
+) {
+    if tilemap_q.is_empty() {
+        spawn_editor_level(&mut commands, &images, &Level::blank(32, 32));
+    }
+    for mut visibility in tilemap_q.iter_mut() {
+        *visibility = Visibility::Inherited;
+    }
+    // Bring back the placement sprites removed on exit
+    placements.set_changed();
+}
+
+pub fn spawn_editor_level(commands: &mut Commands, images: &ImageAssets, level: &Level) {
+    let tilemap_entity = spawn_level(commands, images, level, None);
+    commands.entity(tilemap_entity).insert(EditorTilemap);
+}
+
+pub fn new_level(
+    mut commands: Commands,
+    images: Res<ImageAssets>,
+    mut new_level_reader: EventReader<NewLevelEvent>,
+    mut current: ResMut<CurrentLevel>,
+    mut action_stack: ResMut<ActionStack>,
+    mut placements: ResMut<Placements>,
+    tilemap_q: Query<(Entity, &TileStorage)>,
+) {
+    let NewLevelEvent { width, height } = get_some!(new_level_reader.iter().last()).clone();
+
+    for (tilemap_entity, storage) in tilemap_q.iter() {
+        despawn_level(&mut commands, tilemap_entity, storage);
+    }
+    spawn_editor_level(&mut commands, &images, &Level::blank(width, height));
+
+    *action_stack = ActionStack::default();
+    *current = CurrentLevel::default();
+    *placements = Placements::default();
+}
+
+/**
+ * Turn a resize request into an undoable edit of the whole map
+ */
+pub fn resize_level(
+    mut resize_reader: EventReader<ResizeLevelEvent>,
+    mut add_edit_action: EventWriter<EditEvent>,
+    tilemap_q: Query<(&TileStorage, &TilemapSize)>,
+    tile_query: Query<&TileMaterial>,
+) {
+    for event in resize_reader.iter() {
+        let (storage, size) = get_single!(tilemap_q);
+        let tiles = snapshot_level(storage, size, &tile_query).resized(
+            event.width,
+            event.height,
+            event.anchor_x,
+            event.anchor_y,
+            TileMaterial::Floor,
+        );
+        add_edit_action.send(EditEvent(vec![EditAction::ReplaceTiles { tiles }]));
+    }
+}
+
+/**
+ * Keep the map around but out of sight while play testing, so editing can resume where it left off
+ */
+pub fn teardown(
+    mut commands: Commands,
+    mut tilemap_q: Query<&mut Visibility, With<EditorTilemap>>,
+    markers: Query<Entity, With<PlacementMarker>>,
+) {
+    for mut visibility in tilemap_q.iter_mut() {
+        *visibility = Visibility::Hidden;
+    }
+    for entity in markers.iter() {
+        commands.entity(entity).despawn();
+    }
+}
 
 // /**
 //  * lage en et eget set med plasserbare tiles for tomrom, vegger, gulv, etc.
@@ -411,3 +773,95 @@ pub fn teardown() {}
 // ADD_ACTION_EVENT(Action) Action { TARGET, PREVIOUS_VALUE, NEW_VALUE}
 // POP_ACTION_EVENT() // tar siste action og reverserer den ved å sette TARGET til previous value
 //  */
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::{tilemap::spawn_tiles, Grid};
+    use bevy::ecs::system::CommandQueue;
+
+    /**
+     * Clears the flag once commands are applied, like `update_board` does
+     */
+    fn clear_replaced(mut dirty: ResMut<DirtyTiles>) {
+        dirty.replaced = false;
+    }
+
+    fn edit_app(width: u32, height: u32) -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_event::<EditEvent>()
+            .add_event::<UndoEditEvent>()
+            .add_event::<RedoEditEvent>()
+            .insert_resource(ActionStack::default())
+            .init_resource::<DirtyTiles>()
+            .init_resource::<Placements>()
+            .add_systems(
+                Update,
+                (
+                    add_edit_actions,
+                    undo_edit_action,
+                    redo_edit_action,
+                    apply_deferred,
+                    clear_replaced,
+                )
+                    .chain(),
+            );
+
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &app.world);
+        let tilemap_entity = commands.spawn_empty().id();
+        let tiles = Grid::new(width, height, TileMaterial::Floor);
+        let storage = spawn_tiles(&mut commands, tilemap_entity, &tiles, None);
+        commands.entity(tilemap_entity).insert(storage);
+        queue.apply(&mut app.world);
+        app
+    }
+
+    fn map_size(app: &mut App) -> (u32, u32) {
+        let size = app.world.query::<&TileStorage>().single(&app.world).size;
+        (size.x, size.y)
+    }
+
+    fn material_at(app: &mut App, tile_pos: TilePos) -> TileMaterial {
+        let storage = app.world.query::<&TileStorage>().single(&app.world);
+        let entity = storage.get(&tile_pos).unwrap();
+        app.world.get::<TileMaterial>(entity).unwrap().clone()
+    }
+
+    #[test]
+    fn edits_after_a_replace_wait_for_the_new_tiles() {
+        let mut app = edit_app(3, 3);
+        let corner = TilePos { x: 4, y: 3 };
+        let replace = EditAction::ReplaceTiles {
+            tiles: Grid::new(5, 4, TileMaterial::Floor),
+        };
+        let wall = EditAction::PlaceTile {
+            tile_pos: corner,
+            material: TileMaterial::Wall,
+            size: 1,
+            footprint: Footprint::Square,
+        };
+        app.world.send_event(EditEvent(vec![replace]));
+        app.world.send_event(EditEvent(vec![wall]));
+
+        app.update();
+        assert_eq!(map_size(&mut app), (5, 4));
+        app.update();
+        assert_eq!(material_at(&mut app, corner), TileMaterial::Wall);
+
+        // Undoing both in one frame takes the map back to where it started
+        app.world.send_event(UndoEditEvent);
+        app.world.send_event(UndoEditEvent);
+        app.update();
+        assert_eq!(map_size(&mut app), (3, 3));
+
+        // Redoing both needs a frame for the replace and one for the wall after it
+        app.world.send_event(RedoEditEvent);
+        app.world.send_event(RedoEditEvent);
+        app.update();
+        assert_eq!(map_size(&mut app), (5, 4));
+        assert_eq!(material_at(&mut app, corner), TileMaterial::Floor);
+        app.update();
+        assert_eq!(material_at(&mut app, corner), TileMaterial::Wall);
+    }
+}
@@ -0,0 +1,197 @@
+use super::components::{
+    BrushMode, EditAction, EditEvent, EditorTilemap, HoveredTile, PlacementLayer, PlacementMarker,
+    Placements, RogBrush,
+};
+use crate::{
+    effects::{Effect, EffectEvent},
+    get_single, get_some,
+    level::{items::Items, monsters::Monsters, tilemap::tile_to_world},
+    ImageAssets,
+};
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use std::collections::HashSet;
+
+/// Markers of fresh placements pop up to this scale
+const PLACED_POP_SCALE: f32 = 1.5;
+
+/**
+ * Place the selected monster or item on the clicked tile, or clear the tile with the right
+ * button
+ */
+pub fn placement_tool(
+    mouse_btn: Res<Input<MouseButton>>,
+    hovered: Res<HoveredTile>,
+    brush: Res<RogBrush>,
+    placements: Res<Placements>,
+    mut add_edit_action: EventWriter<EditEvent>,
+) {
+    let (layer, selected) = match &brush.mode {
+        BrushMode::Place(layer, name) => (*layer, name),
+        _ => return,
+    };
+    let tile_pos = get_some!(hovered.0);
+
+    let name = if mouse_btn.just_released(MouseButton::Left) {
+        Some(selected.clone())
+    } else if mouse_btn.just_released(MouseButton::Right) {
+        None
+    } else {
+        return;
+    };
+    if placements.layer(layer).get(&tile_pos) == name.as_ref() {
+        return;
+    }
+
+    add_edit_action.send(EditEvent(vec![EditAction::Place {
+        layer,
+        tile_pos,
+        name,
+    }]));
+}
+
+/**
+ * Place the configured door or chest on the clicked tile, or clear the tile with the right
+ * button. Shift clicking an object picks up its configuration so it can be edited and placed
+ * again.
+ */
+pub fn object_tool(
+    mouse_btn: Res<Input<MouseButton>>,
+    keyboard: Res<Input<KeyCode>>,
+    hovered: Res<HoveredTile>,
+    mut brush: ResMut<RogBrush>,
+    placements: Res<Placements>,
+    mut add_edit_action: EventWriter<EditEvent>,
+) {
+    let selected = match &brush.mode {
+        BrushMode::Object(object) => object.clone(),
+        _ => return,
+    };
+    let tile_pos = get_some!(hovered.0);
+    let current = placements.objects.get(&tile_pos);
+
+    let object = if mouse_btn.just_released(MouseButton::Left) {
+        if keyboard.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+            if let Some(object) = current {
+                brush.mode = BrushMode::Object(object.clone());
+            }
+            return;
+        }
+        Some(selected)
+    } else if mouse_btn.just_released(MouseButton::Right) {
+        None
+    } else {
+        return;
+    };
+    if current == object.as_ref() {
+        return;
+    }
+
+    add_edit_action.send(EditEvent(vec![EditAction::PlaceObject {
+        tile_pos,
+        object,
+    }]));
+}
+
+/**
+ * Respawn the placement sprites when the placements change or the board moves
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn draw_placements(
+    mut commands: Commands,
+    images: Res<ImageAssets>,
+    monsters: Monsters,
+    items: Items,
+    placements: Res<Placements>,
+    tilemap_q: Query<(Ref<Transform>, &TilemapSize), With<EditorTilemap>>,
+    markers: Query<Entity, With<PlacementMarker>>,
+    mut edit_reader: EventReader<EditEvent>,
+    mut effect_writer: EventWriter<EffectEvent>,
+) {
+    // Tiles that just got something placed on them, which pop up when they're redrawn
+    let placed: HashSet<TilePos> = edit_reader
+        .iter()
+        .flat_map(|EditEvent(actions)| actions.iter())
+        .filter_map(|action| match action {
+            EditAction::Place {
+                tile_pos,
+                name: Some(_),
+                ..
+            } => Some(*tile_pos),
+            EditAction::PlaceObject {
+                tile_pos,
+                object: Some(_),
+            } => Some(*tile_pos),
+            _ => None,
+        })
+        .collect();
+
+    let (transform, size) = get_single!(tilemap_q);
+    if !placements.is_changed() && !transform.is_changed() {
+        return;
+    }
+
+    for entity in markers.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    let mut spawn_marker = |tile_pos: &TilePos, sprite_index: usize, color: Color, z: f32| {
+        let marker = commands.spawn((
+            Name::new("Placement Marker"),
+            SpriteSheetBundle {
+                texture_atlas: images.image_atlas.clone(),
+                sprite: TextureAtlasSprite {
+                    index: sprite_index,
+                    color,
+                    ..default()
+                },
+                transform: Transform::from_translation(
+                    tile_to_world(tile_pos, &transform) + Vec3::Z * z,
+                ),
+                ..default()
+            },
+            PlacementMarker,
+        ));
+        if placed.contains(tile_pos) {
+            let pop = Effect::Scale {
+                to: Vec3::splat(PLACED_POP_SCALE),
+                duration: 0.08,
+            };
+            let settle = Effect::Scale {
+                to: Vec3::ONE,
+                duration: 0.12,
+            };
+            effect_writer.send(EffectEvent::sequence(marker.id(), vec![pop, settle]));
+        }
+    };
+
+    for placement in placements.object_placements(size) {
+        spawn_marker(
+            &placement.tile_pos(),
+            placement.object.sprite_index(),
+            placement.object.tint(),
+            0.5,
+        );
+    }
+
+    for (layer, z) in [(PlacementLayer::Item, 1.0), (PlacementLayer::Monster, 2.0)] {
+        for placement in placements.placements(layer, size) {
+            let sprite = match layer {
+                PlacementLayer::Monster => monsters
+                    .set()
+                    .and_then(|set| set.get(&placement.name))
+                    .map(|def| (def.sprite_index, Color::WHITE)),
+                PlacementLayer::Item => items
+                    .set()
+                    .and_then(|set| set.get(&placement.name))
+                    .map(|def| (def.sprite_index, def.tint())),
+            };
+            match sprite {
+                Some((sprite_index, color)) => {
+                    spawn_marker(&placement.tile_pos(), sprite_index, color, z)
+                }
+                None => warn!("Unknown {:?} {}", layer, placement.name),
+            }
+        }
+    }
+}
@@ -0,0 +1,2 @@
+// stuff you want to export to other mods
+pub use super::components::TileMaterial;
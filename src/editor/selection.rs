@@ -0,0 +1,179 @@
+use super::{
+    components::{
+        BrushMode, Clipboard, ClipboardEvent, EditAction, EditEvent, HoveredTile, RogBrush,
+        Selection, StampLibrary, TileMaterial,
+    },
+    file::snapshot_level,
+    shapes::{rectangle, Footprint},
+};
+use crate::{get_single, get_some, level::load_asset_json, LIGHT};
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+pub const STAMP_LIBRARY_PATH: &str = "stamps.json";
+
+pub fn load_stamp_library(mut commands: Commands) {
+    let library = load_asset_json(STAMP_LIBRARY_PATH).unwrap_or_else(|err| {
+        info!("No stamp library loaded: {}", err);
+        StampLibrary::default()
+    });
+    commands.insert_resource(library);
+}
+
+pub fn clipboard_keys(keyboard: Res<Input<KeyCode>>, mut clipboard: EventWriter<ClipboardEvent>) {
+    if !keyboard.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]) {
+        return;
+    }
+
+    for (key, event) in [
+        (KeyCode::C, ClipboardEvent::Copy),
+        (KeyCode::X, ClipboardEvent::Cut),
+        (KeyCode::V, ClipboardEvent::Paste),
+        (KeyCode::R, ClipboardEvent::Rotate),
+        (KeyCode::M, ClipboardEvent::Mirror),
+    ] {
+        if keyboard.just_released(key) {
+            clipboard.send(event);
+        }
+    }
+}
+
+pub fn selection_tool(
+    mouse_btn: Res<Input<MouseButton>>,
+    hovered: Res<HoveredTile>,
+    brush: Res<RogBrush>,
+    mut selection: ResMut<Selection>,
+) {
+    if brush.mode != BrushMode::Select {
+        return;
+    }
+
+    if mouse_btn.just_pressed(MouseButton::Left) {
+        selection.start = hovered.0;
+        selection.end = hovered.0;
+    }
+    if mouse_btn.pressed(MouseButton::Left) && hovered.0.is_some() {
+        selection.end = hovered.0;
+    }
+}
+
+pub fn clipboard_action(
+    mut clipboard_reader: EventReader<ClipboardEvent>,
+    mut add_edit_action: EventWriter<EditEvent>,
+    mut clipboard: ResMut<Clipboard>,
+    mut brush: ResMut<RogBrush>,
+    selection: Res<Selection>,
+    tilemap_q: Query<(&TileStorage, &TilemapSize)>,
+    tile_query: Query<&TileMaterial>,
+) {
+    for event in clipboard_reader.iter() {
+        match event {
+            ClipboardEvent::Copy | ClipboardEvent::Cut => {
+                let (origin, width, height) = get_some!(selection.bounds());
+                let (storage, size) = get_single!(tilemap_q);
+                let tiles = snapshot_level(storage, size, &tile_query);
+                let copied = match tiles.crop(&origin, width, height) {
+                    Some(copied) => copied,
+                    // Keep the old clipboard if the selection runs off the map
+                    None => continue,
+                };
+                clipboard.0 = Some(copied);
+
+                if *event == ClipboardEvent::Cut {
+                    let end = TilePos {
+                        x: origin.x + width - 1,
+                        y: origin.y + height - 1,
+                    };
+                    let actions = rectangle(&origin, &end)
+                        .into_iter()
+                        .map(|tile_pos| EditAction::PlaceTile {
+                            material: TileMaterial::Floor,
+                            tile_pos,
+                            size: 1,
+                            footprint: Footprint::Square,
+                        })
+                        .collect();
+                    add_edit_action.send(EditEvent(actions));
+                }
+            }
+            ClipboardEvent::Paste => {
+                if clipboard.0.is_some() {
+                    brush.mode = BrushMode::Paste;
+                }
+            }
+            ClipboardEvent::Rotate => {
+                clipboard.0 = clipboard.0.as_ref().map(|tiles| tiles.rotated());
+            }
+            ClipboardEvent::Mirror => {
+                clipboard.0 = clipboard.0.as_ref().map(|tiles| tiles.mirrored());
+            }
+        }
+    }
+}
+
+/**
+ * Place the clipboard with its bottom left corner on the clicked tile
+ */
+pub fn paste_tool(
+    mouse_btn: Res<Input<MouseButton>>,
+    hovered: Res<HoveredTile>,
+    brush: Res<RogBrush>,
+    clipboard: Res<Clipboard>,
+    mut add_edit_action: EventWriter<EditEvent>,
+) {
+    if brush.mode != BrushMode::Paste || !mouse_btn.just_released(MouseButton::Left) {
+        return;
+    }
+
+    let origin = get_some!(hovered.0);
+    let tiles = get_some!(clipboard.0.as_ref());
+    let actions = tiles
+        .iter()
+        .map(|(pos, material)| EditAction::PlaceTile {
+            material: material.clone(),
+            tile_pos: TilePos {
+                x: origin.x + pos.x,
+                y: origin.y + pos.y,
+            },
+            size: 1,
+            footprint: Footprint::Square,
+        })
+        .collect();
+    add_edit_action.send(EditEvent(actions));
+}
+
+/**
+ * Outline the selection, or the clipboard under the cursor while pasting
+ */
+pub fn selection_preview(
+    mut gizmos: Gizmos,
+    brush: Res<RogBrush>,
+    selection: Res<Selection>,
+    clipboard: Res<Clipboard>,
+    hovered: Res<HoveredTile>,
+    tilemap_q: Query<(&TilemapGridSize, &TilemapType, &Transform)>,
+) {
+    let (origin, width, height) = match brush.mode {
+        BrushMode::Paste => {
+            let tiles = get_some!(clipboard.0.as_ref());
+            (get_some!(hovered.0), tiles.width(), tiles.height())
+        }
+        _ => get_some!(selection.bounds()),
+    };
+    let (grid_size, map_type, transform) = get_single!(tilemap_q);
+
+    let end = TilePos {
+        x: origin.x + width - 1,
+        y: origin.y + height - 1,
+    };
+    let center = (origin.center_in_world(grid_size, map_type)
+        + end.center_in_world(grid_size, map_type))
+        / 2.0;
+    gizmos.rect_2d(
+        transform.transform_point(center.extend(0.0)).truncate(),
+        0.0,
+        Vec2::new(width as f32 * grid_size.x, height as f32 * grid_size.y)
+            * transform.scale.truncate(),
+        LIGHT,
+    );
+}
@@ -0,0 +1,338 @@
+use bevy_ecs_tilemap::{map::TilemapSize, tiles::TilePos};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shape {
+    Rectangle,
+    HollowRectangle,
+    Line,
+    Ellipse,
+}
+
+impl Shape {
+    /**
+     * The tiles covered by the shape when dragged from `start` to `end`
+     */
+    pub fn tiles(&self, start: &TilePos, end: &TilePos) -> Vec<TilePos> {
+        match self {
+            Shape::Rectangle => rectangle(start, end),
+            Shape::HollowRectangle => hollow_rectangle(start, end),
+            Shape::Line => line(start, end),
+            Shape::Ellipse => ellipse(start, end),
+        }
+    }
+}
+
+/// The stamp a brush of a given size paints around the clicked tile
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Footprint {
+    Square,
+    Circle,
+}
+
+impl Footprint {
+    /**
+     * The tiles covered by a brush of `size` centered on `center`, clipped to the map. Even
+     * sizes have one more tile towards positive x and y.
+     */
+    pub fn tiles(&self, center: &TilePos, size: usize, map_size: &TilemapSize) -> Vec<TilePos> {
+        let size = size.max(1) as i32;
+        let start = -(size - 1) / 2;
+        let mid = (size - 1) as f32 / 2.0;
+        // Shrink the radius a little so small circles don't turn into squares
+        let radius = size as f32 / 2.0 - 0.25;
+
+        let mut tiles = Vec::new();
+        for dy in 0..size {
+            for dx in 0..size {
+                if *self == Footprint::Circle {
+                    let (fx, fy) = (dx as f32 - mid, dy as f32 - mid);
+                    if fx * fx + fy * fy > radius * radius {
+                        continue;
+                    }
+                }
+
+                let x = center.x as i32 + start + dx;
+                let y = center.y as i32 + start + dy;
+                if x >= 0 && y >= 0 && (x as u32) < map_size.x && (y as u32) < map_size.y {
+                    tiles.push(TilePos {
+                        x: x as u32,
+                        y: y as u32,
+                    });
+                }
+            }
+        }
+
+        tiles
+    }
+}
+
+fn bounds(start: &TilePos, end: &TilePos) -> (u32, u32, u32, u32) {
+    (
+        start.x.min(end.x),
+        start.y.min(end.y),
+        start.x.max(end.x),
+        start.y.max(end.y),
+    )
+}
+
+pub fn rectangle(start: &TilePos, end: &TilePos) -> Vec<TilePos> {
+    let (x0, y0, x1, y1) = bounds(start, end);
+
+    (y0..=y1)
+        .flat_map(|y| (x0..=x1).map(move |x| TilePos { x, y }))
+        .collect()
+}
+
+pub fn hollow_rectangle(start: &TilePos, end: &TilePos) -> Vec<TilePos> {
+    let (x0, y0, x1, y1) = bounds(start, end);
+
+    rectangle(start, end)
+        .into_iter()
+        .filter(|pos| pos.x == x0 || pos.x == x1 || pos.y == y0 || pos.y == y1)
+        .collect()
+}
+
+/**
+ * Bresenham line, ordered from start to end
+ */
+pub fn line(start: &TilePos, end: &TilePos) -> Vec<TilePos> {
+    let (mut x, mut y) = (start.x as i32, start.y as i32);
+    let (x1, y1) = (end.x as i32, end.y as i32);
+    let dx = (x1 - x).abs();
+    let dy = -(y1 - y).abs();
+    let sx = if x < x1 { 1 } else { -1 };
+    let sy = if y < y1 { 1 } else { -1 };
+    let mut err = dx + dy;
+
+    let mut tiles = Vec::new();
+    loop {
+        tiles.push(TilePos {
+            x: x as u32,
+            y: y as u32,
+        });
+        if x == x1 && y == y1 {
+            break;
+        }
+
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+
+    tiles
+}
+
+/**
+ * Outline of the ellipse inscribed in the dragged rectangle. A tile is part of the outline
+ * when it is inside the ellipse but has a cardinal neighbor outside of it.
+ */
+pub fn ellipse(start: &TilePos, end: &TilePos) -> Vec<TilePos> {
+    let (x0, y0, x1, y1) = bounds(start, end);
+    let cx = (x0 + x1) as f32 / 2.0;
+    let cy = (y0 + y1) as f32 / 2.0;
+    let rx = (x1 - x0) as f32 / 2.0 + 0.5;
+    let ry = (y1 - y0) as f32 / 2.0 + 0.5;
+
+    let inside = |x: i32, y: i32| {
+        let nx = (x as f32 - cx) / rx;
+        let ny = (y as f32 - cy) / ry;
+        nx * nx + ny * ny <= 1.0
+    };
+
+    rectangle(start, end)
+        .into_iter()
+        .filter(|pos| {
+            let (x, y) = (pos.x as i32, pos.y as i32);
+            inside(x, y)
+                && (!inside(x + 1, y)
+                    || !inside(x - 1, y)
+                    || !inside(x, y + 1)
+                    || !inside(x, y - 1))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coords(tiles: Vec<TilePos>) -> Vec<(u32, u32)> {
+        tiles.iter().map(|pos| (pos.x, pos.y)).collect()
+    }
+
+    fn drag(shape: Shape, start: (u32, u32), end: (u32, u32)) -> Vec<(u32, u32)> {
+        let start = TilePos {
+            x: start.0,
+            y: start.1,
+        };
+        let end = TilePos { x: end.0, y: end.1 };
+        coords(shape.tiles(&start, &end))
+    }
+
+    #[test]
+    fn single_tile_shapes() {
+        for shape in [
+            Shape::Rectangle,
+            Shape::HollowRectangle,
+            Shape::Line,
+            Shape::Ellipse,
+        ] {
+            assert_eq!(drag(shape, (2, 3), (2, 3)), vec![(2, 3)], "{:?}", shape);
+        }
+    }
+
+    #[test]
+    fn rectangles_from_any_corner() {
+        let filled = vec![(0, 0), (1, 0), (2, 0), (0, 1), (1, 1), (2, 1)];
+        let cases = [((0, 0), (2, 1)), ((2, 1), (0, 0)), ((0, 1), (2, 0))];
+        for (start, end) in cases {
+            assert_eq!(drag(Shape::Rectangle, start, end), filled);
+        }
+    }
+
+    #[test]
+    fn hollow_rectangles() {
+        let cases = [
+            // Only the two middle tiles are left out
+            (
+                (3, 2),
+                (0, 0),
+                vec![
+                    (0, 0),
+                    (1, 0),
+                    (2, 0),
+                    (3, 0),
+                    (0, 1),
+                    (3, 1),
+                    (0, 2),
+                    (1, 2),
+                    (2, 2),
+                    (3, 2),
+                ],
+            ),
+            // Too thin to have an inside
+            ((0, 0), (1, 1), vec![(0, 0), (1, 0), (0, 1), (1, 1)]),
+            ((0, 0), (0, 2), vec![(0, 0), (0, 1), (0, 2)]),
+        ];
+        for (start, end, expected) in cases {
+            assert_eq!(drag(Shape::HollowRectangle, start, end), expected);
+        }
+    }
+
+    #[test]
+    fn lines() {
+        let cases = [
+            ((0, 0), (3, 0), vec![(0, 0), (1, 0), (2, 0), (3, 0)]),
+            ((0, 0), (2, 2), vec![(0, 0), (1, 1), (2, 2)]),
+            ((0, 0), (4, 2), vec![(0, 0), (1, 1), (2, 1), (3, 2), (4, 2)]),
+            // Steep lines step along y
+            ((0, 0), (1, 4), vec![(0, 0), (0, 1), (1, 2), (1, 3), (1, 4)]),
+            ((1, 4), (0, 0), vec![(1, 4), (1, 3), (0, 2), (0, 1), (0, 0)]),
+        ];
+        for (start, end, expected) in cases {
+            assert_eq!(drag(Shape::Line, start, end), expected);
+        }
+    }
+
+    #[test]
+    fn lines_have_no_gaps() {
+        let ends = [(0, 0), (7, 2), (2, 7), (7, 7), (0, 5), (5, 0)];
+        for start in ends {
+            for end in ends {
+                let tiles = drag(Shape::Line, start, end);
+                assert_eq!(tiles.first(), Some(&start));
+                assert_eq!(tiles.last(), Some(&end));
+                for pair in tiles.windows(2) {
+                    let (a, b) = (pair[0], pair[1]);
+                    assert!(a.0.abs_diff(b.0) <= 1 && a.1.abs_diff(b.1) <= 1);
+                    assert_ne!(a, b);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ellipse_outline() {
+        // . # # # .
+        // # . . . #
+        // . # # # .
+        let expected = vec![
+            (1, 0),
+            (2, 0),
+            (3, 0),
+            (0, 1),
+            (4, 1),
+            (1, 2),
+            (2, 2),
+            (3, 2),
+        ];
+        assert_eq!(drag(Shape::Ellipse, (0, 0), (4, 2)), expected);
+        assert_eq!(drag(Shape::Ellipse, (4, 0), (0, 2)), expected);
+    }
+
+    fn stamp(footprint: Footprint, center: (u32, u32), size: usize) -> Vec<(u32, u32)> {
+        let center = TilePos {
+            x: center.0,
+            y: center.1,
+        };
+        coords(footprint.tiles(&center, size, &TilemapSize { x: 10, y: 10 }))
+    }
+
+    #[test]
+    fn square_footprints() {
+        let cases = [
+            (0, vec![(5, 5)]),
+            (1, vec![(5, 5)]),
+            // Even sizes lean towards positive x and y
+            (2, vec![(5, 5), (6, 5), (5, 6), (6, 6)]),
+            (
+                3,
+                vec![
+                    (4, 4),
+                    (5, 4),
+                    (6, 4),
+                    (4, 5),
+                    (5, 5),
+                    (6, 5),
+                    (4, 6),
+                    (5, 6),
+                    (6, 6),
+                ],
+            ),
+        ];
+        for (size, expected) in cases {
+            assert_eq!(stamp(Footprint::Square, (5, 5), size), expected);
+        }
+    }
+
+    #[test]
+    fn circle_footprints() {
+        let cases = [
+            (1, vec![(5, 5)]),
+            (2, vec![(5, 5), (6, 5), (5, 6), (6, 6)]),
+            // Size 3 drops the corners
+            (3, vec![(5, 4), (4, 5), (5, 5), (6, 5), (5, 6)]),
+        ];
+        for (size, expected) in cases {
+            assert_eq!(stamp(Footprint::Circle, (5, 5), size), expected);
+        }
+        assert_eq!(stamp(Footprint::Circle, (5, 5), 5).len(), 21);
+    }
+
+    #[test]
+    fn footprints_are_clipped_to_the_map() {
+        let corner = vec![(0, 0), (1, 0), (0, 1), (1, 1)];
+        assert_eq!(stamp(Footprint::Square, (0, 0), 3), corner);
+        assert_eq!(stamp(Footprint::Square, (9, 9), 2), vec![(9, 9)]);
+        assert_eq!(
+            stamp(Footprint::Circle, (0, 9), 3),
+            vec![(0, 8), (0, 9), (1, 9)]
+        );
+    }
+}
@@ -1,12 +1,34 @@
-use bevy::prelude::ResMut;
+use crate::level::{
+    generate::{bsp::BspParams, wfc::FixedTile, Generator},
+    items::{ItemSet, Items},
+    materials::Materials,
+    monsters::Monsters,
+    objects::{KeyColor, TileObject},
+    validate::Severity,
+    Anchor,
+};
+use crate::{game::prelude::MainCamera, get_single, get_single_mut, get_some};
+use bevy::prelude::{
+    error, info, EventWriter, Local, Query, Res, ResMut, Time, Transform, With, Without,
+};
+use bevy_ecs_tilemap::prelude::{TileStorage, TilemapGridSize, TilemapSize, TilemapType};
 use bevy_egui::{
     egui::{self, style, Align2, Color32, FontData, FontDefinitions, FontFamily, FontId, RichText},
     EguiContexts, EguiSettings,
 };
+use bevy_turborand::{DelegatedRng, GlobalRng};
 
-use super::components::{RogBrush, TileMaterial};
+use super::components::{
+    BrushMode, Clipboard, ClipboardEvent, Connectivity, CurrentLevel, EditAction, EditEvent,
+    LintReport, NewLevelEvent, OpenLevelEvent, PlacementLayer, PlayTestEvent, ResizeLevelEvent,
+    RogBrush, SaveLevelEvent, Selection, Stamp, StampLibrary, StatusMessage, TileMaterial,
+    MAX_BRUSH_SIZE,
+};
+use super::file::snapshot_level;
+use super::selection::STAMP_LIBRARY_PATH;
+use super::shapes::{Footprint, Shape};
 
-pub fn editor_indicator_ui(mut contexts: EguiContexts) {
+pub fn editor_indicator_ui(mut contexts: EguiContexts, mut play_test: EventWriter<PlayTestEvent>) {
     egui::Area::new("Indicator")
         .anchor(Align2::CENTER_TOP, egui::emath::vec2(10., 5.))
         .show(contexts.ctx_mut(), |ui| {
@@ -16,10 +38,37 @@ pub fn editor_indicator_ui(mut contexts: EguiContexts) {
                         .font(FontId::proportional(24.))
                         .color(Color32::WHITE),
                 );
+                if ui.button("Play").clicked() {
+                    play_test.send(PlayTestEvent);
+                }
             });
         });
 }
 
+pub fn status_message_ui(
+    mut contexts: EguiContexts,
+    mut status: ResMut<StatusMessage>,
+    time: Res<Time>,
+) {
+    if status.text.is_none() {
+        return;
+    }
+
+    status.timer.tick(time.delta());
+    if status.timer.finished() {
+        status.text = None;
+        return;
+    }
+
+    egui::Area::new("Status")
+        .anchor(Align2::CENTER_TOP, egui::emath::vec2(0., 40.))
+        .show(contexts.ctx_mut(), |ui| {
+            if let Some(text) = &status.text {
+                ui.label(RichText::new(text).color(Color32::LIGHT_RED));
+            }
+        });
+}
+
 pub fn brush_mode_ui(mut contexts: EguiContexts, mut brush: ResMut<RogBrush>) {
     egui::Area::new("Brush")
         .anchor(Align2::CENTER_TOP, egui::emath::vec2(0., -50.))
@@ -33,27 +82,512 @@ pub fn brush_mode_ui(mut contexts: EguiContexts, mut brush: ResMut<RogBrush>) {
                 if ui.button("Eraser").clicked() {
                     brush.material = TileMaterial::Floor;
                 }
+                ui.separator();
+                ui.selectable_value(&mut brush.mode, BrushMode::Paint, "Paint");
+                ui.selectable_value(&mut brush.mode, BrushMode::Fill, "Fill");
+                if brush.mode == BrushMode::Fill {
+                    ui.selectable_value(&mut brush.connectivity, Connectivity::Four, "4");
+                    ui.selectable_value(&mut brush.connectivity, Connectivity::Eight, "8");
+                }
             });
         });
 }
 
-pub fn brush_panel_ui(mut contexts: EguiContexts, mut brush: ResMut<RogBrush>) {
+pub fn brush_panel_ui(
+    mut contexts: EguiContexts,
+    mut brush: ResMut<RogBrush>,
+    materials: Materials,
+    monsters: Monsters,
+    items: Items,
+) {
     egui::Area::new("Brush Panel")
         .anchor(Align2::RIGHT_TOP, egui::emath::vec2(0., 100.))
         .show(contexts.ctx_mut(), |ui| {
             ui.with_layout(egui::Layout::top_down(egui::Align::TOP), |ui| {
-                if ui.button("Spawn").clicked() {
-                    brush.material = TileMaterial::PlayerSpawn;
+                // The palette follows the material definitions and is rebuilt when they change
+                for def in materials.set().iter().flat_map(|set| set.materials.iter()) {
+                    let [r, g, b] = def.color;
+                    let color =
+                        Color32::from_rgb((r * 255.) as u8, (g * 255.) as u8, (b * 255.) as u8);
+                    let label = match &def.icon {
+                        Some(icon) => format!("{} {}", icon, def.name),
+                        None => def.name.clone(),
+                    };
+
+                    let material = TileMaterial::from(def.name.clone());
+                    if ui
+                        .selectable_label(
+                            brush.material == material,
+                            RichText::new(label).color(color),
+                        )
+                        .clicked()
+                    {
+                        brush.material = material;
+                        if let BrushMode::Place(..) | BrushMode::Object(..) = brush.mode {
+                            brush.mode = BrushMode::Paint;
+                        }
+                    }
                 }
 
-                if ui.button("Wall").clicked() {
-                    // todo: SetMaterial?
-                    brush.material = TileMaterial::Wall;
+                ui.separator();
+                let monster_names = monsters
+                    .set()
+                    .into_iter()
+                    .flat_map(|set| set.monsters.iter().map(|def| def.name.as_str()));
+                let item_names = items
+                    .set()
+                    .into_iter()
+                    .flat_map(|set| set.items.iter().map(|def| def.name.as_str()));
+                for (layer, names) in [
+                    (PlacementLayer::Monster, monster_names.collect::<Vec<_>>()),
+                    (PlacementLayer::Item, item_names.collect()),
+                ] {
+                    ui.horizontal_wrapped(|ui| {
+                        for name in names {
+                            ui.selectable_value(
+                                &mut brush.mode,
+                                BrushMode::Place(layer, name.to_string()),
+                                name,
+                            );
+                        }
+                    });
                 }
-                // todo: SetMaterial?
-                if ui.button("Eraser").clicked() {
-                    brush.material = TileMaterial::Floor;
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    for object in [TileObject::door(), TileObject::chest()] {
+                        let selected = match &brush.mode {
+                            BrushMode::Object(current) => current.name() == object.name(),
+                            _ => false,
+                        };
+                        if ui.selectable_label(selected, object.name()).clicked() && !selected {
+                            brush.mode = BrushMode::Object(object);
+                        }
+                    }
+                });
+                if let BrushMode::Object(object) = &mut brush.mode {
+                    object_config_ui(ui, object, items.set());
+                }
+
+                ui.separator();
+                ui.add(egui::Slider::new(&mut brush.size, 1..=MAX_BRUSH_SIZE).text("Size"));
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut brush.footprint, Footprint::Square, "Square");
+                    ui.selectable_value(&mut brush.footprint, Footprint::Circle, "Circle");
+                });
+
+                ui.separator();
+                for (shape, label) in [
+                    (Shape::Rectangle, "Rect"),
+                    (Shape::HollowRectangle, "Room"),
+                    (Shape::Line, "Line"),
+                    (Shape::Ellipse, "Ellipse"),
+                ] {
+                    ui.selectable_value(&mut brush.mode, BrushMode::Shape(shape), label);
+                }
+            })
+        });
+}
+
+/**
+ * Settings of the door or chest about to be placed
+ */
+fn object_config_ui(ui: &mut egui::Ui, object: &mut TileObject, items: Option<&ItemSet>) {
+    ui.label("Shift click an object to edit it");
+    match object {
+        TileObject::Door { open, lock } => {
+            ui.checkbox(open, "Open");
+            egui::ComboBox::from_label("Lock")
+                .selected_text(lock.map_or("None".to_string(), |color| format!("{:?}", color)))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(lock, None, "None");
+                    for color in KeyColor::ALL {
+                        ui.selectable_value(lock, Some(color), format!("{:?}", color));
+                    }
+                });
+        }
+        TileObject::Chest { loot, .. } => {
+            let mut remove = None;
+            for (idx, name) in loot.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(name);
+                    if ui.small_button("x").clicked() {
+                        remove = Some(idx);
+                    }
+                });
+            }
+            if let Some(idx) = remove {
+                loot.remove(idx);
+            }
+
+            egui::ComboBox::from_label("Loot")
+                .selected_text("Add")
+                .show_ui(ui, |ui| {
+                    for def in items.iter().flat_map(|set| set.items.iter()) {
+                        if ui.selectable_label(false, &def.name).clicked() {
+                            loot.push(def.name.clone());
+                        }
+                    }
+                });
+        }
+    }
+}
+
+pub fn level_file_ui(
+    mut contexts: EguiContexts,
+    mut current: ResMut<CurrentLevel>,
+    mut save_level: EventWriter<SaveLevelEvent>,
+    mut open_level: EventWriter<OpenLevelEvent>,
+) {
+    egui::Area::new("Level File")
+        .anchor(Align2::LEFT_TOP, egui::emath::vec2(5., 5.))
+        .show(contexts.ctx_mut(), |ui| {
+            ui.with_layout(egui::Layout::top_down(egui::Align::LEFT), |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Name");
+                    ui.text_edit_singleline(&mut current.name);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Author");
+                    ui.text_edit_singleline(&mut current.author);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Path");
+                    ui.text_edit_singleline(&mut current.path_input);
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Save").clicked() {
+                        save_level.send(SaveLevelEvent::Save);
+                    }
+                    if ui.button("Save As").clicked() {
+                        save_level.send(SaveLevelEvent::SaveAs(current.path_input.clone()));
+                    }
+                    if ui.button("Open").clicked() {
+                        open_level.send(OpenLevelEvent(current.path_input.clone()));
+                    }
+                });
+            })
+        });
+}
+
+pub fn stamp_library_ui(
+    mut contexts: EguiContexts,
+    mut brush: ResMut<RogBrush>,
+    mut clipboard: ResMut<Clipboard>,
+    mut library: ResMut<StampLibrary>,
+    mut clipboard_action: EventWriter<ClipboardEvent>,
+    mut stamp_name: Local<String>,
+) {
+    egui::Area::new("Stamps")
+        .anchor(Align2::LEFT_BOTTOM, egui::emath::vec2(5., -5.))
+        .show(contexts.ctx_mut(), |ui| {
+            ui.with_layout(egui::Layout::top_down(egui::Align::LEFT), |ui| {
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut brush.mode, BrushMode::Select, "Select");
+                    for (event, label) in [
+                        (ClipboardEvent::Copy, "Copy"),
+                        (ClipboardEvent::Cut, "Cut"),
+                        (ClipboardEvent::Paste, "Paste"),
+                        (ClipboardEvent::Rotate, "Rotate"),
+                        (ClipboardEvent::Mirror, "Mirror"),
+                    ] {
+                        if ui.button(label).clicked() {
+                            clipboard_action.send(event);
+                        }
+                    }
+                });
+
+                let mut use_stamp = None;
+                let mut remove_stamp = None;
+                for (idx, stamp) in library.stamps.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        if ui.button(&stamp.name).clicked() {
+                            use_stamp = Some(idx);
+                        }
+                        if ui.small_button("x").clicked() {
+                            remove_stamp = Some(idx);
+                        }
+                    });
+                }
+                if let Some(idx) = use_stamp {
+                    clipboard.0 = Some(library.stamps[idx].tiles.clone());
+                    brush.mode = BrushMode::Paste;
+                }
+                if let Some(idx) = remove_stamp {
+                    library.stamps.remove(idx);
+                }
+
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut *stamp_name);
+                    if ui.button("Add Stamp").clicked() && !stamp_name.is_empty() {
+                        if let Some(tiles) = clipboard.0.clone() {
+                            library.stamps.push(Stamp {
+                                name: stamp_name.clone(),
+                                tiles,
+                            });
+                            stamp_name.clear();
+                        }
+                    }
+                    if ui.button("Save Stamps").clicked() {
+                        match library.save(STAMP_LIBRARY_PATH) {
+                            Ok(_) => info!("Saved stamp library"),
+                            Err(err) => error!("Could not save stamp library: {}", err),
+                        }
+                    }
+                });
+            })
+        });
+}
+
+pub struct MapSizeInput {
+    width: u32,
+    height: u32,
+    anchor_x: Anchor,
+    anchor_y: Anchor,
+}
+
+impl Default for MapSizeInput {
+    fn default() -> Self {
+        Self {
+            width: 32,
+            height: 32,
+            anchor_x: Anchor::Center,
+            anchor_y: Anchor::Center,
+        }
+    }
+}
+
+pub const MAX_MAP_SIZE: u32 = 512;
+
+pub fn map_size_ui(
+    mut contexts: EguiContexts,
+    mut input: Local<MapSizeInput>,
+    mut new_level: EventWriter<NewLevelEvent>,
+    mut resize_level: EventWriter<ResizeLevelEvent>,
+    tilemap_q: Query<&TilemapSize>,
+) {
+    egui::Area::new("Map Size")
+        .anchor(Align2::RIGHT_BOTTOM, egui::emath::vec2(-5., -5.))
+        .show(contexts.ctx_mut(), |ui| {
+            ui.with_layout(egui::Layout::top_down(egui::Align::RIGHT), |ui| {
+                if let Ok(size) = tilemap_q.get_single() {
+                    ui.label(format!("Map {}x{}", size.x, size.y));
                 }
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut input.width).clamp_range(1..=MAX_MAP_SIZE));
+                    ui.label("x");
+                    ui.add(egui::DragValue::new(&mut input.height).clamp_range(1..=MAX_MAP_SIZE));
+                });
+
+                // Anchor picker, laid out like the map with the top row first
+                egui::Grid::new("Anchor").show(ui, |ui| {
+                    for anchor_y in [Anchor::End, Anchor::Center, Anchor::Start] {
+                        for anchor_x in [Anchor::Start, Anchor::Center, Anchor::End] {
+                            let selected = input.anchor_x == anchor_x && input.anchor_y == anchor_y;
+                            if ui.selectable_label(selected, "o").clicked() {
+                                input.anchor_x = anchor_x;
+                                input.anchor_y = anchor_y;
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    if ui.button("New Level").clicked() {
+                        new_level.send(NewLevelEvent {
+                            width: input.width,
+                            height: input.height,
+                        });
+                    }
+                    if ui.button("Resize").clicked() {
+                        resize_level.send(ResizeLevelEvent {
+                            width: input.width,
+                            height: input.height,
+                            anchor_x: input.anchor_x,
+                            anchor_y: input.anchor_y,
+                        });
+                    }
+                });
             })
         });
 }
+
+pub struct GenerateInput {
+    generator: Generator,
+    seed: u64,
+}
+
+impl Default for GenerateInput {
+    fn default() -> Self {
+        Self {
+            generator: Generator::Bsp(BspParams::default()),
+            seed: 0,
+        }
+    }
+}
+
+/**
+ * Replace the map with a generated one of the same size. It's a regular edit, so it can be
+ * undone or touched up.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn generate_ui(
+    mut contexts: EguiContexts,
+    mut input: Local<GenerateInput>,
+    mut global_rng: ResMut<GlobalRng>,
+    mut add_edit_action: EventWriter<EditEvent>,
+    mut status: ResMut<StatusMessage>,
+    selection: Res<Selection>,
+    tilemap_q: Query<(&TileStorage, &TilemapSize)>,
+    tile_query: Query<&TileMaterial>,
+) {
+    // The tiles of the current selection, for generators that build around them
+    let selected_tiles = || match (selection.bounds(), tilemap_q.get_single()) {
+        (Some((origin, width, height)), Ok((storage, size))) => {
+            snapshot_level(storage, size, &tile_query)
+                .iter()
+                .filter(|(pos, _)| {
+                    (origin.x..origin.x + width).contains(&pos.x)
+                        && (origin.y..origin.y + height).contains(&pos.y)
+                })
+                .map(|(pos, material)| FixedTile::new(material, &pos))
+                .collect::<Vec<_>>()
+        }
+        _ => Vec::new(),
+    };
+
+    egui::Window::new("Generate")
+        .anchor(Align2::LEFT_TOP, egui::emath::vec2(5., 110.))
+        .default_open(false)
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            egui::ComboBox::from_label("Generator")
+                .selected_text(input.generator.name())
+                .show_ui(ui, |ui| {
+                    for generator in Generator::all() {
+                        let selected = generator.name() == input.generator.name();
+                        if ui.selectable_label(selected, generator.name()).clicked() && !selected {
+                            input.generator = generator;
+                        }
+                    }
+                });
+            generator_params_ui(ui, &mut input.generator, selected_tiles);
+
+            ui.horizontal(|ui| {
+                ui.label("Seed");
+                ui.add(egui::DragValue::new(&mut input.seed));
+                if ui.button("Random").clicked() {
+                    input.seed = global_rng.u64(..);
+                }
+            });
+
+            if ui.button("Generate").clicked() {
+                if let Ok((_, size)) = tilemap_q.get_single() {
+                    match input.generator.generate(size.x, size.y, input.seed) {
+                        Ok(tiles) => add_edit_action
+                            .send(EditEvent(vec![EditAction::ReplaceTiles { tiles }])),
+                        Err(err) => {
+                            error!("Couldn't generate a level: {}", err);
+                            status.show(&err);
+                        }
+                    }
+                }
+            }
+        });
+}
+
+fn generator_params_ui(
+    ui: &mut egui::Ui,
+    generator: &mut Generator,
+    selected_tiles: impl FnOnce() -> Vec<FixedTile>,
+) {
+    match generator {
+        Generator::Bsp(params) => {
+            ui.add(egui::Slider::new(&mut params.min_leaf, 3..=32).text("Min area"));
+            ui.add(egui::Slider::new(&mut params.min_room, 1..=16).text("Min room"));
+            ui.add(egui::Slider::new(&mut params.max_depth, 1..=8).text("Splits"));
+        }
+        Generator::Cave(params) => {
+            ui.add(egui::Slider::new(&mut params.fill, 0.3..=0.7).text("Fill"));
+            ui.add(egui::Slider::new(&mut params.iterations, 0..=10).text("Smoothing"));
+        }
+        Generator::Walk(params) => {
+            ui.add(egui::Slider::new(&mut params.coverage, 0.05..=0.9).text("Coverage"));
+        }
+        Generator::Wfc(params) => {
+            ui.horizontal(|ui| {
+                ui.label("Example");
+                ui.text_edit_singleline(&mut params.example);
+            });
+            ui.add(egui::Slider::new(&mut params.pattern_size, 2..=4).text("Pattern size"));
+            ui.checkbox(&mut params.symmetry, "Rotate and mirror");
+            ui.horizontal(|ui| {
+                ui.label("Max backtracks");
+                ui.add(egui::DragValue::new(&mut params.max_backtracks));
+            });
+            ui.horizontal(|ui| {
+                ui.label(format!("{} fixed tiles", params.fixed.len()));
+                if ui.button("Fix selection").clicked() {
+                    params.fixed = selected_tiles();
+                }
+                if ui.button("Clear").clicked() {
+                    params.fixed.clear();
+                }
+            });
+        }
+    }
+}
+
+/**
+ * List what's wrong with the map. Clicking a problem selects its tile and moves the camera
+ * there.
+ */
+pub fn lint_ui(
+    mut contexts: EguiContexts,
+    report: Res<LintReport>,
+    mut selection: ResMut<Selection>,
+    tilemap_q: Query<(&TilemapGridSize, &TilemapType, &Transform), Without<MainCamera>>,
+    mut camera_q: Query<&mut Transform, With<MainCamera>>,
+) {
+    let mut jump = None;
+    egui::Window::new(format!("Problems ({})", report.0.len()))
+        .id(egui::Id::new("Problems"))
+        .anchor(Align2::CENTER_BOTTOM, egui::emath::vec2(0., -5.))
+        .default_open(false)
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            if report.0.is_empty() {
+                ui.label("No problems found");
+            }
+            egui::ScrollArea::vertical()
+                .max_height(150.)
+                .show(ui, |ui| {
+                    for problem in report.0.iter() {
+                        let color = match problem.severity {
+                            Severity::Error => Color32::LIGHT_RED,
+                            Severity::Warning => Color32::YELLOW,
+                        };
+                        let text = RichText::new(&problem.message).color(color);
+                        let clicked = match problem.tile_pos {
+                            Some(_) => ui.selectable_label(false, text).clicked(),
+                            None => ui.label(text).clicked(),
+                        };
+                        if clicked {
+                            jump = problem.tile_pos;
+                        }
+                    }
+                });
+        });
+
+    let tile_pos = get_some!(jump);
+    selection.start = Some(tile_pos);
+    selection.end = Some(tile_pos);
+
+    let (grid_size, map_type, map_transform) = get_single!(tilemap_q);
+    let mut camera = get_single_mut!(camera_q);
+    let center = tile_pos.center_in_world(grid_size, map_type);
+    let target = map_transform.transform_point(center.extend(0.0));
+    camera.translation.x = target.x;
+    camera.translation.y = target.y;
+}
@@ -0,0 +1,538 @@
+use crate::DARK;
+use bevy::{
+    prelude::*,
+    time::{Time, Timer},
+};
+use derive_more::From;
+use std::collections::VecDeque;
+
+/**
+ * Visual effects for sprites. Effects are started by sending an `EffectEvent`, and the effects
+ * of one event play one after the other.
+ */
+pub struct EffectsPlugin;
+impl Plugin for EffectsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<EffectEvent>().add_systems(
+            Update,
+            (
+                start_effects,
+                apply_deferred,
+                run_sequences,
+                apply_deferred,
+                (
+                    flick_system,
+                    shrink_system,
+                    darken_system,
+                    delayed_visibility_system,
+                    timed_removal_system,
+                    move_tween_system,
+                    scale_tween_system,
+                    color_tween_system,
+                ),
+            )
+                .chain(),
+        );
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Effect {
+    /// Blink, switching visibility every `switch` seconds
+    Flick {
+        duration: f32,
+        switch: f32,
+    },
+    Shrink {
+        duration: f32,
+    },
+    /// Fade the sprite color into the background
+    Darken {
+        duration: f32,
+    },
+    /// Hide the sprite and show it again after a delay
+    Show {
+        delay: f32,
+    },
+    Despawn {
+        delay: f32,
+    },
+    Move {
+        to: Vec3,
+        duration: f32,
+    },
+    Scale {
+        to: Vec3,
+        duration: f32,
+    },
+    Color {
+        to: Color,
+        duration: f32,
+    },
+}
+
+/// Play effects on an entity, in order and after the effects already queued
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct EffectEvent {
+    pub entity: Entity,
+    pub effects: Vec<Effect>,
+    /// Start the first effect right away, replacing a running effect of the same kind
+    pub interrupt: bool,
+}
+
+impl EffectEvent {
+    pub fn new(entity: Entity, effect: Effect) -> Self {
+        Self::sequence(entity, vec![effect])
+    }
+
+    pub fn sequence(entity: Entity, effects: Vec<Effect>) -> Self {
+        Self {
+            entity,
+            effects,
+            interrupt: false,
+        }
+    }
+
+    /// For feedback that can't wait, like moving or getting hit
+    pub fn interrupt(entity: Entity, effect: Effect) -> Self {
+        Self {
+            entity,
+            effects: vec![effect],
+            interrupt: true,
+        }
+    }
+}
+
+/// Effects waiting for the running one to finish
+#[derive(Debug, Component, Default)]
+pub struct EffectSequence(pub VecDeque<Effect>);
+
+#[derive(Debug, Component, Default, From)]
+pub struct Flick {
+    pub switch_timer: Timer,
+    pub duration: Timer,
+}
+
+#[derive(Debug, Component)]
+pub struct TimedRemoval(pub Timer);
+
+#[derive(Debug, Component)]
+pub struct Darken(pub Timer);
+#[derive(Debug, Component)]
+pub struct Shrink(pub Timer);
+
+#[derive(Debug, Component)]
+pub struct DelayedVisibility(pub Timer);
+
+/// Tweens start from wherever the entity is when they first run
+#[derive(Debug, Component)]
+pub struct MoveTween {
+    pub from: Option<Vec3>,
+    pub to: Vec3,
+    pub timer: Timer,
+}
+
+#[derive(Debug, Component)]
+pub struct ScaleTween {
+    pub from: Option<Vec3>,
+    pub to: Vec3,
+    pub timer: Timer,
+}
+
+#[derive(Debug, Component)]
+pub struct ColorTween {
+    pub from: Option<Color>,
+    pub to: Color,
+    pub timer: Timer,
+}
+
+fn once(seconds: f32) -> Timer {
+    Timer::from_seconds(seconds, TimerMode::Once)
+}
+
+impl Effect {
+    fn start(&self, entity: &mut bevy::ecs::system::EntityCommands) {
+        match self {
+            Effect::Flick { duration, switch } => {
+                entity.insert(Flick {
+                    switch_timer: Timer::from_seconds(*switch, TimerMode::Repeating),
+                    duration: once(*duration),
+                });
+            }
+            Effect::Shrink { duration } => {
+                entity.insert(Shrink(once(*duration)));
+            }
+            Effect::Darken { duration } => {
+                entity.insert(Darken(once(*duration)));
+            }
+            Effect::Show { delay } => {
+                entity.insert((Visibility::Hidden, DelayedVisibility(once(*delay))));
+            }
+            Effect::Despawn { delay } => {
+                entity.insert(TimedRemoval(once(*delay)));
+            }
+            Effect::Move { to, duration } => {
+                entity.insert(MoveTween {
+                    from: None,
+                    to: *to,
+                    timer: once(*duration),
+                });
+            }
+            Effect::Scale { to, duration } => {
+                entity.insert(ScaleTween {
+                    from: None,
+                    to: *to,
+                    timer: once(*duration),
+                });
+            }
+            Effect::Color { to, duration } => {
+                entity.insert(ColorTween {
+                    from: None,
+                    to: *to,
+                    timer: once(*duration),
+                });
+            }
+        }
+    }
+}
+
+pub fn start_effects(
+    mut commands: Commands,
+    mut effect_reader: EventReader<EffectEvent>,
+    mut sequences: Query<&mut EffectSequence>,
+) {
+    for EffectEvent {
+        entity,
+        effects,
+        interrupt,
+    } in effect_reader.iter()
+    {
+        let mut effects = effects.iter().cloned();
+        if *interrupt {
+            if let (Some(effect), Some(mut entity_commands)) =
+                (effects.next(), commands.get_entity(*entity))
+            {
+                effect.start(&mut entity_commands);
+            }
+        }
+
+        if let Ok(mut sequence) = sequences.get_mut(*entity) {
+            sequence.0.extend(effects);
+        } else if let Some(mut entity_commands) = commands.get_entity(*entity) {
+            entity_commands.insert(EffectSequence(effects.collect()));
+        }
+    }
+}
+
+/**
+ * Start the next effect of every sequence that has nothing running
+ */
+#[allow(clippy::type_complexity)]
+pub fn run_sequences(
+    mut commands: Commands,
+    mut sequences: Query<
+        (Entity, &mut EffectSequence),
+        (
+            Without<Flick>,
+            Without<Shrink>,
+            Without<Darken>,
+            Without<DelayedVisibility>,
+            Without<TimedRemoval>,
+            Without<MoveTween>,
+            Without<ScaleTween>,
+            Without<ColorTween>,
+        ),
+    >,
+) {
+    for (entity, mut sequence) in sequences.iter_mut() {
+        match sequence.0.pop_front() {
+            Some(effect) => effect.start(&mut commands.entity(entity)),
+            None => {
+                commands.entity(entity).remove::<EffectSequence>();
+            }
+        }
+    }
+}
+
+pub fn flick_system(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Visibility, &mut Flick)>,
+    time: Res<Time>,
+) {
+    for (entity, mut visibility, mut flick) in query.iter_mut() {
+        flick.duration.tick(time.delta());
+        flick.switch_timer.tick(time.delta());
+
+        if flick.duration.finished() {
+            *visibility = Visibility::Inherited;
+            commands.entity(entity).remove::<Flick>();
+        } else if flick.switch_timer.just_finished() {
+            *visibility = match visibility.clone() {
+                Visibility::Inherited => Visibility::Hidden,
+                _ => Visibility::Inherited,
+            };
+        }
+    }
+}
+
+/**
+ * Shrink the component by a little every frame until the timer finishes
+ */
+pub fn shrink_system(
+    mut commands: Commands,
+    mut shrinking: Query<(Entity, &mut Transform, &mut Shrink)>,
+    time: Res<Time>,
+) {
+    for (entity, mut transform, mut shrink) in shrinking.iter_mut() {
+        shrink.0.tick(time.delta());
+
+        if shrink.0.finished() {
+            commands.entity(entity).remove::<Shrink>();
+        } else if transform.scale.x > 0.0 && transform.scale.y > 0.0 {
+            transform.scale *= 1.0 - (0.9 * time.delta_seconds());
+        }
+    }
+}
+
+/**
+ * Fade the sprite color towards the background so it reaches it when the timer finishes
+ */
+pub fn darken_system(
+    mut commands: Commands,
+    mut query: Query<(
+        Entity,
+        &mut Darken,
+        Option<&mut Sprite>,
+        Option<&mut TextureAtlasSprite>,
+    )>,
+    time: Res<Time>,
+) {
+    for (entity, mut darken, sprite, atlas_sprite) in query.iter_mut() {
+        let remaining = darken.0.remaining_secs();
+        darken.0.tick(time.delta());
+        let amount = if remaining > 0.0 {
+            (time.delta_seconds() / remaining).min(1.0)
+        } else {
+            1.0
+        };
+
+        if let Some(mut sprite) = sprite {
+            sprite.color = lerp_color(sprite.color, DARK, amount);
+        }
+        if let Some(mut sprite) = atlas_sprite {
+            sprite.color = lerp_color(sprite.color, DARK, amount);
+        }
+        if darken.0.finished() {
+            commands.entity(entity).remove::<Darken>();
+        }
+    }
+}
+
+pub fn timed_removal_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut TimedRemoval, Without<DelayedVisibility>)>,
+) {
+    for (entity, mut removal, _) in query.iter_mut() {
+        removal.0.tick(time.delta());
+
+        if removal.0.finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+pub fn delayed_visibility_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut DelayedVisibility, &mut Visibility)>,
+) {
+    for (entity, mut delay, mut visibility) in query.iter_mut() {
+        delay.0.tick(time.delta());
+
+        if delay.0.finished() {
+            commands.entity(entity).remove::<DelayedVisibility>();
+            *visibility = Visibility::Inherited;
+        }
+    }
+}
+
+pub fn move_tween_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Transform, &mut MoveTween)>,
+) {
+    for (entity, mut transform, mut tween) in query.iter_mut() {
+        let from = *tween.from.get_or_insert(transform.translation);
+        tween.timer.tick(time.delta());
+        transform.translation = from.lerp(tween.to, tween.timer.percent());
+
+        if tween.timer.finished() {
+            commands.entity(entity).remove::<MoveTween>();
+        }
+    }
+}
+
+pub fn scale_tween_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Transform, &mut ScaleTween)>,
+) {
+    for (entity, mut transform, mut tween) in query.iter_mut() {
+        let from = *tween.from.get_or_insert(transform.scale);
+        tween.timer.tick(time.delta());
+        transform.scale = from.lerp(tween.to, tween.timer.percent());
+
+        if tween.timer.finished() {
+            commands.entity(entity).remove::<ScaleTween>();
+        }
+    }
+}
+
+pub fn color_tween_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(
+        Entity,
+        &mut ColorTween,
+        Option<&mut Sprite>,
+        Option<&mut TextureAtlasSprite>,
+    )>,
+) {
+    for (entity, mut tween, sprite, atlas_sprite) in query.iter_mut() {
+        let current = match (&sprite, &atlas_sprite) {
+            (Some(sprite), _) => sprite.color,
+            (_, Some(sprite)) => sprite.color,
+            _ => continue,
+        };
+        let from = *tween.from.get_or_insert(current);
+        tween.timer.tick(time.delta());
+        let color = lerp_color(from, tween.to, tween.timer.percent());
+
+        if let Some(mut sprite) = sprite {
+            sprite.color = color;
+        }
+        if let Some(mut sprite) = atlas_sprite {
+            sprite.color = color;
+        }
+        if tween.timer.finished() {
+            commands.entity(entity).remove::<ColorTween>();
+        }
+    }
+}
+
+fn lerp_color(from: Color, to: Color, amount: f32) -> Color {
+    let rgba = Vec4::from(from.as_rgba_f32()).lerp(Vec4::from(to.as_rgba_f32()), amount);
+    Color::rgba(rgba.x, rgba.y, rgba.z, rgba.w)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::time::TimeUpdateStrategy;
+    use std::time::Duration;
+
+    /// Every frame moves time on by this much. Effect durations below are multiples of it that
+    /// f32 holds exactly, so timers finish on a known frame
+    const FRAME: Duration = Duration::from_millis(125);
+
+    fn effects_app() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, EffectsPlugin))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(FRAME));
+        // The first frame only starts the clock
+        app.update();
+        app
+    }
+
+    fn send(app: &mut App, event: EffectEvent) {
+        app.world.resource_mut::<Events<EffectEvent>>().send(event);
+    }
+
+    fn transform(app: &App, entity: Entity) -> Transform {
+        *app.world.get::<Transform>(entity).unwrap()
+    }
+
+    #[test]
+    fn sequence_plays_in_order() {
+        let mut app = effects_app();
+        let entity = app.world.spawn(Transform::default()).id();
+        let to = Vec3::new(10.0, 0.0, 0.0);
+        send(
+            &mut app,
+            EffectEvent::sequence(
+                entity,
+                vec![
+                    Effect::Move { to, duration: 0.5 },
+                    Effect::Scale {
+                        to: Vec3::splat(2.0),
+                        duration: 0.25,
+                    },
+                ],
+            ),
+        );
+
+        let mut moved_frames = 0;
+        let mut scaled_frames = 0;
+        for _ in 0..10 {
+            app.update();
+            let moving = app.world.get::<MoveTween>(entity).is_some();
+            let scaling = app.world.get::<ScaleTween>(entity).is_some();
+            assert!(!(moving && scaling), "Effects of a sequence overlap");
+            if moving {
+                moved_frames += 1;
+                assert_eq!(scaled_frames, 0, "The scale started before the move");
+            }
+            if scaling {
+                scaled_frames += 1;
+            }
+        }
+
+        // A tween is gone by the end of the frame it finishes on
+        assert_eq!((moved_frames, scaled_frames), (3, 1));
+        assert_eq!(transform(&app, entity).translation, to);
+        assert_eq!(transform(&app, entity).scale, Vec3::splat(2.0));
+        assert!(app.world.get::<EffectSequence>(entity).is_none());
+    }
+
+    #[test]
+    fn interrupt_cancels_running_effect() {
+        let mut app = effects_app();
+        let entity = app.world.spawn(Transform::default()).id();
+        let (slow_to, fast_to) = (Vec3::new(10.0, 0.0, 0.0), Vec3::new(0.0, 5.0, 0.0));
+        send(
+            &mut app,
+            EffectEvent::sequence(
+                entity,
+                vec![
+                    Effect::Move {
+                        to: slow_to,
+                        duration: 1.0,
+                    },
+                    Effect::Scale {
+                        to: Vec3::splat(2.0),
+                        duration: 0.1,
+                    },
+                ],
+            ),
+        );
+        app.update();
+        app.update();
+        assert!(transform(&app, entity).translation.x > 0.0);
+
+        let fast = Effect::Move {
+            to: fast_to,
+            duration: 0.25,
+        };
+        send(&mut app, EffectEvent::interrupt(entity, fast));
+        for _ in 0..5 {
+            app.update();
+        }
+
+        // The slow move never finishes, but what was queued after it still plays
+        let transform = transform(&app, entity);
+        assert_eq!(transform.translation, fast_to);
+        assert_eq!(transform.scale, Vec3::splat(2.0));
+        assert!(app.world.get::<MoveTween>(entity).is_none());
+    }
+}
@@ -0,0 +1,112 @@
+use super::{
+    components::{
+        Action, ActionEvent, Actor, CombatStats, DeathEvent, Drops, Equipment, GameOver, Health,
+        Player, Turn,
+    },
+    dungeon::Dungeon,
+};
+use crate::{
+    effects::{Effect, EffectEvent},
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_ecs_tilemap::tiles::TilePos;
+use bevy_turborand::{DelegatedRng, GlobalRng};
+
+/// How long a hit actor blinks, and how fast
+const HIT_FLICK_SECS: f32 = 0.4;
+const HIT_FLICK_SWITCH_SECS: f32 = 0.08;
+/// How long the dead take to shrink away
+const DEATH_SHRINK_SECS: f32 = 0.3;
+
+/**
+ * Roll damage for the attacks performed this frame. Rolls come from the seeded global
+ * generator in the order the scheduler performed the attacks, so fights replay the same.
+ */
+pub fn resolve_attacks(
+    mut global_rng: ResMut<GlobalRng>,
+    mut action_reader: EventReader<ActionEvent>,
+    mut effect_writer: EventWriter<EffectEvent>,
+    mut death_writer: EventWriter<DeathEvent>,
+    stats_q: Query<(&CombatStats, Option<&Equipment>)>,
+    mut target_q: Query<(&mut Health, &TilePos, Option<&Drops>)>,
+) {
+    for ActionEvent { entity, action } in action_reader.iter() {
+        let target = match action {
+            Action::Attack(target) => *target,
+            _ => continue,
+        };
+        let (attack, defense) = match (stats_q.get(*entity), stats_q.get(target)) {
+            // Equipped items add their bonuses to the base stats
+            (Ok((attacker, weapon)), Ok((defender, armor))) => (
+                attacker.attack + weapon.map_or(0, |equipment| equipment.attack),
+                defender.defense + armor.map_or(0, |equipment| equipment.defense),
+            ),
+            _ => continue,
+        };
+        let (mut health, tile_pos, drops) = match target_q.get_mut(target) {
+            Ok(target) if target.0.current > 0 => target,
+            _ => continue,
+        };
+
+        let damage = (global_rng.i32(1..=attack.max(1)) - defense).max(0);
+        if damage == 0 {
+            continue;
+        }
+
+        health.current -= damage;
+        effect_writer.send(EffectEvent::interrupt(
+            target,
+            Effect::Flick {
+                duration: HIT_FLICK_SECS,
+                switch: HIT_FLICK_SWITCH_SECS,
+            },
+        ));
+
+        if health.current <= 0 {
+            death_writer.send(DeathEvent {
+                entity: target,
+                tile_pos: *tile_pos,
+                drops: drops.map_or(Vec::new(), |drops| drops.0.clone()),
+            });
+        }
+    }
+}
+
+/**
+ * Take the dead out of the game and let them shrink away. The game is over when the player
+ * dies.
+ */
+pub fn handle_deaths(
+    mut commands: Commands,
+    mut death_reader: EventReader<DeathEvent>,
+    mut effect_writer: EventWriter<EffectEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
+    turn: Res<Turn>,
+    mut dungeon: Option<ResMut<Dungeon>>,
+    player_q: Query<(), With<Player>>,
+) {
+    for DeathEvent { entity, .. } in death_reader.iter() {
+        if player_q.contains(*entity) {
+            commands.insert_resource(GameOver { turn: turn.0 });
+            // The next game starts from the top of the dungeon
+            if let Some(dungeon) = dungeon.as_mut() {
+                dungeon.reset();
+            }
+            next_state.set(GameState::MainMenu);
+        } else {
+            commands
+                .entity(*entity)
+                .remove::<(Actor, Health, TilePos)>();
+            effect_writer.send(EffectEvent::sequence(
+                *entity,
+                vec![
+                    Effect::Shrink {
+                        duration: DEATH_SHRINK_SECS,
+                    },
+                    Effect::Despawn { delay: 0.0 },
+                ],
+            ));
+        }
+    }
+}
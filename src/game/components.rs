@@ -1,12 +1,12 @@
+use crate::level::{monsters::Behaviour, objects::KeyColor, Grid};
 use bevy::prelude::*;
+use bevy_ecs_tilemap::tiles::TilePos;
 use derive_more::From;
+use std::collections::HashSet;
 
 #[derive(Resource)]
 pub struct Paused(pub bool);
 
-#[derive(Component)]
-pub struct ExampleGameText;
-
 #[derive(Component)]
 pub struct PausedText;
 
@@ -34,11 +34,298 @@ pub struct AnimationIndices {
 #[derive(Component, Deref, DerefMut)]
 pub struct AnimationTimer(pub Timer);
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Direction {
     Left,
     Right,
     Up,
     Down,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
 }
+
+impl Direction {
+    /**
+     * The tile offset of a step in this direction, with y pointing up
+     */
+    pub fn offset(&self) -> (i32, i32) {
+        match self {
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0),
+            Direction::Up => (0, 1),
+            Direction::Down => (0, -1),
+            Direction::UpLeft => (-1, 1),
+            Direction::UpRight => (1, 1),
+            Direction::DownLeft => (-1, -1),
+            Direction::DownRight => (1, -1),
+        }
+    }
+
+    pub fn from_offset(dx: i32, dy: i32) -> Option<Self> {
+        match (dx.signum(), dy.signum()) {
+            (-1, 0) => Some(Direction::Left),
+            (1, 0) => Some(Direction::Right),
+            (0, 1) => Some(Direction::Up),
+            (0, -1) => Some(Direction::Down),
+            (-1, 1) => Some(Direction::UpLeft),
+            (1, 1) => Some(Direction::UpRight),
+            (-1, -1) => Some(Direction::DownLeft),
+            (1, -1) => Some(Direction::DownRight),
+            _ => None,
+        }
+    }
+
+    /**
+     * The direction of a single step from one tile to a neighboring one
+     */
+    pub fn between(from: &TilePos, to: &TilePos) -> Option<Self> {
+        Self::from_offset(to.x as i32 - from.x as i32, to.y as i32 - from.y as i32)
+    }
+}
+
 #[derive(Component)]
 pub struct Player;
+
+/// Index of the player in the `1BitRogueSet` atlas
+pub const PLAYER_SPRITE_INDEX: usize = 118;
+pub const PLAYER_HP: i32 = 20;
+pub const PLAYER_ATTACK: i32 = 4;
+pub const PLAYER_DEFENSE: i32 = 1;
+pub const PLAYER_INVENTORY_CAPACITY: usize = 10;
+
+/// The tilemap being played
+#[derive(Component)]
+pub struct GameTilemap;
+
+/// The number of turns taken since the level started
+#[derive(Resource, Default, Debug)]
+pub struct Turn(pub u32);
+
+/// Sent whenever the player spends a turn
+#[derive(Event, Clone, Debug, PartialEq)]
+pub struct TurnEvent {
+    pub entity: Entity,
+    pub turn: u32,
+}
+
+/// An actor may act once its energy reaches this
+pub const ENERGY_THRESHOLD: i32 = 100;
+/// Speed of an ordinary actor, which acts once for every action of cost 100
+pub const NORMAL_SPEED: i32 = 100;
+
+/**
+ * Anything that takes turns. Every tick of game time adds `speed` to `energy`, and actions
+ * spend it again.
+ */
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Actor {
+    pub speed: i32,
+    pub energy: i32,
+    /// Decided by input or AI, and performed once the actor is ready
+    pub action: Option<Action>,
+}
+
+impl Actor {
+    pub fn new(speed: i32) -> Self {
+        Self {
+            speed,
+            energy: 0,
+            action: None,
+        }
+    }
+
+    pub fn ready(&self) -> bool {
+        self.energy >= ENERGY_THRESHOLD
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Wait,
+    Move(Direction),
+    /// Moving into a hostile actor attacks it instead
+    Attack(Entity),
+    /// Pick up the item on the actor's tile
+    PickUp,
+    /// Use, equip or drop the item at an inventory slot
+    Use(usize),
+    Equip(usize),
+    Drop(usize),
+    /// Moving into a closed door or chest opens it instead
+    Open(Entity),
+    Close(Entity),
+}
+
+impl Action {
+    /// Energy spent by the action
+    pub fn cost(&self) -> i32 {
+        match self {
+            Action::Wait => 100,
+            Action::Move(_) => 100,
+            Action::Attack(_) => 100,
+            Action::PickUp => 100,
+            Action::Use(_) => 100,
+            Action::Equip(_) => 100,
+            Action::Drop(_) => 50,
+            Action::Open(_) => 100,
+            Action::Close(_) => 100,
+        }
+    }
+}
+
+/// Sent for every action an actor performs
+#[derive(Event, Clone, Debug, PartialEq)]
+pub struct ActionEvent {
+    pub entity: Entity,
+    pub action: Action,
+}
+
+/**
+ * The scheduler runs in this order every frame. Input and AI decide actions in `Decide`, and
+ * systems reacting to performed actions belong in `Resolve`.
+ */
+#[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
+pub enum TurnSet {
+    Energy,
+    Decide,
+    Act,
+    Resolve,
+}
+
+/// How far the player can see
+pub const PLAYER_SIGHT_RADIUS: u32 = 8;
+
+/// The tiles an actor can currently see
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct FieldOfView {
+    pub radius: u32,
+    pub visible: HashSet<TilePos>,
+}
+
+impl FieldOfView {
+    pub fn new(radius: u32) -> Self {
+        Self {
+            radius,
+            visible: HashSet::new(),
+        }
+    }
+}
+
+/// Tiles the player has seen at some point since entering the level
+#[derive(Resource, Debug, Clone)]
+pub struct RememberedTiles(pub Grid<bool>);
+
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Monster {
+    pub name: String,
+}
+
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Health {
+    pub current: i32,
+    pub max: i32,
+}
+
+impl Health {
+    pub fn new(max: i32) -> Self {
+        Self { current: max, max }
+    }
+
+    pub fn fraction(&self) -> f32 {
+        self.current as f32 / self.max.max(1) as f32
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AiState {
+    /// Wandering or standing guard, depending on the behaviour
+    Idle,
+    /// Heading for where the player was last seen
+    Chasing(TilePos),
+    Fleeing,
+}
+
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct MonsterAi {
+    pub behaviour: Behaviour,
+    pub sight_radius: u32,
+    pub flee_below: f32,
+    pub state: AiState,
+}
+
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct CombatStats {
+    /// Attacks roll between 1 and this much damage
+    pub attack: i32,
+    /// Subtracted from the damage of every hit taken
+    pub defense: i32,
+}
+
+/// Names of the items an actor leaves behind when it dies
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Drops(pub Vec<String>);
+
+/// Sent when an actor's health runs out
+#[derive(Event, Clone, Debug, PartialEq)]
+pub struct DeathEvent {
+    pub entity: Entity,
+    pub tile_pos: TilePos,
+    pub drops: Vec<String>,
+}
+
+/// How the last game ended, shown by the main menu
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct GameOver {
+    pub turn: u32,
+}
+
+/// An item lying on a tile
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Item {
+    pub name: String,
+}
+
+/// Items carried by an actor, by name
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Inventory {
+    pub items: Vec<String>,
+    pub capacity: usize,
+}
+
+impl Inventory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            items: Vec::new(),
+            capacity,
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.items.len() >= self.capacity
+    }
+}
+
+/// The equipped weapon and armor, and the bonuses they give
+#[derive(Component, Debug, Clone, Default, PartialEq)]
+pub struct Equipment {
+    pub weapon: Option<String>,
+    pub armor: Option<String>,
+    pub attack: i32,
+    pub defense: i32,
+}
+
+/// A door on a tile. Closed doors block movement and sight.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Door {
+    pub open: bool,
+    pub lock: Option<KeyColor>,
+}
+
+/// A chest on a tile, handing out its loot once opened. Chests always block movement.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Chest {
+    pub loot: Vec<String>,
+    pub open: bool,
+}
@@ -0,0 +1,265 @@
+use super::components::{
+    Action, ActionEvent, Chest, Door, Equipment, Health, Inventory, Item, Monster, Player,
+    RememberedTiles,
+};
+use crate::{
+    editor::prelude::TileMaterial,
+    get_single, get_some,
+    level::{
+        dungeon::DungeonDef,
+        objects::{ObjectPlacement, TileObject},
+        ActiveLevel, Grid, Level, Placement,
+    },
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_ecs_tilemap::tiles::TilePos;
+use std::{collections::HashMap, io};
+
+/// Where the player ends up when entering a floor
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Arrival {
+    /// On the player spawn, when starting out
+    Spawn,
+    /// On the stairs up, when coming down from the floor above
+    StairsUp,
+    /// On the stairs down, when coming up from the floor below
+    StairsDown,
+}
+
+/// A monster on a floor. Without hit points it is at full health.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FloorMonster {
+    pub placement: Placement,
+    pub hp: Option<i32>,
+}
+
+/// Everything on a floor that can change while playing it
+#[derive(Clone, Debug, PartialEq)]
+pub struct FloorState {
+    pub monsters: Vec<FloorMonster>,
+    pub items: Vec<Placement>,
+    pub objects: Vec<ObjectPlacement>,
+    pub remembered: Grid<bool>,
+}
+
+impl FloorState {
+    /**
+     * A floor as authored, before anyone set foot on it
+     */
+    pub fn fresh(level: &Level) -> Self {
+        Self {
+            monsters: level
+                .monsters
+                .iter()
+                .map(|placement| FloorMonster {
+                    placement: placement.clone(),
+                    hp: None,
+                })
+                .collect(),
+            items: level.items.clone(),
+            objects: level.objects.clone(),
+            remembered: Grid::new(level.tiles.width(), level.tiles.height(), false),
+        }
+    }
+}
+
+/// What the player takes along to the next floor
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlayerState {
+    pub health: Health,
+    pub inventory: Inventory,
+    pub equipment: Equipment,
+}
+
+/**
+ * The floors being played and what has happened on them. Only the current floor is spawned,
+ * the others are kept as they were left.
+ */
+#[derive(Resource, Debug)]
+pub struct Dungeon {
+    pub name: String,
+    pub floors: Vec<Level>,
+    pub current: usize,
+    pub arrival: Arrival,
+    /// The floor to go to once the current one has been torn down
+    pub next: Option<(usize, Arrival)>,
+    pub saved: HashMap<usize, FloorState>,
+    pub player: Option<PlayerState>,
+}
+
+impl Dungeon {
+    pub fn new(name: &str, floors: Vec<Level>) -> Self {
+        Self {
+            name: name.to_string(),
+            floors,
+            current: 0,
+            arrival: Arrival::Spawn,
+            next: None,
+            saved: HashMap::new(),
+            player: None,
+        }
+    }
+
+    /**
+     * A dungeon of just one floor, e.g. for play testing a level
+     */
+    pub fn single(level: Level) -> Self {
+        Self::new(&level.name.clone(), vec![level])
+    }
+
+    pub fn from_def(def: &DungeonDef) -> io::Result<Self> {
+        Ok(Self::new(&def.name, def.build_floors()?))
+    }
+
+    pub fn level(&self) -> &Level {
+        &self.floors[self.current]
+    }
+
+    /**
+     * Start over from the top with every floor as authored
+     */
+    pub fn reset(&mut self) {
+        *self = Self::new(&self.name, std::mem::take(&mut self.floors));
+    }
+
+    /**
+     * Where the player stands when entering the current floor. Floors without the stairs they
+     * are entered by fall back to the player spawn.
+     */
+    pub fn arrival_pos(&self) -> Result<TilePos, &'static str> {
+        let level = self.level();
+        let stairs = match self.arrival {
+            Arrival::Spawn => None,
+            Arrival::StairsUp => level.find(&TileMaterial::StairsUp),
+            Arrival::StairsDown => level.find(&TileMaterial::StairsDown),
+        };
+
+        match stairs {
+            Some(tile_pos) => Ok(tile_pos),
+            None => level.player_spawn(),
+        }
+    }
+
+    /**
+     * The floor, and where on it, taking the stairs on a tile of `material` leads to
+     */
+    pub fn stairs_target(&self, material: &TileMaterial) -> Option<(usize, Arrival)> {
+        match material {
+            TileMaterial::StairsDown if self.current + 1 < self.floors.len() => {
+                Some((self.current + 1, Arrival::StairsUp))
+            }
+            TileMaterial::StairsUp if self.current > 0 => {
+                Some((self.current - 1, Arrival::StairsDown))
+            }
+            _ => None,
+        }
+    }
+}
+
+/**
+ * Stepping onto stairs leaves the floor. The floor is saved and torn down on the way out of
+ * `InGame`, and the next one set up on the way back in.
+ */
+pub fn take_stairs(
+    mut action_reader: EventReader<ActionEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
+    dungeon: Option<ResMut<Dungeon>>,
+    active_level: Option<Res<ActiveLevel>>,
+    player_q: Query<(Entity, &TilePos), With<Player>>,
+) {
+    let mut dungeon = get_some!(dungeon);
+    let ActiveLevel(level) = get_some!(active_level.as_deref());
+    let (player, tile_pos) = get_single!(player_q);
+
+    let moved = action_reader
+        .iter()
+        .any(|event| event.entity == player && matches!(event.action, Action::Move(_)));
+    if !moved {
+        return;
+    }
+    let material = get_some!(level.tiles.get(tile_pos));
+    match dungeon.stairs_target(material) {
+        Some(next) => {
+            dungeon.next = Some(next);
+            next_state.set(GameState::ChangingFloor);
+        }
+        None if matches!(material, TileMaterial::StairsDown | TileMaterial::StairsUp) => {
+            info!("The stairs lead nowhere");
+        }
+        None => {}
+    }
+}
+
+/**
+ * Remember the floor being left and what the player carries, before `teardown` despawns it all
+ */
+pub fn save_floor(
+    dungeon: Option<ResMut<Dungeon>>,
+    remembered: Option<Res<RememberedTiles>>,
+    player_q: Query<(&Health, &Inventory, &Equipment), With<Player>>,
+    monster_q: Query<(&Monster, &TilePos, &Health)>,
+    item_q: Query<(&Item, &TilePos)>,
+    door_q: Query<(&Door, &TilePos)>,
+    chest_q: Query<(&Chest, &TilePos)>,
+) {
+    let mut dungeon = get_some!(dungeon);
+    if dungeon.next.is_none() {
+        return;
+    }
+    let remembered = get_some!(remembered);
+    let (health, inventory, equipment) = get_single!(player_q);
+
+    let doors = door_q.iter().map(|(door, tile_pos)| {
+        let object = TileObject::Door {
+            open: door.open,
+            lock: door.lock,
+        };
+        ObjectPlacement::new(&object, tile_pos)
+    });
+    let chests = chest_q.iter().map(|(chest, tile_pos)| {
+        let object = TileObject::Chest {
+            loot: chest.loot.clone(),
+            open: chest.open,
+        };
+        ObjectPlacement::new(&object, tile_pos)
+    });
+    let state = FloorState {
+        monsters: monster_q
+            .iter()
+            .map(|(monster, tile_pos, health)| FloorMonster {
+                placement: Placement::new(&monster.name, tile_pos),
+                hp: Some(health.current),
+            })
+            .collect(),
+        items: item_q
+            .iter()
+            .map(|(item, tile_pos)| Placement::new(&item.name, tile_pos))
+            .collect(),
+        objects: doors.chain(chests).collect(),
+        remembered: remembered.0.clone(),
+    };
+
+    let current = dungeon.current;
+    dungeon.saved.insert(current, state);
+    dungeon.player = Some(PlayerState {
+        health: health.clone(),
+        inventory: inventory.clone(),
+        equipment: equipment.clone(),
+    });
+}
+
+/**
+ * Move on to the next floor once the last one is gone
+ */
+pub fn change_floor(
+    dungeon: Option<ResMut<Dungeon>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let mut dungeon = get_some!(dungeon);
+    if let Some((floor, arrival)) = dungeon.next.take() {
+        dungeon.current = floor;
+        dungeon.arrival = arrival;
+    }
+    next_state.set(GameState::InGame);
+}
@@ -0,0 +1,245 @@
+use super::components::{
+    Action, ActionEvent, Actor, DeathEvent, Equipment, GameTilemap, Health, Inventory, Item, Player,
+};
+use crate::{
+    get_single, get_single_mut,
+    level::{
+        items::{ItemDef, ItemKind, Items},
+        tilemap::tile_to_world,
+    },
+    ImageAssets,
+};
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use bevy_egui::{
+    egui::{self, Color32, FontId, RichText},
+    EguiContexts,
+};
+
+/**
+ * Spawn an item lying on a tile of a tilemap spawned by `spawn_level`. It stays hidden until
+ * the player sees it.
+ */
+pub fn spawn_item(
+    commands: &mut Commands,
+    images: &ImageAssets,
+    def: &ItemDef,
+    tile_pos: TilePos,
+    tilemap_transform: &Transform,
+) -> Entity {
+    commands
+        .spawn((
+            Name::new(def.name.clone()),
+            SpriteSheetBundle {
+                texture_atlas: images.image_atlas.clone(),
+                sprite: TextureAtlasSprite {
+                    index: def.sprite_index,
+                    color: def.tint(),
+                    ..default()
+                },
+                // Below the actors walking over it
+                transform: Transform::from_translation(
+                    tile_to_world(&tile_pos, tilemap_transform) + Vec3::Z * 0.5,
+                ),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            Item {
+                name: def.name.clone(),
+            },
+            tile_pos,
+        ))
+        .id()
+}
+
+/**
+ * Pick up, use, equip and drop the items of actors that chose to this turn
+ */
+pub fn resolve_item_actions(
+    mut commands: Commands,
+    images: Res<ImageAssets>,
+    items: Items,
+    mut action_reader: EventReader<ActionEvent>,
+    tilemap_q: Query<&Transform, With<GameTilemap>>,
+    mut actor_q: Query<(
+        &TilePos,
+        &mut Inventory,
+        Option<&mut Equipment>,
+        Option<&mut Health>,
+    )>,
+    ground_q: Query<(Entity, &TilePos, &Item)>,
+) {
+    let set = match items.set() {
+        Some(set) => set,
+        None => return,
+    };
+    let tilemap_transform = get_single!(tilemap_q);
+
+    for ActionEvent { entity, action } in action_reader.iter() {
+        let (tile_pos, mut inventory, equipment, health) = match actor_q.get_mut(*entity) {
+            Ok(actor) => actor,
+            _ => continue,
+        };
+
+        match *action {
+            Action::PickUp => {
+                let (item_entity, _, item) = match ground_q
+                    .iter()
+                    .find(|(_, item_pos, _)| *item_pos == tile_pos)
+                {
+                    Some(item) => item,
+                    None => continue,
+                };
+                if inventory.is_full() {
+                    info!("No room for the {}", item.name);
+                    continue;
+                }
+
+                inventory.items.push(item.name.clone());
+                commands.entity(item_entity).despawn();
+            }
+            Action::Use(slot) => {
+                let def = match inventory.items.get(slot).and_then(|name| set.get(name)) {
+                    Some(def) => def,
+                    None => continue,
+                };
+                if let (ItemKind::Consumable { heal }, Some(mut health)) = (&def.kind, health) {
+                    health.current = (health.current + heal).min(health.max);
+                    inventory.items.remove(slot);
+                }
+            }
+            Action::Equip(slot) => {
+                let (def, mut equipment) = match (
+                    inventory.items.get(slot).and_then(|name| set.get(name)),
+                    equipment,
+                ) {
+                    (Some(def), Some(equipment)) => (def, equipment),
+                    _ => continue,
+                };
+
+                // Whatever was equipped before goes back into the inventory
+                let previous = match def.kind {
+                    ItemKind::Weapon { attack } => {
+                        equipment.attack = attack;
+                        equipment.weapon.replace(inventory.items.remove(slot))
+                    }
+                    ItemKind::Armor { defense } => {
+                        equipment.defense = defense;
+                        equipment.armor.replace(inventory.items.remove(slot))
+                    }
+                    _ => continue,
+                };
+                inventory.items.extend(previous);
+            }
+            Action::Drop(slot) => {
+                if slot >= inventory.items.len() {
+                    continue;
+                }
+                let name = inventory.items.remove(slot);
+                if let Some(def) = set.get(&name) {
+                    spawn_item(&mut commands, &images, def, *tile_pos, tilemap_transform);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/**
+ * The dead leave their drops on the tile they died on
+ */
+pub fn drop_loot(
+    mut commands: Commands,
+    images: Res<ImageAssets>,
+    items: Items,
+    mut death_reader: EventReader<DeathEvent>,
+    tilemap_q: Query<&Transform, With<GameTilemap>>,
+) {
+    let tilemap_transform = get_single!(tilemap_q);
+    for DeathEvent {
+        tile_pos, drops, ..
+    } in death_reader.iter()
+    {
+        for name in drops {
+            match items.set().and_then(|set| set.get(name)) {
+                Some(def) => {
+                    spawn_item(&mut commands, &images, def, *tile_pos, tilemap_transform);
+                }
+                None => warn!("Unknown item {}", name),
+            }
+        }
+    }
+}
+
+/**
+ * The player's inventory, toggled with I. Buttons choose the player's next action.
+ */
+pub fn inventory_ui(
+    mut contexts: EguiContexts,
+    mut open: Local<bool>,
+    keyboard: Res<Input<KeyCode>>,
+    items: Items,
+    mut player_q: Query<(&Inventory, &Equipment, &mut Actor), With<Player>>,
+) {
+    if keyboard.just_pressed(KeyCode::I) {
+        *open = !*open;
+    }
+    let (inventory, equipment, mut actor) = get_single_mut!(player_q);
+    let font = FontId::proportional(18.);
+
+    egui::Window::new("Inventory")
+        .open(&mut open)
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(
+                RichText::new(format!(
+                    "{}/{} items",
+                    inventory.items.len(),
+                    inventory.capacity
+                ))
+                .font(font.clone()),
+            );
+            for (slot, name) in [("Weapon", &equipment.weapon), ("Armor", &equipment.armor)] {
+                ui.label(
+                    RichText::new(format!("{}: {}", slot, name.as_deref().unwrap_or("-")))
+                        .font(font.clone())
+                        .color(Color32::GRAY),
+                );
+            }
+            ui.separator();
+
+            let mut chosen = None;
+            egui::Grid::new("Inventory Items").show(ui, |ui| {
+                for (slot, name) in inventory.items.iter().enumerate() {
+                    ui.label(RichText::new(name).font(font.clone()));
+                    match items
+                        .set()
+                        .and_then(|set| set.get(name))
+                        .map(|def| &def.kind)
+                    {
+                        Some(ItemKind::Consumable { .. }) => {
+                            if ui.button("Use").clicked() {
+                                chosen = Some(Action::Use(slot));
+                            }
+                        }
+                        Some(ItemKind::Weapon { .. } | ItemKind::Armor { .. }) => {
+                            if ui.button("Equip").clicked() {
+                                chosen = Some(Action::Equip(slot));
+                            }
+                        }
+                        _ => {
+                            ui.label("");
+                        }
+                    }
+                    if ui.button("Drop").clicked() {
+                        chosen = Some(Action::Drop(slot));
+                    }
+                    ui.end_row();
+                }
+            });
+
+            if chosen.is_some() && actor.ready() && actor.action.is_none() {
+                actor.action = chosen;
+            }
+        });
+}
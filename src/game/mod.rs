@@ -1,37 +1,67 @@
 use self::{
-    components::{Paused, PhysicsSet},
-    effects::flick_system,
-    systems::{animate_sprite, game_indicator_ui, game_keys, teardown, toggle_edit_mode},
+    combat::{handle_deaths, resolve_attacks},
+    components::{Paused, PhysicsSet, TurnSet},
+    dungeon::{change_floor, save_floor, take_stairs},
+    items::{drop_loot, inventory_ui, resolve_item_actions},
+    monsters::monster_ai,
+    objects::{render_objects, resolve_object_actions},
+    systems::{
+        animate_actions, animate_sprite, follow_player, game_indicator_ui, game_keys, setup_level,
+        teardown, toggle_edit_mode,
+    },
+    turns::TurnPlugin,
+    vision::{render_fov, reveal_entities, update_fov},
 };
 use crate::GameState;
 use bevy::prelude::*;
 
 mod collision;
+mod combat;
 mod components;
-mod effects;
+mod dungeon;
+mod items;
+mod monsters;
+mod objects;
 pub mod prelude;
 mod systems;
+mod turns;
+mod vision;
 
 pub struct GamePlugin;
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
-        // app.add_systems(OnEnter(GameState::InGame))
-        app.add_systems(
-            Update,
-            (
-                game_keys,
-                animate_sprite,
-                flick_system,
-                game_indicator_ui,
-                toggle_edit_mode,
+        app.add_plugins(TurnPlugin)
+            .add_systems(OnEnter(GameState::InGame), setup_level)
+            .add_systems(
+                Update,
+                (
+                    (game_keys, monster_ai).in_set(TurnSet::Decide),
+                    (
+                        animate_actions,
+                        (resolve_attacks, handle_deaths).chain(),
+                        (resolve_item_actions, drop_loot).after(handle_deaths),
+                        resolve_object_actions,
+                        take_stairs,
+                        // Doors opened this turn are seen through right away
+                        (update_fov, render_fov, reveal_entities, render_objects)
+                            .chain()
+                            .after(resolve_object_actions),
+                    )
+                        .in_set(TurnSet::Resolve),
+                    follow_player.after(TurnSet::Resolve),
+                    animate_sprite,
+                    game_indicator_ui,
+                    inventory_ui,
+                    toggle_edit_mode,
+                )
+                    .run_if(in_state(GameState::InGame)),
             )
-                .run_if(in_state(GameState::InGame)),
-        )
-        .configure_set(
-            Update,
-            PhysicsSet::Movement.before(PhysicsSet::CollisionDetection),
-        )
-        .add_systems(OnExit(GameState::InGame), teardown)
-        .insert_resource(Paused(false));
+            .configure_set(
+                Update,
+                PhysicsSet::Movement.before(PhysicsSet::CollisionDetection),
+            )
+            .add_systems(OnExit(GameState::InGame), (save_floor, teardown).chain())
+            .add_systems(OnEnter(GameState::ChangingFloor), change_floor)
+            .insert_resource(Paused(false));
     }
 }
@@ -0,0 +1,162 @@
+use super::{
+    components::{
+        Action, Actor, AiState, CombatStats, Direction, Drops, FieldOfView, Health, Monster,
+        MonsterAi, Player,
+    },
+    objects::Obstacles,
+};
+use crate::{
+    get_single, get_some,
+    level::{
+        materials::Materials,
+        monsters::{Behaviour, MonsterDef},
+        pathfinding::{astar, DijkstraMap},
+        tilemap::tile_to_world,
+        ActiveLevel,
+    },
+    ImageAssets,
+};
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use bevy_turborand::{DelegatedRng, GlobalRng, RngComponent};
+use std::collections::HashSet;
+
+/**
+ * Spawn a monster on a tile of a tilemap spawned by `spawn_level`. Each monster gets its own
+ * random number generator forked from the global one, so seeded games play out the same.
+ */
+pub fn spawn_monster(
+    commands: &mut Commands,
+    images: &ImageAssets,
+    global_rng: &mut GlobalRng,
+    def: &MonsterDef,
+    tile_pos: TilePos,
+    tilemap_transform: &Transform,
+) -> Entity {
+    commands
+        .spawn((
+            Name::new(def.name.clone()),
+            SpriteSheetBundle {
+                texture_atlas: images.image_atlas.clone(),
+                sprite: TextureAtlasSprite::new(def.sprite_index),
+                transform: Transform::from_translation(
+                    tile_to_world(&tile_pos, tilemap_transform) + Vec3::Z,
+                ),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            Monster {
+                name: def.name.clone(),
+            },
+            Actor::new(def.speed),
+            Health::new(def.hp),
+            CombatStats {
+                attack: def.attack,
+                defense: def.defense,
+            },
+            Drops(def.drops.clone()),
+            MonsterAi {
+                behaviour: def.behaviour,
+                sight_radius: def.sight_radius,
+                flee_below: def.flee_below,
+                state: AiState::Idle,
+            },
+            RngComponent::from(global_rng),
+            tile_pos,
+        ))
+        .id()
+}
+
+/**
+ * A monster notices the player when it is within its sight radius and the player can see it.
+ * The field of view is symmetric, so that also means the monster can see the player.
+ */
+fn notices(
+    ai: &MonsterAi,
+    tile_pos: &TilePos,
+    player_pos: &TilePos,
+    player_fov: &FieldOfView,
+) -> bool {
+    let (dx, dy) = (
+        tile_pos.x as i32 - player_pos.x as i32,
+        tile_pos.y as i32 - player_pos.y as i32,
+    );
+    let radius = ai.sight_radius as i32;
+    dx * dx + dy * dy <= radius * radius && player_fov.visible.contains(tile_pos)
+}
+
+/**
+ * Decide what the ready monsters do. They wander until they notice the player, chase the player
+ * to where they were last seen, and flee once badly hurt.
+ */
+pub fn monster_ai(
+    active_level: Option<Res<ActiveLevel>>,
+    materials: Materials,
+    obstacles: Obstacles,
+    player_q: Query<(&TilePos, &FieldOfView), With<Player>>,
+    mut monster_q: Query<
+        (
+            &TilePos,
+            &mut Actor,
+            &mut MonsterAi,
+            &Health,
+            &mut RngComponent,
+        ),
+        Without<Player>,
+    >,
+    actor_q: Query<&TilePos, With<Actor>>,
+) {
+    let ActiveLevel(level) = get_some!(active_level.as_deref());
+    let (player_pos, player_fov) = get_single!(player_q);
+    let set = materials.set();
+    let size = level.tiles.size();
+    // Monsters don't open doors, so closed ones are as good as walls
+    let blocking = obstacles.blocking();
+    let passable =
+        |pos: &TilePos| level.passable(pos.x as i32, pos.y as i32, set) && !blocking.contains(pos);
+    let occupied: HashSet<TilePos> = actor_q.iter().copied().collect();
+    // Only built once somebody needs it, and then shared by everyone fleeing this turn
+    let mut flee_map: Option<DijkstraMap> = None;
+
+    for (tile_pos, mut actor, mut ai, health, mut rng) in monster_q.iter_mut() {
+        if !actor.ready() || actor.action.is_some() {
+            continue;
+        }
+
+        let sees_player =
+            ai.behaviour != Behaviour::Wanderer && notices(&ai, tile_pos, player_pos, player_fov);
+        ai.state = match ai.state {
+            _ if sees_player && health.fraction() <= ai.flee_below => AiState::Fleeing,
+            _ if sees_player => AiState::Chasing(*player_pos),
+            AiState::Chasing(last_seen) if last_seen == *tile_pos => AiState::Idle,
+            AiState::Fleeing if !sees_player => AiState::Idle,
+            state => state,
+        };
+
+        let step = match ai.state {
+            AiState::Idle if ai.behaviour == Behaviour::Guard => None,
+            AiState::Idle => {
+                // One in nine chance of staying put
+                let (dx, dy) = (rng.i32(-1..=1), rng.i32(-1..=1));
+                let target = TilePos {
+                    x: (tile_pos.x as i32 + dx).max(0) as u32,
+                    y: (tile_pos.y as i32 + dy).max(0) as u32,
+                };
+                (passable(&target) && !occupied.contains(&target)).then_some(target)
+            }
+            AiState::Chasing(target) => {
+                astar(tile_pos, &target, &size, passable).and_then(|path| path.first().copied())
+            }
+            AiState::Fleeing => flee_map
+                .get_or_insert_with(|| {
+                    DijkstraMap::new(&[*player_pos], &size, passable).flee_map(passable)
+                })
+                .downhill(tile_pos, |pos| occupied.contains(pos)),
+        };
+
+        actor.action = Some(
+            step.and_then(|step| Direction::between(tile_pos, &step))
+                .map_or(Action::Wait, Action::Move),
+        );
+    }
+}
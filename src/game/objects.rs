@@ -0,0 +1,259 @@
+use super::{
+    components::{
+        Action, ActionEvent, Actor, Chest, Door, FieldOfView, GameTilemap, Inventory, Item, Player,
+        RememberedTiles,
+    },
+    items::spawn_item,
+    vision::REMEMBERED_FADE,
+};
+use crate::{
+    get_single, get_some,
+    level::{
+        items::{ItemKind, Items},
+        objects::{
+            ObjectPlacement, TileObject, CHEST_CLOSED_SPRITE_INDEX, CHEST_OPEN_SPRITE_INDEX,
+            DOOR_CLOSED_SPRITE_INDEX, DOOR_OPEN_SPRITE_INDEX,
+        },
+        tilemap::tile_to_world,
+    },
+    ImageAssets, DARK,
+};
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_ecs_tilemap::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+/**
+ * Spawn a door or chest on a tile of a tilemap spawned by `spawn_level`. It stays hidden until
+ * the player has seen its tile.
+ */
+pub fn spawn_object(
+    commands: &mut Commands,
+    images: &ImageAssets,
+    placement: &ObjectPlacement,
+    tilemap_transform: &Transform,
+) -> Entity {
+    let tile_pos = placement.tile_pos();
+    let mut entity = commands.spawn((
+        Name::new(placement.object.name()),
+        SpriteSheetBundle {
+            texture_atlas: images.image_atlas.clone(),
+            sprite: TextureAtlasSprite::new(placement.object.sprite_index()),
+            // Below items and actors
+            transform: Transform::from_translation(
+                tile_to_world(&tile_pos, tilemap_transform) + Vec3::Z * 0.25,
+            ),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        tile_pos,
+    ));
+
+    match &placement.object {
+        TileObject::Door { open, lock } => entity.insert(Door {
+            open: *open,
+            lock: *lock,
+        }),
+        TileObject::Chest { loot, open } => entity.insert(Chest {
+            loot: loot.clone(),
+            open: *open,
+        }),
+    };
+    entity.id()
+}
+
+/**
+ * Where doors and chests get in the way of actors and sight
+ */
+#[derive(SystemParam)]
+pub struct Obstacles<'w, 's> {
+    door_q: Query<'w, 's, (Entity, &'static TilePos, &'static Door), Without<Actor>>,
+    chest_q: Query<'w, 's, (Entity, &'static TilePos, &'static Chest), Without<Actor>>,
+}
+
+impl Obstacles<'_, '_> {
+    /**
+     * Closed doors and chests, which actors open by walking into them
+     */
+    pub fn openable(&self) -> HashMap<TilePos, Entity> {
+        let doors = self
+            .door_q
+            .iter()
+            .filter(|(_, _, door)| !door.open)
+            .map(|(entity, tile_pos, _)| (*tile_pos, entity));
+        let chests = self
+            .chest_q
+            .iter()
+            .filter(|(_, _, chest)| !chest.open)
+            .map(|(entity, tile_pos, _)| (*tile_pos, entity));
+        doors.chain(chests).collect()
+    }
+
+    /**
+     * Tiles nobody can walk onto, whatever their material
+     */
+    pub fn blocking(&self) -> HashSet<TilePos> {
+        let chests = self.chest_q.iter().map(|(_, tile_pos, _)| *tile_pos);
+        self.opaque().into_iter().chain(chests).collect()
+    }
+
+    /**
+     * Tiles nobody can see through, whatever their material
+     */
+    pub fn opaque(&self) -> HashSet<TilePos> {
+        self.door_q
+            .iter()
+            .filter(|(_, _, door)| !door.open)
+            .map(|(_, tile_pos, _)| *tile_pos)
+            .collect()
+    }
+}
+
+/**
+ * Open and close the doors and chests actors chose to this turn. Locked doors use up a key of
+ * their color from the actor's inventory.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn resolve_object_actions(
+    mut commands: Commands,
+    images: Res<ImageAssets>,
+    items: Items,
+    mut action_reader: EventReader<ActionEvent>,
+    tilemap_q: Query<&Transform, With<GameTilemap>>,
+    mut actor_q: Query<(&TilePos, Option<&mut Inventory>), With<Actor>>,
+    mut door_q: Query<(&TilePos, &mut Door), Without<Actor>>,
+    mut chest_q: Query<&mut Chest, Without<Actor>>,
+    item_q: Query<&TilePos, (With<Item>, Without<Actor>)>,
+) {
+    let tilemap_transform = get_single!(tilemap_q);
+    let set = items.set();
+
+    for ActionEvent { entity, action } in action_reader.iter() {
+        match *action {
+            Action::Open(target) => {
+                let (tile_pos, inventory) = match actor_q.get_mut(*entity) {
+                    Ok(actor) => actor,
+                    _ => continue,
+                };
+
+                if let Ok((_, mut door)) = door_q.get_mut(target) {
+                    if let Some(color) = door.lock {
+                        let key = inventory.and_then(|mut inventory| {
+                            let slot = inventory.items.iter().position(|name| {
+                                set.and_then(|set| set.get(name)).map(|def| &def.kind)
+                                    == Some(&ItemKind::Key { color })
+                            })?;
+                            Some(inventory.items.remove(slot))
+                        });
+                        match key {
+                            Some(key) => info!("The {} unlocks the door", key),
+                            None => {
+                                info!("The door is locked");
+                                continue;
+                            }
+                        }
+                        door.lock = None;
+                    }
+                    door.open = true;
+                } else if let Ok(mut chest) = chest_q.get_mut(target) {
+                    if chest.open {
+                        continue;
+                    }
+                    chest.open = true;
+
+                    // What doesn't fit in the inventory ends up on the floor
+                    let mut inventory = inventory;
+                    for name in chest.loot.drain(..) {
+                        match inventory.as_mut() {
+                            Some(inventory) if !inventory.is_full() => inventory.items.push(name),
+                            _ => match set.and_then(|set| set.get(&name)) {
+                                Some(def) => {
+                                    spawn_item(
+                                        &mut commands,
+                                        &images,
+                                        def,
+                                        *tile_pos,
+                                        tilemap_transform,
+                                    );
+                                }
+                                None => warn!("Unknown item {}", name),
+                            },
+                        }
+                    }
+                }
+            }
+            Action::Close(target) => {
+                let door_pos = match door_q.get(target) {
+                    Ok((door_pos, _)) => *door_pos,
+                    _ => continue,
+                };
+                // Doors don't close on anything standing or lying in the doorway
+                let blocked = actor_q.iter().any(|(tile_pos, _)| *tile_pos == door_pos)
+                    || item_q.iter().any(|tile_pos| *tile_pos == door_pos);
+                if blocked {
+                    continue;
+                }
+                if let Ok((_, mut door)) = door_q.get_mut(target) {
+                    door.open = false;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/**
+ * Doors and chests stay visible once seen, faded like remembered tiles while out of sight
+ */
+#[allow(clippy::type_complexity)]
+pub fn render_objects(
+    remembered: Option<Res<RememberedTiles>>,
+    player_q: Query<&FieldOfView, With<Player>>,
+    mut object_q: Query<
+        (
+            &TilePos,
+            &mut TextureAtlasSprite,
+            &mut Visibility,
+            Option<&Door>,
+            Option<&Chest>,
+        ),
+        Or<(With<Door>, With<Chest>)>,
+    >,
+) {
+    let fov = get_single!(player_q);
+    let remembered = get_some!(remembered);
+
+    for (tile_pos, mut sprite, mut visibility, door, chest) in object_q.iter_mut() {
+        let (index, tint) = match (door, chest) {
+            (Some(door), _) => (
+                if door.open {
+                    DOOR_OPEN_SPRITE_INDEX
+                } else {
+                    DOOR_CLOSED_SPRITE_INDEX
+                },
+                door.lock.map_or(Color::WHITE, |color| color.color()),
+            ),
+            (_, Some(chest)) => (
+                if chest.open {
+                    CHEST_OPEN_SPRITE_INDEX
+                } else {
+                    CHEST_CLOSED_SPRITE_INDEX
+                },
+                Color::WHITE,
+            ),
+            _ => continue,
+        };
+        sprite.index = index;
+
+        if fov.visible.contains(tile_pos) {
+            *visibility = Visibility::Inherited;
+            sprite.color = tint;
+        } else if remembered.0.get(tile_pos) == Some(&true) {
+            *visibility = Visibility::Inherited;
+            let faded = Vec4::from(tint.as_rgba_f32())
+                .lerp(Vec4::from(DARK.as_rgba_f32()), REMEMBERED_FADE);
+            sprite.color = Color::rgba(faded.x, faded.y, faded.z, 1.0);
+        } else {
+            *visibility = Visibility::Hidden;
+        }
+    }
+}
@@ -1,3 +1,5 @@
+pub use super::components::GameOver;
+pub use super::dungeon::Dungeon;
 use bevy::prelude::Component;
 
 // stuff you want to export to other mods
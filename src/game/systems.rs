@@ -8,11 +8,34 @@ use bevy_turborand::DelegatedRng;
 use bevy_turborand::{GlobalRng, RngComponent};
 use std::time::Duration;
 
-use crate::GameState;
+use crate::{
+    game::prelude::MainCamera,
+    get_single, get_single_mut, get_some,
+    level::{
+        items::Items,
+        materials::Materials,
+        monsters::Monsters,
+        tilemap::{despawn_level, spawn_level, tile_to_world, TILE_SIZE},
+        ActiveLevel,
+    },
+    GameState, ImageAssets,
+};
+use bevy_ecs_tilemap::prelude::*;
 
 use super::components::{
-    AnimationIndices, AnimationTimer, ExampleGameText, Paused, PausedText, Player, Pos, Vel,
+    Action, ActionEvent, Actor, AnimationIndices, AnimationTimer, Chest, CombatStats, Direction,
+    Door, Equipment, FieldOfView, GameTilemap, Health, Inventory, Item, Monster, Paused,
+    PausedText, Player, Pos, RememberedTiles, Turn, Vel, NORMAL_SPEED, PLAYER_ATTACK,
+    PLAYER_DEFENSE, PLAYER_HP, PLAYER_INVENTORY_CAPACITY, PLAYER_SIGHT_RADIUS, PLAYER_SPRITE_INDEX,
 };
+use super::dungeon::{Dungeon, FloorMonster, FloorState, PlayerState};
+use super::items::spawn_item;
+use super::monsters::spawn_monster;
+use super::objects::spawn_object;
+use crate::effects::{Effect, EffectEvent};
+
+/// How long it takes a sprite to slide to the next tile
+const MOVE_ANIMATION_SECS: f32 = 0.08;
 
 pub fn toggle_edit_mode(
     mut next_state: ResMut<NextState<GameState>>,
@@ -23,20 +46,126 @@ pub fn toggle_edit_mode(
     }
 }
 
+/**
+ * Map a just pressed key to a step direction. Arrows, WASD, the numpad and vi keys all work.
+ */
+fn direction_from_keys(keyboard: &Input<KeyCode>) -> Option<Direction> {
+    let bindings: [(Direction, &[KeyCode]); 8] = [
+        (
+            Direction::Up,
+            &[KeyCode::Up, KeyCode::W, KeyCode::Numpad8, KeyCode::K],
+        ),
+        (
+            Direction::Down,
+            &[KeyCode::Down, KeyCode::S, KeyCode::Numpad2, KeyCode::J],
+        ),
+        (
+            Direction::Left,
+            &[KeyCode::Left, KeyCode::A, KeyCode::Numpad4, KeyCode::H],
+        ),
+        (
+            Direction::Right,
+            &[KeyCode::Right, KeyCode::D, KeyCode::Numpad6, KeyCode::L],
+        ),
+        (Direction::UpLeft, &[KeyCode::Numpad7, KeyCode::Y]),
+        (Direction::UpRight, &[KeyCode::Numpad9, KeyCode::U]),
+        (Direction::DownLeft, &[KeyCode::Numpad1, KeyCode::B]),
+        (Direction::DownRight, &[KeyCode::Numpad3, KeyCode::N]),
+    ];
+
+    bindings
+        .into_iter()
+        .find(|(_, keys)| keyboard.any_just_pressed(keys.iter().copied()))
+        .map(|(direction, _)| direction)
+}
+
+/**
+ * Decide the player's next action. It's performed by the scheduler once the player is ready.
+ */
 pub fn game_keys(
-    mut paused: ResMut<Paused>,
+    paused: Res<Paused>,
     keyboard: Res<Input<KeyCode>>,
-    mut player: Query<(
-        &Player,
-        &mut Transform,
-        &mut AnimationIndices,
-        &mut TextureAtlasSprite,
-        &mut AnimationTimer,
-    )>,
+    mut player_q: Query<(&mut Actor, &TilePos), With<Player>>,
+    item_q: Query<&TilePos, With<Item>>,
+    door_q: Query<(Entity, &TilePos, &Door)>,
+) {
+    if paused.0 {
+        return;
+    }
+    let (mut actor, player_pos) = get_single_mut!(player_q);
+    if !actor.ready() || actor.action.is_some() {
+        return;
+    }
+
+    if let Some(direction) = direction_from_keys(&keyboard) {
+        actor.action = Some(Action::Move(direction));
+    } else if keyboard.any_just_pressed([KeyCode::Numpad5, KeyCode::Period]) {
+        actor.action = Some(Action::Wait);
+    } else if keyboard.any_just_pressed([KeyCode::G, KeyCode::Comma])
+        && item_q.iter().any(|tile_pos| tile_pos == player_pos)
+    {
+        actor.action = Some(Action::PickUp);
+    } else if keyboard.just_pressed(KeyCode::C) {
+        // Close the first open door next to the player
+        let adjacent = |tile_pos: &TilePos| {
+            tile_pos != player_pos
+                && tile_pos.x.abs_diff(player_pos.x) <= 1
+                && tile_pos.y.abs_diff(player_pos.y) <= 1
+        };
+        if let Some((door, _, _)) = door_q
+            .iter()
+            .find(|(_, tile_pos, door)| door.open && adjacent(tile_pos))
+        {
+            actor.action = Some(Action::Close(door));
+        }
+    }
+}
+
+/**
+ * Start sliding actors that moved to their new tile
+ */
+pub fn animate_actions(
+    mut action_reader: EventReader<ActionEvent>,
+    mut effect_writer: EventWriter<EffectEvent>,
+    tilemap_q: Query<&Transform, With<GameTilemap>>,
+    mut actor_q: Query<(&TilePos, &mut TextureAtlasSprite)>,
+) {
+    let tilemap_transform = get_single!(tilemap_q);
+    for ActionEvent { entity, action } in action_reader.iter() {
+        let direction = match action {
+            Action::Move(direction) => direction,
+            _ => continue,
+        };
+        let (tile_pos, mut sprite) = match actor_q.get_mut(*entity) {
+            Ok(actor) => actor,
+            _ => continue,
+        };
+
+        let (dx, _) = direction.offset();
+        if dx != 0 {
+            sprite.flip_x = dx < 0;
+        }
+        effect_writer.send(EffectEvent::interrupt(
+            *entity,
+            Effect::Move {
+                to: tile_to_world(tile_pos, tilemap_transform) + Vec3::Z,
+                duration: MOVE_ANIMATION_SECS,
+            },
+        ));
+    }
+}
+
+pub fn follow_player(
+    player_q: Query<&Transform, With<Player>>,
+    mut camera_q: Query<&mut Transform, (With<MainCamera>, Without<Player>)>,
 ) {
+    let player = get_single!(player_q);
+    let mut camera = get_single_mut!(camera_q);
+    camera.translation.x = player.translation.x;
+    camera.translation.y = player.translation.y;
 }
 
-pub fn game_indicator_ui(mut contexts: EguiContexts) {
+pub fn game_indicator_ui(mut contexts: EguiContexts, player_q: Query<&Health, With<Player>>) {
     egui::Area::new("Indicator")
         .anchor(Align2::CENTER_TOP, egui::emath::vec2(10., 5.))
         .show(contexts.ctx_mut(), |ui| {
@@ -46,42 +175,152 @@ pub fn game_indicator_ui(mut contexts: EguiContexts) {
                         .font(FontId::proportional(24.))
                         .color(Color32::WHITE),
                 );
+                if let Ok(health) = player_q.get_single() {
+                    ui.label(
+                        RichText::new(format!("HP {}/{}", health.current, health.max))
+                            .font(FontId::proportional(24.))
+                            .color(Color32::LIGHT_RED),
+                    );
+                }
             });
         });
 }
 
-// pub fn setup_level(mut commands: Commands, asset_server: Res<AssetServer>) {
-//     // Size of the tile map in tiles.
-//     let map_size = TilemapSize { x: 32, y: 32 };
-
-//     // To create a map we use the TileStorage component.
-//     // This component is a grid of tile entities and is used to help keep track of individual
-//     // tiles in the world. If you have multiple layers of tiles you would have a Tilemap2dStorage
-//     // component per layer.
-//     let mut tile_storage = TileStorage::empty(map_size);
-
-//     // For the purposes of this example, we consider a tilemap with rectangular tiles.
-//     let map_type = TilemapType::Square;
-
-//     let tilemap_entity = commands.spawn_empty().id();
-
-//     // Spawn a 32 by 32 tilemap.
-//     // Alternatively, you can use helpers::fill_tilemap.
-//     for x in 0..map_size.x {
-//         for y in 0..map_size.y {
-//             let tile_pos = TilePos { x, y };
-//             let tile_entity = commands
-//                 .spawn(TileBundle {
-//                     position: tile_pos,
-//                     tilemap_id: TilemapId(tilemap_entity),
-//                     ..Default::default()
-//                 })
-//                 .id();
-//         }
-//     }
-// }
-
-pub fn teardown(mut commands: Commands, texts: Query<(Entity, With<ExampleGameText>)>) {}
+/**
+ * Spawn the current floor of the dungeon as it was left, and put the player where they arrive
+ */
+pub fn setup_level(
+    mut commands: Commands,
+    images: Res<ImageAssets>,
+    mut global_rng: ResMut<GlobalRng>,
+    dungeon: Option<ResMut<Dungeon>>,
+    materials: Materials,
+    monsters: Monsters,
+    items: Items,
+) {
+    let mut dungeon = get_some!(dungeon);
+    let level = dungeon.level().clone();
+    let spawn = match dungeon.arrival_pos() {
+        Ok(spawn) => spawn,
+        Err(err) => {
+            error!("Can't play level {}: {}", level.name, err);
+            return;
+        }
+    };
+    let current = dungeon.current;
+    let floor = dungeon
+        .saved
+        .remove(&current)
+        .unwrap_or_else(|| FloorState::fresh(&level));
+
+    let tilemap_entity = spawn_level(&mut commands, &images, &level, materials.set());
+    let tilemap_transform = get_tilemap_center_transform(
+        &level.tiles.size(),
+        &TILE_SIZE.into(),
+        &TilemapType::default(),
+        0.0,
+    );
+    commands.entity(tilemap_entity).insert(GameTilemap);
+    commands.insert_resource(RememberedTiles(floor.remembered));
+
+    // The player only starts over when entering the dungeon, not when changing floors
+    let player = dungeon.player.take().unwrap_or_else(|| {
+        commands.insert_resource(Turn::default());
+        PlayerState {
+            health: Health::new(PLAYER_HP),
+            inventory: Inventory::new(PLAYER_INVENTORY_CAPACITY),
+            equipment: Equipment::default(),
+        }
+    });
+    commands.spawn((
+        Name::new("Player"),
+        SpriteSheetBundle {
+            texture_atlas: images.image_atlas.clone(),
+            sprite: TextureAtlasSprite::new(PLAYER_SPRITE_INDEX),
+            transform: Transform::from_translation(
+                tile_to_world(&spawn, &tilemap_transform) + Vec3::Z,
+            ),
+            ..default()
+        },
+        Player,
+        Actor::new(NORMAL_SPEED),
+        player.health,
+        CombatStats {
+            attack: PLAYER_ATTACK,
+            defense: PLAYER_DEFENSE,
+        },
+        FieldOfView::new(PLAYER_SIGHT_RADIUS),
+        player.inventory,
+        player.equipment,
+        spawn,
+    ));
+
+    for FloorMonster { placement, hp } in floor.monsters.iter() {
+        match monsters.set().and_then(|set| set.get(&placement.name)) {
+            Some(def) => {
+                let monster = spawn_monster(
+                    &mut commands,
+                    &images,
+                    &mut global_rng,
+                    def,
+                    placement.tile_pos(),
+                    &tilemap_transform,
+                );
+                if let Some(hp) = hp {
+                    commands.entity(monster).insert(Health {
+                        current: *hp,
+                        max: def.hp,
+                    });
+                }
+            }
+            None => warn!("Unknown monster {}", placement.name),
+        }
+    }
+
+    for placement in floor.objects.iter() {
+        spawn_object(&mut commands, &images, placement, &tilemap_transform);
+    }
+
+    for placement in floor.items.iter() {
+        match items.set().and_then(|set| set.get(&placement.name)) {
+            Some(def) => {
+                spawn_item(
+                    &mut commands,
+                    &images,
+                    def,
+                    placement.tile_pos(),
+                    &tilemap_transform,
+                );
+            }
+            None => warn!("Unknown item {}", placement.name),
+        }
+    }
+
+    commands.insert_resource(ActiveLevel(level));
+}
+
+#[allow(clippy::type_complexity)]
+pub fn teardown(
+    mut commands: Commands,
+    tilemap_q: Query<(Entity, &TileStorage), With<GameTilemap>>,
+    entities: Query<
+        Entity,
+        Or<(
+            With<Player>,
+            With<Monster>,
+            With<Item>,
+            With<Door>,
+            With<Chest>,
+        )>,
+    >,
+) {
+    for (tilemap_entity, storage) in tilemap_q.iter() {
+        despawn_level(&mut commands, tilemap_entity, storage);
+    }
+    for entity in entities.iter() {
+        commands.entity(entity).despawn();
+    }
+}
 
 pub fn animate_sprite(
     time: Res<Time>,
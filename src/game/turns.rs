@@ -0,0 +1,239 @@
+use super::{
+    components::{
+        Action, ActionEvent, Actor, DeathEvent, Player, Turn, TurnEvent, TurnSet, ENERGY_THRESHOLD,
+    },
+    objects::Obstacles,
+};
+use crate::{
+    get_some,
+    level::{materials::Materials, ActiveLevel},
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_ecs_tilemap::tiles::TilePos;
+use std::{cmp::Reverse, collections::HashMap};
+
+/**
+ * Classic roguelike time where the world only moves on once the player has acted. Nothing in
+ * here reads input or renders, so the scheduler can be stepped headlessly with `App::update`.
+ */
+pub struct TurnPlugin;
+impl Plugin for TurnPlugin {
+    fn build(&self, app: &mut App) {
+        app.configure_sets(
+            Update,
+            (
+                TurnSet::Energy,
+                TurnSet::Decide,
+                TurnSet::Act,
+                TurnSet::Resolve,
+            )
+                .chain(),
+        )
+        .add_systems(
+            Update,
+            (
+                gain_energy.in_set(TurnSet::Energy),
+                perform_actions.in_set(TurnSet::Act),
+            )
+                .run_if(in_state(GameState::InGame)),
+        )
+        .add_event::<TurnEvent>()
+        .add_event::<ActionEvent>()
+        .add_event::<DeathEvent>()
+        .init_resource::<Turn>();
+    }
+}
+
+/**
+ * When nobody is ready, skip ahead as many ticks as it takes for the first actor to be
+ */
+pub fn gain_energy(mut actors: Query<&mut Actor>) {
+    if actors.iter().any(|actor| actor.ready()) {
+        return;
+    }
+
+    let ticks = get_some!(actors
+        .iter()
+        .filter(|actor| actor.speed > 0)
+        .map(|actor| (ENERGY_THRESHOLD - actor.energy + actor.speed - 1) / actor.speed)
+        .min());
+    for mut actor in actors.iter_mut() {
+        actor.energy += actor.speed * ticks;
+    }
+}
+
+/**
+ * Let the ready actors act, most energetic first. Time stops at the player until they have
+ * decided what to do. Ties go to the player and then to the oldest entity, so a game seeded
+ * through `RngPlugin` always plays out the same. The player's action ends the frame, so it is
+ * resolved (and whoever it killed removed) before anyone else acts.
+ */
+pub fn perform_actions(
+    mut turn: ResMut<Turn>,
+    mut turn_writer: EventWriter<TurnEvent>,
+    mut action_writer: EventWriter<ActionEvent>,
+    active_level: Option<Res<ActiveLevel>>,
+    materials: Materials,
+    obstacles: Obstacles,
+    mut actors: Query<(Entity, &mut Actor, &mut TilePos, Option<&Player>)>,
+) {
+    let ActiveLevel(level) = get_some!(active_level.as_deref());
+    let openable = obstacles.openable();
+    let blocking = obstacles.blocking();
+
+    let mut ready: Vec<(i32, bool, Entity)> = actors
+        .iter()
+        .filter(|(_, actor, _, _)| actor.ready())
+        .map(|(entity, actor, _, player)| (actor.energy, player.is_some(), entity))
+        .collect();
+    ready.sort_by_key(|(energy, is_player, entity)| (Reverse(*energy), !is_player, *entity));
+    let mut occupants: HashMap<TilePos, (Entity, bool)> = actors
+        .iter()
+        .map(|(entity, _, pos, player)| (*pos, (entity, player.is_some())))
+        .collect();
+
+    for (_, is_player, entity) in ready {
+        let (_, mut actor, mut tile_pos, _) = match actors.get_mut(entity) {
+            Ok(actor) => actor,
+            _ => continue,
+        };
+        let mut action = match actor.action.take() {
+            Some(action) => action,
+            None if is_player => break,
+            None => Action::Wait,
+        };
+
+        if let Action::Move(direction) = action {
+            let (dx, dy) = direction.offset();
+            let (x, y) = (tile_pos.x as i32 + dx, tile_pos.y as i32 + dy);
+            let target = TilePos {
+                x: x as u32,
+                y: y as u32,
+            };
+
+            match occupants.get(&target) {
+                // Only the player and monsters fight, monsters don't hurt each other
+                Some((other, other_is_player)) if is_player != *other_is_player => {
+                    action = Action::Attack(*other);
+                }
+                // Only the player opens doors and chests
+                None if is_player && openable.contains_key(&target) => {
+                    action = Action::Open(openable[&target]);
+                }
+                None if !blocking.contains(&target) && level.passable(x, y, materials.set()) => {
+                    occupants.remove(&tile_pos);
+                    occupants.insert(target, (entity, is_player));
+                    *tile_pos = target;
+                }
+                _ if is_player => {
+                    // Bumping into a wall doesn't take a turn
+                    break;
+                }
+                _ => action = Action::Wait,
+            }
+        }
+
+        actor.energy -= action.cost();
+        action_writer.send(ActionEvent { entity, action });
+        if is_player {
+            turn.0 += 1;
+            turn_writer.send(TurnEvent {
+                entity,
+                turn: turn.0,
+            });
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{game::components::NORMAL_SPEED, level::Level};
+
+    /// Everyone who acted, in order
+    #[derive(Resource, Default)]
+    struct ActionLog(Vec<Entity>);
+
+    fn log_actions(mut log: ResMut<ActionLog>, mut action_reader: EventReader<ActionEvent>) {
+        log.0.extend(action_reader.iter().map(|event| event.entity));
+    }
+
+    fn turn_app() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, TurnPlugin))
+            .add_state::<GameState>()
+            .init_resource::<ActionLog>()
+            .add_systems(Update, log_actions.in_set(TurnSet::Resolve))
+            .insert_resource(ActiveLevel(Level::blank(10, 10)))
+            .insert_resource(NextState(Some(GameState::InGame)));
+        app
+    }
+
+    fn spawn_actor(app: &mut App, speed: i32, x: u32) -> Entity {
+        app.world
+            .spawn((Actor::new(speed), TilePos { x, y: 5 }))
+            .id()
+    }
+
+    /**
+     * Run frames until the player has taken a turn, waiting whenever they are asked to act, and
+     * return who acted on the way
+     */
+    fn next_turn(app: &mut App, player: Entity) -> Vec<Entity> {
+        let turn = app.world.resource::<Turn>().0;
+        for _ in 0..10 {
+            let mut actor = app.world.get_mut::<Actor>(player).unwrap();
+            if actor.action.is_none() {
+                actor.action = Some(Action::Wait);
+            }
+            app.update();
+            if app.world.resource::<Turn>().0 > turn {
+                return std::mem::take(&mut app.world.resource_mut::<ActionLog>().0);
+            }
+        }
+        panic!("The player never got a turn");
+    }
+
+    #[test]
+    fn actors_act_by_speed() {
+        let mut app = turn_app();
+        let player = spawn_actor(&mut app, NORMAL_SPEED, 1);
+        app.world.entity_mut(player).insert(Player);
+        let fast = spawn_actor(&mut app, NORMAL_SPEED * 2, 3);
+        let slow = spawn_actor(&mut app, NORMAL_SPEED / 2, 5);
+
+        assert_eq!(next_turn(&mut app, player), vec![fast, player]);
+        assert_eq!(next_turn(&mut app, player), vec![fast, fast, player]);
+        assert_eq!(next_turn(&mut app, player), vec![fast, slow, fast, player]);
+        assert_eq!(next_turn(&mut app, player), vec![fast, fast, player]);
+        assert_eq!(app.world.resource::<Turn>().0, 4);
+    }
+
+    #[test]
+    fn player_wins_ties() {
+        let mut app = turn_app();
+        let monster = spawn_actor(&mut app, NORMAL_SPEED, 3);
+        let player = spawn_actor(&mut app, NORMAL_SPEED, 1);
+        app.world.entity_mut(player).insert(Player);
+
+        assert_eq!(next_turn(&mut app, player), vec![player]);
+        assert_eq!(next_turn(&mut app, player), vec![monster, player]);
+        assert_eq!(next_turn(&mut app, player), vec![monster, player]);
+    }
+
+    #[test]
+    fn time_stops_for_the_player() {
+        let mut app = turn_app();
+        let player = spawn_actor(&mut app, NORMAL_SPEED, 1);
+        app.world.entity_mut(player).insert(Player);
+        spawn_actor(&mut app, NORMAL_SPEED, 3);
+
+        for _ in 0..5 {
+            app.update();
+        }
+        assert!(app.world.resource::<ActionLog>().0.is_empty());
+        assert_eq!(app.world.resource::<Turn>().0, 0);
+    }
+}
@@ -0,0 +1,107 @@
+use super::{
+    components::{Door, FieldOfView, GameTilemap, Item, Monster, Player, RememberedTiles},
+    objects::Obstacles,
+};
+use crate::{
+    editor::prelude::TileMaterial,
+    effects::Flick,
+    get_single, get_some,
+    level::{fov::field_of_view, materials::Materials, ActiveLevel},
+    DARK, LIGHT,
+};
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+/// How far remembered tiles are faded towards the background
+pub const REMEMBERED_FADE: f32 = 0.6;
+
+/**
+ * Recompute the field of view of everything that moved, or of everyone when a door opened or
+ * closed
+ */
+pub fn update_fov(
+    active_level: Option<Res<ActiveLevel>>,
+    materials: Materials,
+    obstacles: Obstacles,
+    changed_doors: Query<(), Changed<Door>>,
+    mut viewers: Query<(Ref<TilePos>, &mut FieldOfView)>,
+) {
+    let ActiveLevel(level) = get_some!(active_level.as_deref());
+    let set = materials.set();
+    let doors_changed = !changed_doors.is_empty();
+    let opaque = obstacles.opaque();
+
+    for (tile_pos, mut fov) in viewers.iter_mut() {
+        if !doors_changed && !tile_pos.is_changed() {
+            continue;
+        }
+        fov.visible = field_of_view(&tile_pos, fov.radius, &level.tiles.size(), |pos| {
+            level.opaque(pos.x as i32, pos.y as i32, set) || opaque.contains(pos)
+        });
+    }
+}
+
+/**
+ * Show what the player sees, darken what they remember and hide the rest
+ */
+pub fn render_fov(
+    materials: Materials,
+    remembered: Option<ResMut<RememberedTiles>>,
+    player_q: Query<&FieldOfView, (With<Player>, Changed<FieldOfView>)>,
+    tilemap_q: Query<&TileStorage, With<GameTilemap>>,
+    mut tile_q: Query<(&TilePos, &TileMaterial, &mut TileVisible, &mut TileColor)>,
+) {
+    let fov = get_single!(player_q);
+    let storage = get_single!(tilemap_q);
+    let mut remembered = get_some!(remembered);
+    let set = materials.set();
+
+    for tile_pos in fov.visible.iter() {
+        remembered.0.set(tile_pos, true);
+    }
+
+    for tile_entity in storage.iter().flatten() {
+        let (tile_pos, material, mut visible, mut color) = match tile_q.get_mut(*tile_entity) {
+            Ok(tile) => tile,
+            _ => continue,
+        };
+        let tint = rgba(set.map_or(Color::WHITE, |set| set.tint(material))) * rgba(LIGHT);
+
+        if fov.visible.contains(tile_pos) {
+            *visible = TileVisible(true);
+            color.0 = Color::rgba(tint.x, tint.y, tint.z, tint.w);
+        } else if remembered.0.get(tile_pos) == Some(&true) {
+            *visible = TileVisible(true);
+            let faded = tint.lerp(rgba(DARK), REMEMBERED_FADE);
+            color.0 = Color::rgba(faded.x, faded.y, faded.z, tint.w);
+        } else {
+            *visible = TileVisible(false);
+        }
+    }
+}
+
+fn rgba(color: Color) -> Vec4 {
+    Vec4::from(color.as_rgba_f32())
+}
+
+/**
+ * Monsters and items are only shown while the player can see them
+ */
+#[allow(clippy::type_complexity)]
+pub fn reveal_entities(
+    player_q: Query<&FieldOfView, With<Player>>,
+    // Blinking monsters are left to the effect until it's done
+    mut entity_q: Query<
+        (&TilePos, &mut Visibility),
+        (Or<(With<Monster>, With<Item>)>, Without<Flick>),
+    >,
+) {
+    let fov = get_single!(player_q);
+    for (tile_pos, mut visibility) in entity_q.iter_mut() {
+        *visibility = if fov.visible.contains(tile_pos) {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
@@ -0,0 +1,197 @@
+use bevy_ecs_tilemap::prelude::{TilePos, TilemapSize};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+
+/**
+ * Neighbors are packed into a bitmask clockwise beginning at north, so a tile connecting to
+ * north and east has N | NE | E set if the north east tile also connects.
+ */
+pub const N: u8 = 1;
+pub const NE: u8 = 2;
+pub const E: u8 = 4;
+pub const SE: u8 = 8;
+pub const S: u8 = 16;
+pub const SW: u8 = 32;
+pub const W: u8 = 64;
+pub const NW: u8 = 128;
+
+/// Offsets matching the bits above, in the same order
+pub const NEIGHBOR_OFFSETS: [(i32, i32, u8); 8] = [
+    (0, 1, N),
+    (1, 1, NE),
+    (1, 0, E),
+    (1, -1, SE),
+    (0, -1, S),
+    (-1, -1, SW),
+    (-1, 0, W),
+    (-1, 1, NW),
+];
+
+/**
+ * How a material picks its atlas index from the neighbors it connects to
+ */
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind")]
+pub enum AutotileRules {
+    /// 16 atlas indices, indexed by the cardinal mask where N = 1, E = 2, S = 4 and W = 8
+    Cardinal { indices: Vec<u32> },
+    /// Atlas indices for the 47 distinct blob masks. Masks without an entry fall back to the
+    /// cardinal table, if any.
+    Blob {
+        indices: HashMap<u8, u32>,
+        #[serde(default)]
+        cardinal: Option<Vec<u32>>,
+    },
+}
+
+impl AutotileRules {
+    /**
+     * The atlas index for a full 8 neighbor mask, if the rules cover it
+     */
+    pub fn resolve(&self, mask: u8) -> Option<u32> {
+        match self {
+            AutotileRules::Cardinal { indices } => {
+                indices.get(cardinal_mask(mask) as usize).copied()
+            }
+            AutotileRules::Blob { indices, cardinal } => {
+                indices.get(&blob_mask(mask)).copied().or_else(|| {
+                    cardinal
+                        .as_ref()?
+                        .get(cardinal_mask(mask) as usize)
+                        .copied()
+                })
+            }
+        }
+    }
+}
+
+/**
+ * Build the neighbor mask from a function telling whether the tile at an offset connects
+ */
+pub fn neighbor_mask(connects: impl Fn(i32, i32) -> bool) -> u8 {
+    NEIGHBOR_OFFSETS
+        .iter()
+        .filter(|(dx, dy, _)| connects(*dx, *dy))
+        .fold(0, |mask, (_, _, bit)| mask | bit)
+}
+
+/**
+ * Reduce a mask to its 4 cardinal bits, N = 1, E = 2, S = 4 and W = 8
+ */
+pub fn cardinal_mask(mask: u8) -> u8 {
+    [N, E, S, W]
+        .iter()
+        .enumerate()
+        .filter(|(_, bit)| mask & *bit != 0)
+        .fold(0, |cardinal, (idx, _)| cardinal | 1 << idx)
+}
+
+/**
+ * Drop corners that aren't enclosed by both of their cardinal neighbors. Corners only matter
+ * for the blob tile set when both sides connect, which leaves 47 distinct masks.
+ */
+pub fn blob_mask(mask: u8) -> u8 {
+    [(NE, N, E), (SE, S, E), (SW, S, W), (NW, N, W)]
+        .iter()
+        .filter(|(_, a, b)| mask & a == 0 || mask & b == 0)
+        .fold(mask, |mask, (corner, _, _)| mask & !corner)
+}
+
+/**
+ * Changing a tile can change how its neighbors autotile, so every changed position drags its
+ * eight neighbors along
+ */
+pub fn affected_positions<'a>(
+    changed: impl IntoIterator<Item = &'a TilePos>,
+    size: &TilemapSize,
+) -> HashSet<TilePos> {
+    let mut affected = HashSet::new();
+    for pos in changed {
+        affected.insert(*pos);
+        for (dx, dy, _) in NEIGHBOR_OFFSETS {
+            let (x, y) = (pos.x as i32 + dx, pos.y as i32 + dy);
+            if x >= 0 && y >= 0 && (x as u32) < size.x && (y as u32) < size.y {
+                affected.insert(TilePos {
+                    x: x as u32,
+                    y: y as u32,
+                });
+            }
+        }
+    }
+
+    affected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CROSS: u8 = N | E | S | W;
+    const ELBOW: u8 = S | SW | W;
+    /// The wall table of `tiles.materials.json`
+    const WALL_INDICES: [u32; 16] = [3, 12, 14, 8, 13, 10, 2, 6, 9, 16, 3, 7, 4, 1, 0, 5];
+
+    /**
+     * The mask of a 3x3 neighborhood written top row first, with `#` for connecting tiles
+     */
+    fn mask_of(rows: [&str; 3]) -> u8 {
+        neighbor_mask(|dx, dy| rows[(1 - dy) as usize].as_bytes()[(dx + 1) as usize] == b'#')
+    }
+
+    fn rules() -> (AutotileRules, AutotileRules) {
+        let cardinal = AutotileRules::Cardinal {
+            indices: WALL_INDICES.to_vec(),
+        };
+        let blob = AutotileRules::Blob {
+            indices: HashMap::from([(0, 100), (CROSS, 101), (255, 102), (ELBOW, 103)]),
+            cardinal: Some(WALL_INDICES.to_vec()),
+        };
+        (cardinal, blob)
+    }
+
+    #[test]
+    fn golden_neighborhoods() {
+        let (cardinal_rules, blob_rules) = rules();
+        // Neighborhood, mask, cardinal mask, blob mask, cardinal index, blob index
+        let cases = [
+            (["...", ".#.", "..."], 0, 0, 0, 3, 100),
+            ([".#.", ".#.", "..."], N, 1, N, 12, 12),
+            ([".##", ".##", "..."], N | NE | E, 3, N | NE | E, 8, 8),
+            ([".#.", ".##", "..."], N | E, 3, N | E, 8, 8),
+            ([".##", ".#.", "..."], N | NE, 1, N, 12, 12),
+            (["#.#", ".#.", "#.#"], NE | SE | SW | NW, 0, 0, 3, 100),
+            (["...", "###", "..."], E | W, 10, E | W, 3, 3),
+            ([".#.", "###", ".#."], CROSS, 15, CROSS, 5, 101),
+            (["#..", "##.", "##."], ELBOW | NW, 12, ELBOW, 4, 103),
+            (["###", "###", "###"], 255, 15, 255, 5, 102),
+        ];
+
+        for (rows, mask, cardinal, blob, cardinal_index, blob_index) in cases {
+            assert_eq!(mask_of(rows), mask, "mask of {:?}", rows);
+            assert_eq!(cardinal_mask(mask), cardinal, "cardinal mask of {:?}", rows);
+            assert_eq!(blob_mask(mask), blob, "blob mask of {:?}", rows);
+            assert_eq!(cardinal_rules.resolve(mask), Some(cardinal_index));
+            assert_eq!(blob_rules.resolve(mask), Some(blob_index), "{:?}", rows);
+        }
+    }
+
+    #[test]
+    fn blob_corner_needs_both_cardinals() {
+        for (corner, a, b) in [(NE, N, E), (SE, S, E), (SW, S, W), (NW, N, W)] {
+            assert_eq!(blob_mask(corner), 0);
+            assert_eq!(blob_mask(corner | a), a);
+            assert_eq!(blob_mask(corner | b), b);
+            assert_eq!(blob_mask(corner | a | b), corner | a | b);
+        }
+    }
+
+    #[test]
+    fn blob_without_fallback() {
+        let rules = AutotileRules::Blob {
+            indices: HashMap::from([(0, 7)]),
+            cardinal: None,
+        };
+        assert_eq!(rules.resolve(NE), Some(7));
+        assert_eq!(rules.resolve(N), None);
+    }
+}
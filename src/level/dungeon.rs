@@ -0,0 +1,71 @@
+use super::{
+    generate::{place_stairs, Generator},
+    load_asset_json, Level,
+};
+use serde::{Deserialize, Serialize};
+use std::io;
+
+/// Where a floor of a dungeon comes from
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type")]
+pub enum FloorSource {
+    /// A hand authored level, by asset path
+    Level { path: String },
+    /// A level generated from a seed, with stairs added as needed
+    Generated {
+        generator: Generator,
+        width: u32,
+        height: u32,
+        seed: u64,
+    },
+}
+
+impl FloorSource {
+    /**
+     * The level for the floor. Hand authored levels are expected to have their stairs already.
+     */
+    pub fn build(&self, stairs_up: bool, stairs_down: bool) -> io::Result<Level> {
+        match self {
+            FloorSource::Level { path } => load_asset_json(path),
+            FloorSource::Generated {
+                generator,
+                width,
+                height,
+                seed,
+            } => {
+                let mut level = Level::blank(*width, *height);
+                level.name = format!("{} {}", generator.name(), seed);
+                level.tiles = generator
+                    .generate(*width, *height, *seed)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+                place_stairs(&mut level.tiles, stairs_up, stairs_down);
+                Ok(level)
+            }
+        }
+    }
+}
+
+/**
+ * A stack of floors connected by stairs, e.g. `dungeons/crypt.dungeon.json`. The player starts
+ * on the player spawn of the first floor, and stairs down on one floor lead to the stairs up
+ * on the next.
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DungeonDef {
+    pub name: String,
+    pub floors: Vec<FloorSource>,
+}
+
+impl DungeonDef {
+    /**
+     * Build every floor up front, so a broken floor is found before anyone starts playing
+     */
+    pub fn build_floors(&self) -> io::Result<Vec<Level>> {
+        let last = self.floors.len().saturating_sub(1);
+        self.floors
+            .iter()
+            .enumerate()
+            .map(|(idx, floor)| floor.build(idx > 0, idx < last))
+            .collect()
+    }
+}
@@ -0,0 +1,194 @@
+use super::{
+    materials::MaterialSet,
+    tiled::{self, TiledTable},
+    Grid, Level,
+};
+use crate::editor::prelude::TileMaterial;
+use bevy_ecs_tilemap::tiles::TilePos;
+use std::{collections::HashMap, fmt::Write, fs};
+
+/// Characters handed out to materials without a usable icon
+const SPARE_SYMBOLS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+/// The file formats a level can be read from and written to, told apart by extension
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LevelFormat {
+    /// The editor's own format, `.level.json`
+    Json,
+    /// One character per tile under a legend of materials, `.txt`. Placements aren't kept.
+    Text,
+    /// Tiled's XML map format, `.tmx`. Tiles are mapped to materials by a [`TiledTable`].
+    Tmx,
+    /// Tiled's JSON map format, `.tmj`
+    Tmj,
+}
+
+impl LevelFormat {
+    pub fn from_path(path: &str) -> Option<Self> {
+        match path {
+            _ if path.ends_with(".json") => Some(LevelFormat::Json),
+            _ if path.ends_with(".txt") => Some(LevelFormat::Text),
+            _ if path.ends_with(".tmx") => Some(LevelFormat::Tmx),
+            _ if path.ends_with(".tmj") => Some(LevelFormat::Tmj),
+            _ => None,
+        }
+    }
+
+    /**
+     * Whether monsters, items and objects survive being written in this format
+     */
+    pub fn keeps_placements(&self) -> bool {
+        match self {
+            LevelFormat::Json | LevelFormat::Tmx | LevelFormat::Tmj => true,
+            LevelFormat::Text => false,
+        }
+    }
+
+    pub fn read(&self, contents: &str, table: &TiledTable) -> Result<Level, String> {
+        match self {
+            LevelFormat::Json => Level::from_json(contents).map_err(|err| err.to_string()),
+            LevelFormat::Text => from_text(contents),
+            LevelFormat::Tmx => tiled::from_tmx(contents, table),
+            LevelFormat::Tmj => tiled::from_tmj(contents, table),
+        }
+    }
+
+    /**
+     * The level in this format. Material icons are used as characters in text where they're
+     * unique.
+     */
+    pub fn write(
+        &self,
+        level: &Level,
+        materials: Option<&MaterialSet>,
+        table: &TiledTable,
+    ) -> Result<String, String> {
+        match self {
+            LevelFormat::Json => level.to_json().map_err(|err| err.to_string()),
+            LevelFormat::Text => Ok(to_text(level, materials)),
+            LevelFormat::Tmx => tiled::to_tmx(level, table),
+            LevelFormat::Tmj => tiled::to_tmj(level, table),
+        }
+    }
+}
+
+/**
+ * Read a level from a file path in the format of its extension
+ */
+pub fn read_level(path: &str, table: &TiledTable) -> Result<Level, String> {
+    let format =
+        LevelFormat::from_path(path).ok_or_else(|| format!("Unknown level format: {}", path))?;
+    let contents = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+    format
+        .read(&contents, table)
+        .map_err(|err| format!("{}: {}", path, err))
+}
+
+/**
+ * Write a level to a file path in the format of its extension
+ */
+pub fn write_level(
+    level: &Level,
+    path: &str,
+    materials: Option<&MaterialSet>,
+    table: &TiledTable,
+) -> Result<(), String> {
+    let format =
+        LevelFormat::from_path(path).ok_or_else(|| format!("Unknown level format: {}", path))?;
+    let contents = format.write(level, materials, table)?;
+    fs::write(path, contents).map_err(|err| format!("{}: {}", path, err))
+}
+
+/**
+ * A header with the name and author, a legend line per material and the rows of tiles, top row
+ * first so the text looks like the map
+ */
+fn to_text(level: &Level, materials: Option<&MaterialSet>) -> String {
+    let mut symbols: Vec<(char, TileMaterial)> = Vec::new();
+    for (_, material) in level.tiles.iter() {
+        if symbols.iter().any(|(_, other)| other == material) {
+            continue;
+        }
+        let icon = materials
+            .and_then(|set| set.get(material))
+            .and_then(|def| def.icon.as_ref())
+            .and_then(|icon| icon.chars().next());
+        let symbol = icon
+            .into_iter()
+            .chain(SPARE_SYMBOLS.chars())
+            .find(|symbol| {
+                !symbol.is_whitespace() && symbols.iter().all(|(used, _)| used != symbol)
+            })
+            .unwrap_or('?');
+        symbols.push((symbol, material.clone()));
+    }
+
+    let mut text = String::new();
+    let _ = writeln!(text, "name: {}", level.name);
+    let _ = writeln!(text, "author: {}", level.author);
+    for (symbol, material) in symbols.iter() {
+        let _ = writeln!(text, "legend: {} {}", symbol, material.name());
+    }
+    let _ = writeln!(text, "tiles:");
+    for y in (0..level.tiles.height()).rev() {
+        for x in 0..level.tiles.width() {
+            let material = level.tiles.get(&TilePos { x, y });
+            let symbol = symbols
+                .iter()
+                .find(|(_, other)| Some(other) == material)
+                .map_or('?', |(symbol, _)| *symbol);
+            text.push(symbol);
+        }
+        text.push('\n');
+    }
+    text
+}
+
+fn from_text(text: &str) -> Result<Level, String> {
+    let mut level = Level::blank(0, 0);
+    let mut legend = HashMap::new();
+    let mut lines = text.lines();
+    for line in lines.by_ref() {
+        if line == "tiles:" {
+            break;
+        } else if let Some(name) = line.strip_prefix("name:") {
+            level.name = name.trim_start().to_string();
+        } else if let Some(author) = line.strip_prefix("author:") {
+            level.author = author.trim_start().to_string();
+        } else if let Some(entry) = line.strip_prefix("legend: ") {
+            let mut chars = entry.chars();
+            match (chars.next(), chars.next(), chars.as_str()) {
+                (Some(symbol), Some(' '), name) if !name.is_empty() => {
+                    legend.insert(symbol, TileMaterial::from(name.to_string()));
+                }
+                _ => return Err(format!("Bad legend entry: {}", line)),
+            }
+        } else if !line.trim().is_empty() {
+            return Err(format!("Unexpected line: {}", line));
+        }
+    }
+
+    let rows: Vec<Vec<char>> = lines
+        .filter(|line| !line.is_empty())
+        .map(|line| line.chars().collect())
+        .collect();
+    let width = rows.first().map_or(0, |row| row.len()) as u32;
+    let height = rows.len() as u32;
+    if rows.iter().any(|row| row.len() as u32 != width) {
+        return Err("The rows of tiles differ in length".to_string());
+    }
+
+    level.tiles = Grid::new(width, height, TileMaterial::Floor);
+    for (row_idx, row) in rows.iter().enumerate() {
+        let y = height - 1 - row_idx as u32;
+        for (x, symbol) in row.iter().enumerate() {
+            let material = legend
+                .get(symbol)
+                .ok_or_else(|| format!("'{}' isn't in the legend", symbol))?;
+            level
+                .tiles
+                .set(&TilePos { x: x as u32, y }, material.clone());
+        }
+    }
+    Ok(level)
+}
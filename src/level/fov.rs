@@ -0,0 +1,294 @@
+use bevy_ecs_tilemap::prelude::{TilePos, TilemapSize};
+use std::collections::HashSet;
+
+/**
+ * The tiles visible from `origin`, using symmetric recursive shadowcasting. Visibility is
+ * symmetric, so if a floor tile A sees floor tile B then B also sees A. Opaque tiles are
+ * visible themselves but hide everything behind them, and everything outside the map is opaque.
+ */
+pub fn field_of_view(
+    origin: &TilePos,
+    radius: u32,
+    size: &TilemapSize,
+    opaque: impl Fn(&TilePos) -> bool,
+) -> HashSet<TilePos> {
+    let mut visible = HashSet::new();
+    visible.insert(*origin);
+
+    for quadrant in [
+        Quadrant::North,
+        Quadrant::East,
+        Quadrant::South,
+        Quadrant::West,
+    ] {
+        let caster = Caster {
+            origin: (origin.x as i32, origin.y as i32),
+            quadrant,
+            radius: radius as i32,
+            size,
+            opaque: &opaque,
+        };
+        caster.scan(
+            Row {
+                depth: 1,
+                start: Slope::new(-1, 1),
+                end: Slope::new(1, 1),
+            },
+            &mut visible,
+        );
+    }
+
+    visible
+}
+
+#[derive(Clone, Copy)]
+enum Quadrant {
+    North,
+    East,
+    South,
+    West,
+}
+
+/// A rational slope, `num / den` with a positive denominator
+#[derive(Clone, Copy)]
+struct Slope {
+    num: i32,
+    den: i32,
+}
+
+impl Slope {
+    fn new(num: i32, den: i32) -> Self {
+        Self { num, den }
+    }
+
+    /// The slope through the near corner of a tile
+    fn of_tile(depth: i32, col: i32) -> Self {
+        Self::new(2 * col - 1, 2 * depth)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Row {
+    depth: i32,
+    start: Slope,
+    end: Slope,
+}
+
+impl Row {
+    /**
+     * Columns between the slopes. Tiles cut in half by a slope count as inside it.
+     */
+    fn columns(&self) -> std::ops::RangeInclusive<i32> {
+        let min = (2 * self.depth * self.start.num + self.start.den).div_euclid(2 * self.start.den);
+        let max = -(-(2 * self.depth * self.end.num - self.end.den)).div_euclid(2 * self.end.den);
+        min..=max
+    }
+
+    /**
+     * Only floor tiles whose centers lie between the slopes are visible, which keeps the field
+     * of view symmetric
+     */
+    fn is_symmetric(&self, col: i32) -> bool {
+        col * self.start.den >= self.depth * self.start.num
+            && col * self.end.den <= self.depth * self.end.num
+    }
+
+    fn next(&self) -> Self {
+        Self {
+            depth: self.depth + 1,
+            ..*self
+        }
+    }
+}
+
+struct Caster<'a, F: Fn(&TilePos) -> bool> {
+    origin: (i32, i32),
+    quadrant: Quadrant,
+    radius: i32,
+    size: &'a TilemapSize,
+    opaque: &'a F,
+}
+
+impl<F: Fn(&TilePos) -> bool> Caster<'_, F> {
+    /// Map a row and column of the quadrant to a tile, if it's on the map
+    fn tile(&self, depth: i32, col: i32) -> Option<TilePos> {
+        let (ox, oy) = self.origin;
+        let (x, y) = match self.quadrant {
+            Quadrant::North => (ox + col, oy + depth),
+            Quadrant::South => (ox + col, oy - depth),
+            Quadrant::East => (ox + depth, oy + col),
+            Quadrant::West => (ox - depth, oy + col),
+        };
+
+        if x < 0 || y < 0 || x as u32 >= self.size.x || y as u32 >= self.size.y {
+            return None;
+        }
+        Some(TilePos {
+            x: x as u32,
+            y: y as u32,
+        })
+    }
+
+    fn is_wall(&self, depth: i32, col: i32) -> bool {
+        self.tile(depth, col)
+            .is_none_or(|tile| (self.opaque)(&tile))
+    }
+
+    fn in_radius(&self, depth: i32, col: i32) -> bool {
+        depth * depth + col * col <= self.radius * self.radius
+    }
+
+    fn scan(&self, mut row: Row, visible: &mut HashSet<TilePos>) {
+        if row.depth > self.radius {
+            return;
+        }
+
+        let mut prev_wall = None;
+        for col in row.columns() {
+            let wall = self.is_wall(row.depth, col);
+            if (wall || row.is_symmetric(col)) && self.in_radius(row.depth, col) {
+                if let Some(tile) = self.tile(row.depth, col) {
+                    visible.insert(tile);
+                }
+            }
+
+            if prev_wall == Some(true) && !wall {
+                row.start = Slope::of_tile(row.depth, col);
+            }
+            if prev_wall == Some(false) && wall {
+                let mut next = row.next();
+                next.end = Slope::of_tile(row.depth, col);
+                self.scan(next, visible);
+            }
+            prev_wall = Some(wall);
+        }
+
+        if prev_wall == Some(false) {
+            self.scan(row.next(), visible);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /**
+     * Wall positions and size of a map drawn top row first, with `#` for walls
+     */
+    fn map(rows: &[&str]) -> (HashSet<TilePos>, TilemapSize) {
+        let height = rows.len() as u32;
+        let walls = rows
+            .iter()
+            .enumerate()
+            .flat_map(|(row, line)| {
+                line.chars()
+                    .enumerate()
+                    .filter(|(_, cell)| *cell == '#')
+                    .map(move |(x, _)| TilePos {
+                        x: x as u32,
+                        y: height - 1 - row as u32,
+                    })
+            })
+            .collect();
+        let size = TilemapSize {
+            x: rows[0].len() as u32,
+            y: height,
+        };
+        (walls, size)
+    }
+
+    fn fov(walls: &HashSet<TilePos>, size: &TilemapSize, x: u32, y: u32) -> HashSet<TilePos> {
+        field_of_view(&TilePos { x, y }, 20, size, |pos| walls.contains(pos))
+    }
+
+    #[test]
+    fn pillar_casts_shadow() {
+        let (walls, size) = map(&[
+            ".........",
+            ".........",
+            ".........",
+            "....#....",
+            ".........",
+            ".........",
+            ".........",
+        ]);
+        let visible = fov(&walls, &size, 4, 1);
+
+        assert!(visible.contains(&TilePos { x: 4, y: 3 }));
+        assert!(!visible.contains(&TilePos { x: 4, y: 4 }));
+        assert!(!visible.contains(&TilePos { x: 4, y: 6 }));
+        assert!(visible.contains(&TilePos { x: 2, y: 6 }));
+        assert!(visible.contains(&TilePos { x: 6, y: 6 }));
+    }
+
+    #[test]
+    fn corridor() {
+        let (walls, size) = map(&[
+            "#########",
+            "#.......#",
+            "#######.#",
+            "#######.#",
+            "#########",
+        ]);
+        let visible = fov(&walls, &size, 1, 3);
+
+        for x in 0..9 {
+            assert!(visible.contains(&TilePos { x, y: 3 }));
+            assert!(visible.contains(&TilePos { x, y: 4 }));
+        }
+        // Around the corner
+        assert!(!visible.contains(&TilePos { x: 7, y: 1 }));
+        assert!(!visible.contains(&TilePos { x: 7, y: 0 }));
+    }
+
+    #[test]
+    fn radius_cutoff() {
+        let size = TilemapSize { x: 21, y: 21 };
+        let origin = TilePos { x: 10, y: 10 };
+        let visible = field_of_view(&origin, 3, &size, |_| false);
+
+        let in_radius: HashSet<TilePos> = (7..=13)
+            .flat_map(|x| (7..=13).map(move |y| TilePos { x, y }))
+            .filter(|pos| {
+                let (dx, dy) = (pos.x as i32 - 10, pos.y as i32 - 10);
+                dx * dx + dy * dy <= 9
+            })
+            .collect();
+        assert_eq!(visible, in_radius);
+    }
+
+    #[test]
+    fn symmetric() {
+        let (walls, size) = map(&[
+            "############",
+            "#....#.....#",
+            "#.#......#.#",
+            "#....##....#",
+            "#.#........#",
+            "#.....#..#.#",
+            "#..#.......#",
+            "############",
+        ]);
+        let floors: Vec<TilePos> = (0..size.x)
+            .flat_map(|x| (0..size.y).map(move |y| TilePos { x, y }))
+            .filter(|pos| !walls.contains(pos))
+            .collect();
+        let views: Vec<HashSet<TilePos>> = floors
+            .iter()
+            .map(|pos| fov(&walls, &size, pos.x, pos.y))
+            .collect();
+
+        for (a, view_a) in floors.iter().zip(views.iter()) {
+            for (b, view_b) in floors.iter().zip(views.iter()) {
+                assert_eq!(
+                    view_a.contains(b),
+                    view_b.contains(a),
+                    "{:?} and {:?} don't see each other alike",
+                    a,
+                    b
+                );
+            }
+        }
+    }
+}
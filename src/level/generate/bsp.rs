@@ -0,0 +1,220 @@
+use super::{carve_corridor, Rect};
+use crate::{editor::prelude::TileMaterial, level::Grid};
+use bevy_ecs_tilemap::tiles::TilePos;
+use bevy_turborand::{DelegatedRng, RngComponent};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct BspParams {
+    /// Areas are only split when both halves end up at least this wide
+    pub min_leaf: u32,
+    /// Smallest width and height of a room, walls not included
+    pub min_room: u32,
+    /// How many times the map is split at most, so up to 2^depth rooms
+    pub max_depth: u32,
+}
+
+impl Default for BspParams {
+    fn default() -> Self {
+        Self {
+            min_leaf: 8,
+            min_room: 4,
+            max_depth: 5,
+        }
+    }
+}
+
+/**
+ * Split the map into a tree of areas, put a room in each leaf and join the rooms of sibling
+ * areas with corridors. Every split is bridged once, so all rooms are connected. The player
+ * spawns in the middle of the first room. Fails when the map can't hold a room inside its
+ * outer walls.
+ */
+pub fn generate(
+    width: u32,
+    height: u32,
+    params: &BspParams,
+    rng: &mut RngComponent,
+) -> Result<Grid<TileMaterial>, String> {
+    if width < 3 || height < 3 {
+        return Err("Rooms need a map at least 3x3 tiles big".to_string());
+    }
+    let mut tiles = Grid::new(width, height, TileMaterial::Wall);
+    let params = BspParams {
+        min_room: params.min_room.max(1),
+        // Leaves need room for a room and the walls around it
+        min_leaf: params.min_leaf.max(params.min_room.max(1) + 2),
+        ..*params
+    };
+
+    let area = Rect {
+        x: 0,
+        y: 0,
+        width,
+        height,
+    };
+    let rooms = build(&mut tiles, &area, 0, &params, rng);
+    if let Some(room) = rooms.first() {
+        tiles.set(&room.center(), TileMaterial::PlayerSpawn);
+    }
+    Ok(tiles)
+}
+
+/**
+ * Split `area` across its longer side, or `None` if it is too small to hold two leaves
+ */
+fn split(area: &Rect, min_leaf: u32, rng: &mut RngComponent) -> Option<(Rect, Rect)> {
+    let across_x = match (area.width >= min_leaf * 2, area.height >= min_leaf * 2) {
+        (false, false) => return None,
+        (true, false) => true,
+        (false, true) => false,
+        _ if area.width * 4 > area.height * 5 => true,
+        _ if area.height * 4 > area.width * 5 => false,
+        _ => rng.bool(),
+    };
+
+    Some(if across_x {
+        let at = rng.u32(min_leaf..=area.width - min_leaf);
+        (
+            Rect { width: at, ..*area },
+            Rect {
+                x: area.x + at,
+                width: area.width - at,
+                ..*area
+            },
+        )
+    } else {
+        let at = rng.u32(min_leaf..=area.height - min_leaf);
+        (
+            Rect {
+                height: at,
+                ..*area
+            },
+            Rect {
+                y: area.y + at,
+                height: area.height - at,
+                ..*area
+            },
+        )
+    })
+}
+
+/**
+ * Carve the rooms below `area` and return them
+ */
+fn build(
+    tiles: &mut Grid<TileMaterial>,
+    area: &Rect,
+    depth: u32,
+    params: &BspParams,
+    rng: &mut RngComponent,
+) -> Vec<Rect> {
+    let halves = if depth < params.max_depth {
+        split(area, params.min_leaf, rng)
+    } else {
+        None
+    };
+    let (first, second) = match halves {
+        Some(halves) => halves,
+        None => return carve_room(tiles, area, params, rng).into_iter().collect(),
+    };
+
+    let mut rooms = build(tiles, &first, depth + 1, params, rng);
+    let other = build(tiles, &second, depth + 1, params, rng);
+    if !rooms.is_empty() && !other.is_empty() {
+        let from = rooms[rng.usize(0..rooms.len())].center();
+        let to = other[rng.usize(0..other.len())].center();
+        carve_corridor(tiles, &from, &to, rng.bool());
+    }
+    rooms.extend(other);
+    rooms
+}
+
+/**
+ * A room of random size somewhere inside the leaf, keeping a wall between it and the leaf's
+ * edges. Leaves too small for any room stay solid.
+ */
+fn carve_room(
+    tiles: &mut Grid<TileMaterial>,
+    area: &Rect,
+    params: &BspParams,
+    rng: &mut RngComponent,
+) -> Option<Rect> {
+    let (max_width, max_height) = (area.width.checked_sub(2)?, area.height.checked_sub(2)?);
+    if max_width == 0 || max_height == 0 {
+        return None;
+    }
+
+    let width = rng.u32(params.min_room.min(max_width)..=max_width);
+    let height = rng.u32(params.min_room.min(max_height)..=max_height);
+    let room = Rect {
+        x: area.x + 1 + rng.u32(0..=max_width - width),
+        y: area.y + 1 + rng.u32(0..=max_height - height),
+        width,
+        height,
+    };
+
+    for y in room.y..room.y + room.height {
+        for x in room.x..room.x + room.width {
+            tiles.set(&TilePos { x, y }, TileMaterial::Floor);
+        }
+    }
+    Some(room)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::generate::tests::{assert_playable, tiles};
+
+    fn rooms(width: u32, height: u32, params: &BspParams, seed: u64) -> Grid<TileMaterial> {
+        generate(width, height, params, &mut RngComponent::with_seed(seed)).unwrap()
+    }
+
+    #[test]
+    fn same_seed_same_rooms() {
+        let params = BspParams::default();
+        assert_eq!(rooms(48, 32, &params, 3), rooms(48, 32, &params, 3));
+        assert_ne!(rooms(48, 32, &params, 3), rooms(48, 32, &params, 4));
+    }
+
+    #[test]
+    fn every_room_can_be_reached() {
+        let params = [
+            BspParams::default(),
+            BspParams {
+                min_leaf: 4,
+                min_room: 1,
+                max_depth: 8,
+            },
+            // Leaves are widened to fit the rooms
+            BspParams {
+                min_leaf: 0,
+                min_room: 0,
+                max_depth: 3,
+            },
+        ];
+        for params in params.iter() {
+            for (width, height) in [(3, 3), (5, 40), (40, 5), (64, 48)] {
+                for seed in 0..10 {
+                    assert_playable(&rooms(width, height, params, seed));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn smallest_map_is_one_room() {
+        let map = rooms(3, 3, &BspParams::default(), 0);
+        assert_eq!(map, tiles(&["###", "#@#", "###"]));
+    }
+
+    #[test]
+    fn maps_under_3x3_are_refused() {
+        for (width, height) in [(0, 0), (2, 3), (3, 2), (1, 50)] {
+            let mut rng = RngComponent::with_seed(0);
+            let result = generate(width, height, &BspParams::default(), &mut rng);
+            assert!(result.is_err(), "{}x{}", width, height);
+        }
+    }
+}
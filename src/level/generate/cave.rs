@@ -0,0 +1,168 @@
+use super::remove_pockets;
+use crate::{editor::prelude::TileMaterial, level::Grid};
+use bevy_ecs_tilemap::tiles::TilePos;
+use bevy_turborand::{DelegatedRng, RngComponent};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct CaveParams {
+    /// Share of the map that starts out as wall
+    pub fill: f32,
+    /// Smoothing passes over the initial noise
+    pub iterations: u32,
+}
+
+impl Default for CaveParams {
+    fn default() -> Self {
+        Self {
+            fill: 0.45,
+            iterations: 5,
+        }
+    }
+}
+
+/// Noise that smooths into solid wall is thrown away and drawn again this many times at most
+const MAX_ATTEMPTS: u32 = 10;
+
+/**
+ * Fill the map with random walls and smooth it into caves. A tile becomes wall when most of
+ * its eight neighbors are, and floor when few are. Only the largest cave is kept, and the
+ * player spawns somewhere in it. Fails when no cave opens up, like on maps too small for one.
+ */
+pub fn generate(
+    width: u32,
+    height: u32,
+    params: &CaveParams,
+    rng: &mut RngComponent,
+) -> Result<Grid<TileMaterial>, String> {
+    for _ in 0..MAX_ATTEMPTS {
+        let mut tiles = smoothed_noise(width, height, params, rng);
+        let cave = remove_pockets(&mut tiles);
+        if !cave.is_empty() {
+            tiles.set(&cave[rng.usize(0..cave.len())], TileMaterial::PlayerSpawn);
+            return Ok(tiles);
+        }
+    }
+    Err(format!("No cave opened up in {} attempts", MAX_ATTEMPTS))
+}
+
+/**
+ * Random walls smoothed into floor and wall, with every floor region still in place
+ */
+fn smoothed_noise(
+    width: u32,
+    height: u32,
+    params: &CaveParams,
+    rng: &mut RngComponent,
+) -> Grid<TileMaterial> {
+    let border = |x: u32, y: u32| x == 0 || y == 0 || x + 1 >= width || y + 1 >= height;
+    let mut walls = Grid::new(width, height, true);
+    for y in 0..height {
+        for x in 0..width {
+            let wall = border(x, y) || rng.f32() < params.fill;
+            walls.set(&TilePos { x, y }, wall);
+        }
+    }
+
+    for _ in 0..params.iterations {
+        let previous = walls.clone();
+        for y in 0..height {
+            for x in 0..width {
+                let pos = TilePos { x, y };
+                let wall = match wall_neighbors(&previous, &pos) {
+                    _ if border(x, y) => true,
+                    n if n >= 5 => true,
+                    n if n <= 3 => false,
+                    _ => previous.get(&pos) == Some(&true),
+                };
+                walls.set(&pos, wall);
+            }
+        }
+    }
+
+    let mut tiles = Grid::new(width, height, TileMaterial::Wall);
+    for (pos, wall) in walls.iter() {
+        if !wall {
+            tiles.set(&pos, TileMaterial::Floor);
+        }
+    }
+    tiles
+}
+
+/**
+ * Walls among the eight neighbors of a tile, counting the outside as wall
+ */
+fn wall_neighbors(walls: &Grid<bool>, pos: &TilePos) -> u32 {
+    let mut count = 0;
+    for dy in -1..=1 {
+        for dx in -1..=1 {
+            if (dx, dy) == (0, 0) {
+                continue;
+            }
+            let wall = walls.get_i(pos.x as i32 + dx, pos.y as i32 + dy);
+            if wall.copied().unwrap_or(true) {
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::generate::tests::assert_playable;
+
+    fn caves(
+        width: u32,
+        height: u32,
+        params: &CaveParams,
+        seed: u64,
+    ) -> Result<Grid<TileMaterial>, String> {
+        generate(width, height, params, &mut RngComponent::with_seed(seed))
+    }
+
+    #[test]
+    fn same_seed_same_caves() {
+        let params = CaveParams::default();
+        assert_eq!(caves(40, 30, &params, 9), caves(40, 30, &params, 9));
+        assert_ne!(caves(40, 30, &params, 9), caves(40, 30, &params, 10));
+    }
+
+    #[test]
+    fn only_one_cave_is_kept() {
+        for fill in [0.3, 0.45, 0.55] {
+            let params = CaveParams {
+                fill,
+                ..CaveParams::default()
+            };
+            for seed in 0..10 {
+                assert_playable(&caves(40, 30, &params, seed).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn solid_noise_has_no_room_for_a_spawn() {
+        let solid = CaveParams {
+            fill: 1.0,
+            ..CaveParams::default()
+        };
+        assert!(caves(40, 30, &solid, 0).is_err());
+        // Only border walls, which never open up
+        assert!(caves(2, 30, &CaveParams::default(), 0).is_err());
+    }
+
+    #[test]
+    fn smoothing_follows_the_neighbors() {
+        let walls = Grid::from_cells(
+            3,
+            3,
+            vec![true, true, true, true, false, true, false, false, false],
+        )
+        .unwrap();
+        assert_eq!(wall_neighbors(&walls, &TilePos { x: 1, y: 1 }), 5);
+        // The outside counts as wall
+        assert_eq!(wall_neighbors(&walls, &TilePos { x: 0, y: 2 }), 6);
+    }
+}
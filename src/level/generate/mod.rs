@@ -0,0 +1,318 @@
+use super::{pathfinding::DijkstraMap, Grid};
+use crate::editor::prelude::TileMaterial;
+use bevy_ecs_tilemap::tiles::TilePos;
+use bevy_turborand::RngComponent;
+use bsp::BspParams;
+use cave::CaveParams;
+use serde::{Deserialize, Serialize};
+use walk::WalkParams;
+use wfc::WfcParams;
+
+pub mod bsp;
+pub mod cave;
+pub mod walk;
+pub mod wfc;
+
+/// A procedural level generator and its parameters
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind")]
+pub enum Generator {
+    /// Rooms joined by corridors
+    Bsp(BspParams),
+    /// Smoothed noise
+    Cave(CaveParams),
+    /// A random walk
+    Walk(WalkParams),
+    /// Patterns learned from an example level
+    Wfc(WfcParams),
+}
+
+impl Generator {
+    /**
+     * Every generator with its default parameters
+     */
+    pub fn all() -> Vec<Generator> {
+        vec![
+            Generator::Bsp(BspParams::default()),
+            Generator::Cave(CaveParams::default()),
+            Generator::Walk(WalkParams::default()),
+            Generator::Wfc(WfcParams::default()),
+        ]
+    }
+
+    /**
+     * The generator with default parameters stored under a kind, e.g. `Bsp`
+     */
+    pub fn from_kind(kind: &str) -> Option<Generator> {
+        Self::all()
+            .into_iter()
+            .find(|generator| generator.kind().eq_ignore_ascii_case(kind))
+    }
+
+    /**
+     * The tag the generator is stored under in dungeon files
+     */
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Generator::Bsp(_) => "Bsp",
+            Generator::Cave(_) => "Cave",
+            Generator::Walk(_) => "Walk",
+            Generator::Wfc(_) => "Wfc",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Generator::Bsp(_) => "Rooms",
+            Generator::Cave(_) => "Caves",
+            Generator::Walk(_) => "Drunkard's walk",
+            Generator::Wfc(_) => "From example",
+        }
+    }
+
+    /**
+     * Generate a map of the given size. The same seed always gives the same map. Generators
+     * fail when the map is too small for them, and generators that learn from an example also
+     * when the example can't be loaded or its patterns can't fill the map.
+     */
+    pub fn generate(
+        &self,
+        width: u32,
+        height: u32,
+        seed: u64,
+    ) -> Result<Grid<TileMaterial>, String> {
+        let mut rng = RngComponent::with_seed(seed);
+        match self {
+            Generator::Bsp(params) => bsp::generate(width, height, params, &mut rng),
+            Generator::Cave(params) => cave::generate(width, height, params, &mut rng),
+            Generator::Walk(params) => walk::generate(width, height, params, &mut rng),
+            Generator::Wfc(params) => wfc::generate(width, height, params, &mut rng),
+        }
+    }
+}
+
+/// An axis aligned area of a map
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub fn center(&self) -> TilePos {
+        TilePos {
+            x: self.x + self.width / 2,
+            y: self.y + self.height / 2,
+        }
+    }
+}
+
+/**
+ * Dig an L shaped corridor of floor between two tiles, going along x first or along y first
+ */
+pub fn carve_corridor(tiles: &mut Grid<TileMaterial>, from: &TilePos, to: &TilePos, x_first: bool) {
+    let corner = match x_first {
+        true => TilePos { x: to.x, y: from.y },
+        false => TilePos { x: from.x, y: to.y },
+    };
+
+    for (start, end) in [(from, &corner), (&corner, to)] {
+        for y in start.y.min(end.y)..=start.y.max(end.y) {
+            for x in start.x.min(end.x)..=start.x.max(end.x) {
+                let pos = TilePos { x, y };
+                // Don't dig up the spawn or stairs
+                if tiles.get(&pos) == Some(&TileMaterial::Wall) {
+                    tiles.set(&pos, TileMaterial::Floor);
+                }
+            }
+        }
+    }
+}
+
+/**
+ * The largest region of connected tiles of a material. Ties go to the region found first, which
+ * keeps generated maps stable.
+ */
+pub fn largest_region(tiles: &Grid<TileMaterial>, material: &TileMaterial) -> Vec<TilePos> {
+    let mut largest = Vec::new();
+    let mut seen = Grid::new(tiles.width(), tiles.height(), false);
+    for (pos, other) in tiles.iter() {
+        if other != material || seen.get(&pos) == Some(&true) {
+            continue;
+        }
+        let region = tiles.region(&pos, false);
+        for pos in region.iter() {
+            seen.set(pos, true);
+        }
+        if region.len() > largest.len() {
+            largest = region;
+        }
+    }
+    largest
+}
+
+/**
+ * Wall up every floor region but the largest, so the whole map can be walked. Returns the
+ * positions of the region that was kept.
+ */
+pub fn remove_pockets(tiles: &mut Grid<TileMaterial>) -> Vec<TilePos> {
+    let kept = largest_region(tiles, &TileMaterial::Floor);
+    let mut keep = Grid::new(tiles.width(), tiles.height(), false);
+    for pos in kept.iter() {
+        keep.set(pos, true);
+    }
+
+    let pockets: Vec<TilePos> = tiles
+        .iter()
+        .filter(|(pos, material)| **material == TileMaterial::Floor && keep.get(pos) != Some(&true))
+        .map(|(pos, _)| pos)
+        .collect();
+    for pos in pockets.iter() {
+        tiles.set(pos, TileMaterial::Wall);
+    }
+    kept
+}
+
+/**
+ * Put stairs on a generated map for use as a dungeon floor. The stairs down go as far from the
+ * spawn as can be walked, and the stairs up replace the spawn.
+ */
+pub fn place_stairs(tiles: &mut Grid<TileMaterial>, stairs_up: bool, stairs_down: bool) {
+    let spawn = match tiles
+        .iter()
+        .find(|(_, material)| **material == TileMaterial::PlayerSpawn)
+    {
+        Some((spawn, _)) => spawn,
+        None => return,
+    };
+
+    if stairs_down {
+        let distances = DijkstraMap::new(&[spawn], &tiles.size(), |pos| {
+            matches!(tiles.get(pos), Some(TileMaterial::Floor))
+        });
+        let farthest = tiles
+            .iter()
+            .filter(|(_, material)| **material == TileMaterial::Floor)
+            .filter_map(|(pos, _)| Some((distances.distance(&pos)?, pos)))
+            .max_by_key(|(distance, pos)| (*distance, pos.y, pos.x));
+        if let Some((_, pos)) = farthest {
+            tiles.set(&pos, TileMaterial::StairsDown);
+        }
+    }
+    if stairs_up {
+        tiles.set(&spawn, TileMaterial::StairsUp);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /**
+     * A map from rows of `#` walls, `.` floor and `@` for the spawn, bottom row first so row
+     * indices match `y`
+     */
+    pub(super) fn tiles(rows: &[&str]) -> Grid<TileMaterial> {
+        let cells = rows
+            .iter()
+            .flat_map(|row| {
+                row.chars().map(|cell| match cell {
+                    '#' => TileMaterial::Wall,
+                    '@' => TileMaterial::PlayerSpawn,
+                    _ => TileMaterial::Floor,
+                })
+            })
+            .collect();
+        Grid::from_cells(rows[0].len() as u32, rows.len() as u32, cells).unwrap()
+    }
+
+    /**
+     * Check that a generated map is closed by walls, has one spawn and that every tile that
+     * isn't a wall can be walked to from it
+     */
+    pub(super) fn assert_playable(tiles: &Grid<TileMaterial>) {
+        let (width, height) = (tiles.width(), tiles.height());
+        for (pos, material) in tiles.iter() {
+            if pos.x == 0 || pos.y == 0 || pos.x + 1 == width || pos.y + 1 == height {
+                assert_eq!(*material, TileMaterial::Wall, "Open border at {:?}", pos);
+            }
+        }
+
+        let spawns: Vec<TilePos> = tiles
+            .iter()
+            .filter(|(_, material)| **material == TileMaterial::PlayerSpawn)
+            .map(|(pos, _)| pos)
+            .collect();
+        assert_eq!(spawns.len(), 1, "Spawns at {:?}", spawns);
+
+        let open = |pos: &TilePos| !matches!(tiles.get(pos), None | Some(TileMaterial::Wall));
+        let reach = DijkstraMap::new(&spawns, &tiles.size(), open);
+        for (pos, _) in tiles.iter() {
+            if open(&pos) {
+                assert!(reach.distance(&pos).is_some(), "Can't reach {:?}", pos);
+            }
+        }
+    }
+
+    #[test]
+    fn generators_repeat_with_the_same_seed() {
+        for generator in Generator::all() {
+            for seed in [0, 7, 12345] {
+                let map = generator.generate(24, 16, seed).unwrap();
+                // Examples may wall off areas, the spawn only goes in the largest
+                if !matches!(generator, Generator::Wfc(_)) {
+                    assert_playable(&map);
+                }
+                assert_eq!(
+                    generator.generate(24, 16, seed),
+                    Ok(map),
+                    "{}",
+                    generator.kind()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn generators_refuse_maps_without_room_for_a_spawn() {
+        for generator in Generator::all() {
+            for (width, height) in [(0, 0), (2, 2), (2, 10), (10, 2)] {
+                let map = generator.generate(width, height, 0);
+                assert!(map.is_err(), "{} on {}x{}", generator.kind(), width, height);
+            }
+        }
+    }
+
+    #[test]
+    fn remove_pockets_keeps_the_largest_cave() {
+        let mut map = tiles(&["#####", "#..##", "##..#", "#.#.#", "#####"]);
+        let kept = remove_pockets(&mut map);
+        let expected = tiles(&["#####", "#..##", "##..#", "###.#", "#####"]);
+        assert_eq!(map, expected);
+        assert_eq!(kept.len(), 5);
+    }
+
+    #[test]
+    fn stairs_down_go_farthest_from_the_spawn() {
+        let mut map = tiles(&["######", "#@...#", "####.#", "#....#", "######"]);
+        place_stairs(&mut map, true, true);
+        assert_eq!(
+            map.get(&TilePos { x: 1, y: 1 }),
+            Some(&TileMaterial::StairsUp)
+        );
+        assert_eq!(
+            map.get(&TilePos { x: 1, y: 3 }),
+            Some(&TileMaterial::StairsDown)
+        );
+    }
+
+    #[test]
+    fn corridors_leave_the_spawn_in_place() {
+        let mut map = tiles(&["#####", "#@###", "#####"]);
+        let (from, to) = (TilePos { x: 0, y: 1 }, TilePos { x: 4, y: 1 });
+        carve_corridor(&mut map, &from, &to, true);
+        assert_eq!(map, tiles(&["#####", ".@...", "#####"]));
+    }
+}
@@ -0,0 +1,121 @@
+use crate::{
+    editor::prelude::TileMaterial,
+    level::{Grid, CARDINAL_OFFSETS},
+};
+use bevy_ecs_tilemap::tiles::TilePos;
+use bevy_turborand::{DelegatedRng, RngComponent};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct WalkParams {
+    /// Share of the map inside the border walls to dig out
+    pub coverage: f32,
+}
+
+impl Default for WalkParams {
+    fn default() -> Self {
+        Self { coverage: 0.4 }
+    }
+}
+
+/// Give up on the coverage after this many steps per tile of the map
+const MAX_STEPS_PER_TILE: u32 = 50;
+
+/**
+ * Stumble around from the middle of the map, digging out every tile stepped on, until enough of
+ * the map is floor. Everything dug is connected by the walk, and the player spawns where it
+ * started. Fails when the map has no room to walk inside its outer walls.
+ */
+pub fn generate(
+    width: u32,
+    height: u32,
+    params: &WalkParams,
+    rng: &mut RngComponent,
+) -> Result<Grid<TileMaterial>, String> {
+    if width < 3 || height < 3 {
+        return Err("A walk needs a map at least 3x3 tiles big".to_string());
+    }
+    let mut tiles = Grid::new(width, height, TileMaterial::Wall);
+
+    let interior = (width - 2) * (height - 2);
+    let target = ((interior as f32 * params.coverage.clamp(0.0, 1.0)) as u32).max(1);
+    let start = TilePos {
+        x: width / 2,
+        y: height / 2,
+    };
+    tiles.set(&start, TileMaterial::Floor);
+
+    let (mut pos, mut dug) = (start, 1);
+    for _ in 0..width * height * MAX_STEPS_PER_TILE {
+        if dug >= target {
+            break;
+        }
+
+        let (dx, dy) = CARDINAL_OFFSETS[rng.usize(0..CARDINAL_OFFSETS.len())];
+        // Stay off the border so the map stays closed
+        let x = (pos.x as i32 + dx).clamp(1, width as i32 - 2) as u32;
+        let y = (pos.y as i32 + dy).clamp(1, height as i32 - 2) as u32;
+        pos = TilePos { x, y };
+        if tiles.get(&pos) == Some(&TileMaterial::Wall) {
+            tiles.set(&pos, TileMaterial::Floor);
+            dug += 1;
+        }
+    }
+
+    tiles.set(&start, TileMaterial::PlayerSpawn);
+    Ok(tiles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::generate::tests::{assert_playable, tiles};
+
+    fn walk(
+        width: u32,
+        height: u32,
+        coverage: f32,
+        seed: u64,
+    ) -> Result<Grid<TileMaterial>, String> {
+        let params = WalkParams { coverage };
+        generate(width, height, &params, &mut RngComponent::with_seed(seed))
+    }
+
+    #[test]
+    fn same_seed_same_walk() {
+        assert_eq!(walk(40, 30, 0.4, 5), walk(40, 30, 0.4, 5));
+        assert_ne!(walk(40, 30, 0.4, 5), walk(40, 30, 0.4, 6));
+    }
+
+    #[test]
+    fn walk_digs_out_its_share_from_the_spawn() {
+        for (coverage, dug) in [(0.0, 1), (0.25, 57), (0.5, 114)] {
+            for seed in 0..5 {
+                // 38 x 6 tiles inside the border
+                let map = walk(40, 8, coverage, seed).unwrap();
+                assert_playable(&map);
+                assert_eq!(
+                    map.get(&TilePos { x: 20, y: 4 }),
+                    Some(&TileMaterial::PlayerSpawn)
+                );
+                let open = map
+                    .iter()
+                    .filter(|(_, material)| **material != TileMaterial::Wall)
+                    .count();
+                assert_eq!(open, dug, "coverage {}", coverage);
+            }
+        }
+    }
+
+    #[test]
+    fn smallest_map_is_the_spawn() {
+        assert_eq!(walk(3, 3, 1.0, 0), Ok(tiles(&["###", "#@#", "###"])));
+    }
+
+    #[test]
+    fn maps_without_an_inside_are_refused() {
+        for (width, height) in [(0, 0), (2, 3), (3, 2), (1, 50)] {
+            assert!(walk(width, height, 0.4, 0).is_err(), "{}x{}", width, height);
+        }
+    }
+}
@@ -0,0 +1,616 @@
+use super::largest_region;
+use crate::{
+    editor::prelude::TileMaterial,
+    level::{load_asset_json, Grid, Level, CARDINAL_OFFSETS},
+};
+use bevy_ecs_tilemap::tiles::TilePos;
+use bevy_turborand::{DelegatedRng, RngComponent};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct WfcParams {
+    /// Asset path of the level to learn from
+    pub example: String,
+    /// Width and height of the patterns taken from the example
+    pub pattern_size: u32,
+    /// Also learn the example rotated and mirrored
+    pub symmetry: bool,
+    /// Give up after taking back this many choices
+    pub max_backtracks: u32,
+    /// Tiles the map must have, e.g. a room to build around
+    pub fixed: Vec<FixedTile>,
+}
+
+impl Default for WfcParams {
+    fn default() -> Self {
+        Self {
+            example: "levels/crypt-1.level.json".to_string(),
+            pattern_size: 3,
+            symmetry: true,
+            max_backtracks: 500,
+            fixed: Vec::new(),
+        }
+    }
+}
+
+/// A tile that keeps its material in the generated map
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FixedTile {
+    pub x: u32,
+    pub y: u32,
+    pub material: TileMaterial,
+}
+
+impl FixedTile {
+    pub fn new(material: &TileMaterial, tile_pos: &TilePos) -> Self {
+        Self {
+            x: tile_pos.x,
+            y: tile_pos.y,
+            material: material.clone(),
+        }
+    }
+
+    pub fn tile_pos(&self) -> TilePos {
+        TilePos {
+            x: self.x,
+            y: self.y,
+        }
+    }
+}
+
+/**
+ * Generate a map in the style of an example level with the overlapping model of wave function
+ * collapse. Every square of tiles in the map also appears in the example, and squares next to
+ * each other overlap the way they do there. The border is always wall, and the player spawns in
+ * the largest open area unless a fixed tile already is the spawn.
+ */
+pub fn generate(
+    width: u32,
+    height: u32,
+    params: &WfcParams,
+    rng: &mut RngComponent,
+) -> Result<Grid<TileMaterial>, String> {
+    let example: Level = load_asset_json(&params.example)
+        .map_err(|err| format!("Couldn't load {}: {}", params.example, err))?;
+    from_example(&example.tiles, width, height, params, rng)
+}
+
+/**
+ * Generate a map like `generate` does, from the tiles of an example that is already loaded
+ */
+fn from_example(
+    example: &Grid<TileMaterial>,
+    width: u32,
+    height: u32,
+    params: &WfcParams,
+    rng: &mut RngComponent,
+) -> Result<Grid<TileMaterial>, String> {
+    let n = params.pattern_size.max(1);
+    if width < n || height < n {
+        return Err(format!("The map is smaller than the {}x{} patterns", n, n));
+    }
+    let fixed: Vec<&FixedTile> = params
+        .fixed
+        .iter()
+        .filter(|tile| tile.x < width && tile.y < height)
+        .collect();
+    let spawns = fixed
+        .iter()
+        .filter(|tile| tile.material == TileMaterial::PlayerSpawn)
+        .count();
+    if spawns > 1 {
+        return Err("More than one fixed tile is a player spawn".to_string());
+    }
+
+    let model = Model::learn(example, n, params.symmetry);
+    if model.patterns.is_empty() {
+        return Err(format!("{} is smaller than the patterns", params.example));
+    }
+
+    let mut wave = Wave::new(&model, width - n + 1, height - n + 1);
+    let mut constrained = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            if x == 0 || y == 0 || x + 1 == width || y + 1 == height {
+                constrained.extend(wave.constrain(&TilePos { x, y }, &TileMaterial::Wall));
+            }
+        }
+    }
+    for tile in fixed.iter() {
+        constrained.extend(wave.constrain(&tile.tile_pos(), &normalized(&tile.material)));
+    }
+    if !wave.propagate(constrained) {
+        return Err("The border walls and fixed tiles don't fit the example".to_string());
+    }
+    wave.solve(params.max_backtracks, rng)?;
+
+    let mut tiles = wave.tiles(width, height);
+    for tile in fixed.iter() {
+        tiles.set(&tile.tile_pos(), tile.material.clone());
+    }
+    if spawns == 0 {
+        let open = largest_region(&tiles, &TileMaterial::Floor);
+        if open.is_empty() {
+            return Err("The generated map has no floor for the player spawn".to_string());
+        }
+        tiles.set(&open[rng.usize(0..open.len())], TileMaterial::PlayerSpawn);
+    }
+    Ok(tiles)
+}
+
+/**
+ * Spawns and stairs are one of a kind, so they are learned as the floor they stand on
+ */
+fn normalized(material: &TileMaterial) -> TileMaterial {
+    match material {
+        TileMaterial::PlayerSpawn | TileMaterial::StairsDown | TileMaterial::StairsUp => {
+            TileMaterial::Floor
+        }
+        other => other.clone(),
+    }
+}
+
+/// A set of pattern indices
+#[derive(Clone, Debug, PartialEq)]
+struct PatternSet(Vec<u64>);
+
+impl PatternSet {
+    fn new(len: usize, full: bool) -> Self {
+        let mut words = vec![0; len.div_ceil(64)];
+        if full {
+            for idx in 0..len {
+                words[idx / 64] |= 1 << (idx % 64);
+            }
+        }
+        Self(words)
+    }
+
+    fn contains(&self, idx: usize) -> bool {
+        self.0[idx / 64] & 1 << (idx % 64) != 0
+    }
+
+    fn insert(&mut self, idx: usize) {
+        self.0[idx / 64] |= 1 << (idx % 64);
+    }
+
+    fn remove(&mut self, idx: usize) {
+        self.0[idx / 64] &= !(1 << (idx % 64));
+    }
+
+    fn union_with(&mut self, other: &PatternSet) {
+        for (word, other) in self.0.iter_mut().zip(other.0.iter()) {
+            *word |= other;
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.0.iter().enumerate().flat_map(|(word_idx, word)| {
+            (0..64)
+                .filter(move |bit| word & 1 << bit != 0)
+                .map(move |bit| word_idx * 64 + bit)
+        })
+    }
+}
+
+/// The patterns of an example and which of them may sit next to each other
+struct Model {
+    size: u32,
+    /// Square patterns of tiles in row order
+    patterns: Vec<Vec<TileMaterial>>,
+    /// How often each pattern appears in the example
+    weights: Vec<u32>,
+    /// For each of the cardinal offsets, the patterns that may sit at that offset of a pattern
+    compatible: [Vec<PatternSet>; 4],
+}
+
+impl Model {
+    fn learn(example: &Grid<TileMaterial>, size: u32, symmetry: bool) -> Self {
+        let mut variants = vec![example.clone()];
+        if symmetry {
+            for _ in 0..3 {
+                let rotated = variants[variants.len() - 1].rotated();
+                variants.push(rotated);
+            }
+            let mirrored: Vec<_> = variants.iter().map(|grid| grid.mirrored()).collect();
+            variants.extend(mirrored);
+        }
+
+        let mut patterns: Vec<Vec<TileMaterial>> = Vec::new();
+        let mut weights = Vec::new();
+        for grid in variants.iter() {
+            if grid.width() < size || grid.height() < size {
+                continue;
+            }
+            for y in 0..=grid.height() - size {
+                for x in 0..=grid.width() - size {
+                    let mut pattern = Vec::with_capacity((size * size) as usize);
+                    for dy in 0..size {
+                        for dx in 0..size {
+                            let material = grid.get(&TilePos {
+                                x: x + dx,
+                                y: y + dy,
+                            });
+                            pattern.push(normalized(material.unwrap_or(&TileMaterial::Wall)));
+                        }
+                    }
+                    match patterns.iter().position(|other| *other == pattern) {
+                        Some(idx) => weights[idx] += 1,
+                        None => {
+                            patterns.push(pattern);
+                            weights.push(1);
+                        }
+                    }
+                }
+            }
+        }
+
+        let compatible = CARDINAL_OFFSETS.map(|(dx, dy)| {
+            patterns
+                .iter()
+                .map(|pattern| {
+                    let mut set = PatternSet::new(patterns.len(), false);
+                    for (idx, other) in patterns.iter().enumerate() {
+                        if overlaps(pattern, other, size as i32, dx, dy) {
+                            set.insert(idx);
+                        }
+                    }
+                    set
+                })
+                .collect()
+        });
+
+        Self {
+            size,
+            patterns,
+            weights,
+            compatible,
+        }
+    }
+}
+
+/**
+ * Whether `other` agrees with `pattern` where they overlap when placed at an offset from it
+ */
+fn overlaps(pattern: &[TileMaterial], other: &[TileMaterial], size: i32, dx: i32, dy: i32) -> bool {
+    for y in dy.max(0)..size.min(size + dy) {
+        for x in dx.max(0)..size.min(size + dx) {
+            let (ox, oy) = (x - dx, y - dy);
+            if pattern[(y * size + x) as usize] != other[(oy * size + ox) as usize] {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/**
+ * The patterns still possible at each position of the map. Positions are the top left corners
+ * of the patterns, so the last row and column also cover the tiles past them.
+ */
+struct Wave<'m> {
+    model: &'m Model,
+    width: u32,
+    height: u32,
+    cells: Vec<PatternSet>,
+    counts: Vec<usize>,
+    /// Every pattern ruled out so far, in order, so choices can be taken back
+    trail: Vec<(usize, usize)>,
+}
+
+impl<'m> Wave<'m> {
+    fn new(model: &'m Model, width: u32, height: u32) -> Self {
+        let len = model.patterns.len();
+        let cells = (width * height) as usize;
+        Self {
+            model,
+            width,
+            height,
+            cells: vec![PatternSet::new(len, true); cells],
+            counts: vec![len; cells],
+            trail: Vec::new(),
+        }
+    }
+
+    fn ban(&mut self, cell: usize, pattern: usize) {
+        self.cells[cell].remove(pattern);
+        self.counts[cell] -= 1;
+        self.trail.push((cell, pattern));
+    }
+
+    /**
+     * Take back every ban made after the trail had `len` entries
+     */
+    fn undo(&mut self, len: usize) {
+        while self.trail.len() > len {
+            let (cell, pattern) = self.trail.pop().unwrap();
+            self.cells[cell].insert(pattern);
+            self.counts[cell] += 1;
+        }
+    }
+
+    /**
+     * Rule out the patterns that would put another material on a tile. Returns the position
+     * that changed, if any, for propagation.
+     */
+    fn constrain(&mut self, tile_pos: &TilePos, material: &TileMaterial) -> Option<usize> {
+        let x = tile_pos.x.min(self.width - 1);
+        let y = tile_pos.y.min(self.height - 1);
+        let size = self.model.size;
+        let offset = ((tile_pos.y - y) * size + tile_pos.x - x) as usize;
+        let cell = (y * self.width + x) as usize;
+
+        let banned: Vec<usize> = self.cells[cell]
+            .iter()
+            .filter(|pattern| self.model.patterns[*pattern][offset] != *material)
+            .collect();
+        for pattern in banned.iter() {
+            self.ban(cell, *pattern);
+        }
+        (!banned.is_empty()).then_some(cell)
+    }
+
+    /**
+     * Rule out patterns that no longer fit next to their neighbors, starting from the changed
+     * positions. Returns false when a position runs out of patterns.
+     */
+    fn propagate(&mut self, mut changed: Vec<usize>) -> bool {
+        if changed.iter().any(|cell| self.counts[*cell] == 0) {
+            return false;
+        }
+
+        while let Some(cell) = changed.pop() {
+            let (x, y) = (cell as u32 % self.width, cell as u32 / self.width);
+            for (dir, (dx, dy)) in CARDINAL_OFFSETS.iter().enumerate() {
+                let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+                if nx < 0 || ny < 0 || nx as u32 >= self.width || ny as u32 >= self.height {
+                    continue;
+                }
+                let neighbor = (ny as u32 * self.width + nx as u32) as usize;
+
+                let mut allowed = PatternSet::new(self.model.patterns.len(), false);
+                for pattern in self.cells[cell].iter() {
+                    allowed.union_with(&self.model.compatible[dir][pattern]);
+                }
+                let banned: Vec<usize> = self.cells[neighbor]
+                    .iter()
+                    .filter(|pattern| !allowed.contains(*pattern))
+                    .collect();
+                if banned.is_empty() {
+                    continue;
+                }
+
+                for pattern in banned {
+                    self.ban(neighbor, pattern);
+                }
+                if self.counts[neighbor] == 0 {
+                    return false;
+                }
+                changed.push(neighbor);
+            }
+        }
+        true
+    }
+
+    /**
+     * The undecided position with the fewest patterns left, ties broken at random
+     */
+    fn most_constrained(&self, rng: &mut RngComponent) -> Option<usize> {
+        let fewest = self.counts.iter().filter(|count| **count > 1).min()?;
+        let candidates: Vec<usize> = (0..self.counts.len())
+            .filter(|cell| self.counts[*cell] == *fewest)
+            .collect();
+        Some(candidates[rng.usize(0..candidates.len())])
+    }
+
+    /**
+     * A pattern still possible at a position, favoring the common ones in the example
+     */
+    fn choose(&self, cell: usize, rng: &mut RngComponent) -> usize {
+        let possible: Vec<usize> = self.cells[cell].iter().collect();
+        let total: u32 = possible.iter().map(|idx| self.model.weights[*idx]).sum();
+        let mut roll = rng.u32(0..total);
+        for pattern in possible.iter() {
+            let weight = self.model.weights[*pattern];
+            if roll < weight {
+                return *pattern;
+            }
+            roll -= weight;
+        }
+        possible[possible.len() - 1]
+    }
+
+    /**
+     * Settle every position on one pattern. When a choice leaves some position without
+     * patterns, the choice is taken back and ruled out instead.
+     */
+    fn solve(&mut self, max_backtracks: u32, rng: &mut RngComponent) -> Result<(), String> {
+        let mut choices: Vec<(usize, usize, usize)> = Vec::new();
+        let mut backtracks = 0;
+        while let Some(cell) = self.most_constrained(rng) {
+            let chosen = self.choose(cell, rng);
+            choices.push((self.trail.len(), cell, chosen));
+            let others: Vec<usize> = self.cells[cell]
+                .iter()
+                .filter(|pattern| *pattern != chosen)
+                .collect();
+            for pattern in others {
+                self.ban(cell, pattern);
+            }
+
+            let mut consistent = self.propagate(vec![cell]);
+            while !consistent {
+                let (len, cell, chosen) = choices
+                    .pop()
+                    .ok_or("The example's patterns can't fill the map")?;
+                backtracks += 1;
+                if backtracks > max_backtracks {
+                    return Err(format!("Gave up after {} backtracks", max_backtracks));
+                }
+
+                self.undo(len);
+                self.ban(cell, chosen);
+                consistent = self.propagate(vec![cell]);
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * The map once every position is settled
+     */
+    fn tiles(&self, width: u32, height: u32) -> Grid<TileMaterial> {
+        let size = self.model.size;
+        let mut tiles = Grid::new(width, height, TileMaterial::Wall);
+        for y in 0..height {
+            for x in 0..width {
+                let (cx, cy) = (x.min(self.width - 1), y.min(self.height - 1));
+                let cell = (cy * self.width + cx) as usize;
+                if let Some(pattern) = self.cells[cell].iter().next() {
+                    let offset = ((y - cy) * size + x - cx) as usize;
+                    tiles.set(
+                        &TilePos { x, y },
+                        self.model.patterns[pattern][offset].clone(),
+                    );
+                }
+            }
+        }
+        tiles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::generate::tests::tiles;
+
+    /**
+     * A room with a pillar in it, so maps learned from it are rooms with pillars
+     */
+    fn pillar_room() -> Grid<TileMaterial> {
+        tiles(&["#######", "#.....#", "#..#..#", "#.....#", "#######"])
+    }
+
+    fn params(fixed: Vec<FixedTile>) -> WfcParams {
+        WfcParams {
+            pattern_size: 2,
+            fixed,
+            ..WfcParams::default()
+        }
+    }
+
+    fn generate_from(
+        example: &Grid<TileMaterial>,
+        params: &WfcParams,
+        seed: u64,
+    ) -> Result<Grid<TileMaterial>, String> {
+        from_example(example, 12, 9, params, &mut RngComponent::with_seed(seed))
+    }
+
+    fn spawns(tiles: &Grid<TileMaterial>) -> Vec<TilePos> {
+        tiles
+            .iter()
+            .filter(|(_, material)| **material == TileMaterial::PlayerSpawn)
+            .map(|(pos, _)| pos)
+            .collect()
+    }
+
+    #[test]
+    fn learns_every_square_of_the_example() {
+        use TileMaterial::*;
+        let example = tiles(&["###", "#@#", "###"]);
+
+        let model = Model::learn(&example, 2, false);
+        // The spawn is learned as floor, in each corner of the squares
+        let expected = vec![
+            vec![Wall, Wall, Wall, Floor],
+            vec![Wall, Wall, Floor, Wall],
+            vec![Wall, Floor, Wall, Wall],
+            vec![Floor, Wall, Wall, Wall],
+        ];
+        assert_eq!(model.patterns, expected);
+        assert_eq!(model.weights, vec![1, 1, 1, 1]);
+        // The floor in the top right corner of a square is in the top left of the one east of it
+        let east = CARDINAL_OFFSETS
+            .iter()
+            .position(|offset| *offset == (1, 0))
+            .unwrap();
+        assert_eq!(
+            model.compatible[east][0].iter().collect::<Vec<_>>(),
+            vec![1]
+        );
+
+        // Every rotation and mirror image of the example is the same squares again
+        let model = Model::learn(&example, 2, true);
+        assert_eq!(model.patterns, expected);
+        assert_eq!(model.weights, vec![8, 8, 8, 8]);
+
+        assert!(Model::learn(&example, 4, true).patterns.is_empty());
+    }
+
+    #[test]
+    fn same_seed_same_map() {
+        let params = params(Vec::new());
+        let map = generate_from(&pillar_room(), &params, 4).unwrap();
+        assert_eq!(generate_from(&pillar_room(), &params, 4), Ok(map.clone()));
+        assert_eq!(spawns(&map).len(), 1);
+        for (pos, material) in map.iter() {
+            if pos.x == 0 || pos.y == 0 || pos.x == 11 || pos.y == 8 {
+                assert_eq!(*material, TileMaterial::Wall);
+            }
+        }
+    }
+
+    #[test]
+    fn fixed_tiles_stay_in_place() {
+        let spawn = TilePos { x: 2, y: 2 };
+        let pillar = TilePos { x: 6, y: 4 };
+        let params = params(vec![
+            FixedTile::new(&TileMaterial::PlayerSpawn, &spawn),
+            FixedTile::new(&TileMaterial::Wall, &pillar),
+        ]);
+        for seed in 0..5 {
+            let map = generate_from(&pillar_room(), &params, seed).unwrap();
+            assert_eq!(spawns(&map), vec![spawn]);
+            assert_eq!(map.get(&pillar), Some(&TileMaterial::Wall));
+        }
+    }
+
+    #[test]
+    fn tiles_the_example_lacks_are_contradictions() {
+        let water = TileMaterial::Custom("Water".to_string());
+        let contradictions = [
+            // Not in the example at all
+            FixedTile::new(&water, &TilePos { x: 4, y: 4 }),
+            // The border is always wall
+            FixedTile::new(&TileMaterial::Floor, &TilePos { x: 0, y: 4 }),
+        ];
+        for fixed in contradictions {
+            let result = generate_from(&pillar_room(), &params(vec![fixed]), 0);
+            assert_eq!(
+                result,
+                Err("The border walls and fixed tiles don't fit the example".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn maps_that_cant_be_played_are_refused() {
+        let spawn = TileMaterial::PlayerSpawn;
+        let two_spawns = params(vec![
+            FixedTile::new(&spawn, &TilePos { x: 2, y: 2 }),
+            FixedTile::new(&spawn, &TilePos { x: 4, y: 4 }),
+        ]);
+        assert!(generate_from(&pillar_room(), &two_spawns, 0).is_err());
+
+        let solid = tiles(&["###", "###", "###"]);
+        assert_eq!(
+            generate_from(&solid, &params(Vec::new()), 0),
+            Err("The generated map has no floor for the player spawn".to_string())
+        );
+
+        let mut rng = RngComponent::with_seed(0);
+        let tiny = from_example(&pillar_room(), 1, 1, &params(Vec::new()), &mut rng);
+        assert!(tiny.is_err());
+    }
+}
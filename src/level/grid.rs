@@ -0,0 +1,357 @@
+use bevy_ecs_tilemap::prelude::{TilePos, TilemapSize};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+pub const CARDINAL_OFFSETS: [(i32, i32); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+pub const DIAGONAL_OFFSETS: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+
+/// Which edge of an axis stays in place when a grid is resized
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Anchor {
+    /// Left or bottom
+    Start,
+    #[default]
+    Center,
+    /// Right or top
+    End,
+}
+
+impl Anchor {
+    /**
+     * How far existing cells move along the axis when it changes length
+     */
+    fn offset(&self, old: u32, new: u32) -> i32 {
+        let delta = new as i32 - old as i32;
+        match self {
+            Anchor::Start => 0,
+            Anchor::Center => delta / 2,
+            Anchor::End => delta,
+        }
+    }
+}
+
+/**
+ * A plain row-major grid of cells. Indexing follows the same layout as `TileStorage`,
+ * i.e. `y * width + x`, so a grid can be copied to and from a tilemap without reordering.
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Grid<T> {
+    width: u32,
+    height: u32,
+    cells: Vec<T>,
+}
+
+impl<T: Clone> Grid<T> {
+    pub fn new(width: u32, height: u32, fill: T) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![fill; (width * height) as usize],
+        }
+    }
+}
+
+impl<T> Grid<T> {
+    pub fn from_cells(width: u32, height: u32, cells: Vec<T>) -> Option<Self> {
+        if cells.len() != (width * height) as usize {
+            return None;
+        }
+
+        Some(Self {
+            width,
+            height,
+            cells,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn size(&self) -> TilemapSize {
+        TilemapSize {
+            x: self.width,
+            y: self.height,
+        }
+    }
+
+    /**
+     * A grid read from disk may have a cell count that doesn't match its dimensions
+     */
+    pub fn is_valid(&self) -> bool {
+        self.cells.len() == (self.width * self.height) as usize
+    }
+
+    pub fn in_bounds(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && (x as u32) < self.width && (y as u32) < self.height
+    }
+
+    pub fn get(&self, pos: &TilePos) -> Option<&T> {
+        self.get_i(pos.x as i32, pos.y as i32)
+    }
+
+    pub fn get_i(&self, x: i32, y: i32) -> Option<&T> {
+        if !self.in_bounds(x, y) {
+            return None;
+        }
+
+        self.cells.get(y as usize * self.width as usize + x as usize)
+    }
+
+    pub fn set(&mut self, pos: &TilePos, value: T) {
+        if !self.in_bounds(pos.x as i32, pos.y as i32) {
+            return;
+        }
+
+        let idx = pos.y as usize * self.width as usize + pos.x as usize;
+        self.cells[idx] = value;
+    }
+
+    pub fn cells(&self) -> &[T] {
+        &self.cells
+    }
+
+    /**
+     * Iterate all cells together with their position
+     */
+    pub fn iter(&self) -> impl Iterator<Item = (TilePos, &T)> {
+        let width = self.width;
+        self.cells.iter().enumerate().map(move |(idx, cell)| {
+            (
+                TilePos {
+                    x: idx as u32 % width,
+                    y: idx as u32 / width,
+                },
+                cell,
+            )
+        })
+    }
+}
+
+impl<T: PartialEq> Grid<T> {
+    /**
+     * Positions of every cell connected to `start` that hold the same value as `start`.
+     * With `diagonal` set, cells touching only by a corner count as connected (8-connectivity).
+     */
+    pub fn region(&self, start: &TilePos, diagonal: bool) -> Vec<TilePos> {
+        let target = match self.get(start) {
+            Some(target) => target,
+            None => return Vec::new(),
+        };
+
+        let mut visited = vec![false; self.cells.len()];
+        let mut region = Vec::new();
+        let mut queue = VecDeque::from([*start]);
+        visited[(start.y * self.width + start.x) as usize] = true;
+
+        while let Some(pos) = queue.pop_front() {
+            region.push(pos);
+
+            let offsets = CARDINAL_OFFSETS
+                .iter()
+                .chain(DIAGONAL_OFFSETS.iter().filter(|_| diagonal));
+            for (dx, dy) in offsets {
+                let (x, y) = (pos.x as i32 + dx, pos.y as i32 + dy);
+                match self.get_i(x, y) {
+                    Some(value) if value == target => {
+                        let idx = (y as u32 * self.width + x as u32) as usize;
+                        if !visited[idx] {
+                            visited[idx] = true;
+                            queue.push_back(TilePos {
+                                x: x as u32,
+                                y: y as u32,
+                            });
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        region
+    }
+}
+
+impl<T: Clone> Grid<T> {
+    /**
+     * A copy with new dimensions. Cells keep their place relative to the anchored edges, cells
+     * that fall outside are dropped and new cells are set to `fill`.
+     */
+    pub fn resized(
+        &self,
+        width: u32,
+        height: u32,
+        anchor_x: Anchor,
+        anchor_y: Anchor,
+        fill: T,
+    ) -> Self {
+        let dx = anchor_x.offset(self.width, width);
+        let dy = anchor_y.offset(self.height, height);
+
+        let mut cells = Vec::with_capacity((width * height) as usize);
+        for y in 0..height as i32 {
+            for x in 0..width as i32 {
+                cells.push(self.get_i(x - dx, y - dy).unwrap_or(&fill).clone());
+            }
+        }
+
+        Self {
+            width,
+            height,
+            cells,
+        }
+    }
+
+    /**
+     * Copy out the `width` x `height` area whose bottom left corner is at `origin`
+     */
+    pub fn crop(&self, origin: &TilePos, width: u32, height: u32) -> Option<Self> {
+        let mut cells = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                cells.push(
+                    self.get_i((origin.x + x) as i32, (origin.y + y) as i32)?
+                        .clone(),
+                );
+            }
+        }
+
+        Self::from_cells(width, height, cells)
+    }
+
+    /**
+     * Rotate a quarter turn clockwise
+     */
+    pub fn rotated(&self) -> Self {
+        let mut cells = Vec::with_capacity(self.cells.len());
+        for y in 0..self.width {
+            for x in 0..self.height {
+                let idx = x * self.width + (self.width - 1 - y);
+                cells.push(self.cells[idx as usize].clone());
+            }
+        }
+
+        Self {
+            width: self.height,
+            height: self.width,
+            cells,
+        }
+    }
+
+    /**
+     * Flip along the vertical axis, swapping east and west
+     */
+    pub fn mirrored(&self) -> Self {
+        let mut cells = Vec::with_capacity(self.cells.len());
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let idx = y * self.width + (self.width - 1 - x);
+                cells.push(self.cells[idx as usize].clone());
+            }
+        }
+
+        Self {
+            width: self.width,
+            height: self.height,
+            cells,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /**
+     * A grid from rows of `#` and `.`, bottom row first so row indices match `y`
+     */
+    fn grid(rows: &[&str]) -> Grid<bool> {
+        let cells = rows
+            .iter()
+            .flat_map(|row| row.chars().map(|cell| cell == '#'))
+            .collect();
+        Grid::from_cells(rows[0].len() as u32, rows.len() as u32, cells).unwrap()
+    }
+
+    fn region(grid: &Grid<bool>, x: u32, y: u32, diagonal: bool) -> Vec<(u32, u32)> {
+        let mut region: Vec<(u32, u32)> = grid
+            .region(&TilePos { x, y }, diagonal)
+            .iter()
+            .map(|pos| (pos.x, pos.y))
+            .collect();
+        region.sort();
+        region
+    }
+
+    #[test]
+    fn region_of_l_shape() {
+        let shape = grid(&["#...", "#...", "###."]);
+        let expected = vec![(0, 0), (0, 1), (0, 2), (1, 2), (2, 2)];
+        assert_eq!(region(&shape, 0, 0, false), expected);
+        assert_eq!(region(&shape, 0, 0, true), expected);
+        assert_eq!(region(&shape, 3, 0, false).len(), 7);
+    }
+
+    #[test]
+    fn region_of_diagonal_shape() {
+        let shape = grid(&["#..", ".#.", "..#"]);
+        assert_eq!(region(&shape, 1, 1, false), vec![(1, 1)]);
+        assert_eq!(region(&shape, 1, 1, true), vec![(0, 0), (1, 1), (2, 2)]);
+        // The gaps are split by the diagonal for 4-connectivity only
+        assert_eq!(region(&shape, 1, 0, false).len(), 3);
+        assert_eq!(region(&shape, 1, 0, true).len(), 6);
+    }
+
+    #[test]
+    fn region_through_diagonal_gap() {
+        // The two rooms only touch corner to corner, between 1,0 and 2,1
+        let rooms = grid(&["##..", "..##"]);
+        assert_eq!(region(&rooms, 0, 0, false), vec![(0, 0), (1, 0)]);
+        assert_eq!(
+            region(&rooms, 0, 0, true),
+            vec![(0, 0), (1, 0), (2, 1), (3, 1)]
+        );
+        assert_eq!(region(&rooms, 3, 1, false), vec![(2, 1), (3, 1)]);
+    }
+
+    #[test]
+    fn region_of_ring_with_hole() {
+        let shape = grid(&[".....", ".###.", ".#.#.", ".###.", "....."]);
+        for diagonal in [false, true] {
+            assert_eq!(region(&shape, 1, 1, diagonal).len(), 8);
+            assert_eq!(region(&shape, 2, 2, diagonal), vec![(2, 2)]);
+            assert_eq!(region(&shape, 0, 0, diagonal).len(), 16);
+        }
+    }
+
+    #[test]
+    fn region_outside_grid_is_empty() {
+        let single = grid(&["#"]);
+        assert!(single.region(&TilePos { x: 1, y: 0 }, true).is_empty());
+    }
+
+    #[test]
+    fn rotated_quarter_turn_clockwise() {
+        // 3 4 5
+        // 0 1 2
+        let grid = Grid::from_cells(3, 2, (0..6).collect()).unwrap();
+        // 0 3
+        // 1 4
+        // 2 5
+        let rotated = grid.rotated();
+        assert_eq!((rotated.width(), rotated.height()), (2, 3));
+        assert_eq!(rotated.cells(), &[2, 5, 1, 4, 0, 3]);
+        assert_eq!(rotated.rotated().rotated().rotated(), grid);
+    }
+
+    #[test]
+    fn mirrored_swaps_east_and_west() {
+        let grid = Grid::from_cells(3, 2, (0..6).collect()).unwrap();
+        assert_eq!(grid.mirrored().cells(), &[2, 1, 0, 5, 4, 3]);
+        assert_eq!(grid.mirrored().mirrored(), grid);
+    }
+}
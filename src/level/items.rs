@@ -0,0 +1,74 @@
+use super::objects::KeyColor;
+use crate::DataAssets;
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+};
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type")]
+pub enum ItemKind {
+    /// Used up on use
+    Consumable {
+        heal: i32,
+    },
+    Weapon {
+        attack: i32,
+    },
+    Armor {
+        defense: i32,
+    },
+    /// Opens doors locked with the same color
+    Key {
+        color: KeyColor,
+    },
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct ItemDef {
+    pub name: String,
+    /// Index in the `1BitRogueSet` atlas
+    pub sprite_index: usize,
+    pub kind: ItemKind,
+}
+
+impl ItemDef {
+    pub fn tint(&self) -> Color {
+        match self.kind {
+            ItemKind::Key { color } => color.color(),
+            _ => Color::WHITE,
+        }
+    }
+}
+
+/**
+ * Every kind of item, loaded from `armory.items.json`
+ */
+#[derive(Deserialize, TypeUuid, TypePath, Clone, Debug, PartialEq)]
+#[uuid = "7c4e2a9b-1d3f-4b6a-8e5c-9f0a1b2c3d4e"]
+pub struct ItemSet {
+    pub items: Vec<ItemDef>,
+}
+
+impl ItemSet {
+    pub fn get(&self, name: &str) -> Option<&ItemDef> {
+        self.items.iter().find(|def| def.name == name)
+    }
+}
+
+/**
+ * Access to the currently loaded item definitions
+ */
+#[derive(SystemParam)]
+pub struct Items<'w> {
+    data: Option<Res<'w, DataAssets>>,
+    sets: Option<Res<'w, Assets<ItemSet>>>,
+}
+
+impl Items<'_> {
+    pub fn set(&self) -> Option<&ItemSet> {
+        self.sets.as_ref()?.get(&self.data.as_ref()?.items)
+    }
+}
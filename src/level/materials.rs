@@ -0,0 +1,152 @@
+use super::{
+    autotile::{neighbor_mask, AutotileRules},
+    Grid,
+};
+use crate::{editor::prelude::TileMaterial, DataAssets};
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+};
+use bevy_ecs_tilemap::tiles::TilePos;
+use serde::Deserialize;
+
+/// Atlas index used for materials that have no definition
+pub const MISSING_ATLAS_INDEX: u32 = 17;
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct MaterialDef {
+    pub name: String,
+    pub atlas_index: u32,
+    pub passable: bool,
+    pub opaque: bool,
+    /// Tiles in the same group connect to each other when autotiling
+    #[serde(default)]
+    pub autotile_group: Option<String>,
+    /// Other groups this material connects to as if they were its own
+    #[serde(default)]
+    pub connects_to: Vec<String>,
+    /// Without rules the material always uses `atlas_index`
+    #[serde(default)]
+    pub autotile: Option<AutotileRules>,
+    /// Color of the palette button in the editor
+    #[serde(default = "default_color")]
+    pub color: [f32; 3],
+    /// Short label shown on the palette button instead of the name
+    #[serde(default)]
+    pub icon: Option<String>,
+    /// Tint applied to the tile on the map
+    #[serde(default)]
+    pub tint: Option<[f32; 3]>,
+}
+
+fn default_color() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+/**
+ * The set of placeable materials, loaded from `tiles.materials.json`
+ */
+#[derive(Deserialize, TypeUuid, TypePath, Clone, Debug, PartialEq)]
+#[uuid = "b3c1e2f4-5a6d-4e7f-8091-a2b3c4d5e6f7"]
+pub struct MaterialSet {
+    pub materials: Vec<MaterialDef>,
+}
+
+impl MaterialSet {
+    pub fn get(&self, material: &TileMaterial) -> Option<&MaterialDef> {
+        let name = material.name();
+        self.materials.iter().find(|def| def.name == name)
+    }
+
+    pub fn atlas_index(&self, material: &TileMaterial) -> u32 {
+        self.get(material)
+            .map_or(MISSING_ATLAS_INDEX, |def| def.atlas_index)
+    }
+
+    /**
+     * Undefined materials behave like walls if they are one, and like floor otherwise
+     */
+    pub fn passable(&self, material: &TileMaterial) -> bool {
+        self.get(material)
+            .map_or(*material != TileMaterial::Wall, |def| def.passable)
+    }
+
+    pub fn opaque(&self, material: &TileMaterial) -> bool {
+        self.get(material)
+            .map_or(*material == TileMaterial::Wall, |def| def.opaque)
+    }
+
+    pub fn autotile_group(&self, material: &TileMaterial) -> Option<&str> {
+        self.get(material)
+            .and_then(|def| def.autotile_group.as_deref())
+    }
+
+    pub fn tint(&self, material: &TileMaterial) -> Color {
+        self.get(material)
+            .and_then(|def| def.tint)
+            .map_or(Color::WHITE, |[r, g, b]| Color::rgb(r, g, b))
+    }
+
+    /**
+     * Whether a tile of `def` connects to a neighboring tile of `other` when autotiling
+     */
+    pub fn connects(&self, def: &MaterialDef, other: &TileMaterial) -> bool {
+        match (def.autotile_group.as_deref(), self.autotile_group(other)) {
+            (_, None) => false,
+            (Some(group), Some(other_group)) if group == other_group => true,
+            (_, Some(other_group)) => def.connects_to.iter().any(|group| group == other_group),
+        }
+    }
+
+    /**
+     * The atlas index for a tile given a lookup of its neighbors by offset. Neighbors outside
+     * the map should be `None`.
+     */
+    pub fn tile_index<'m>(
+        &self,
+        material: &TileMaterial,
+        neighbor: impl Fn(i32, i32) -> Option<&'m TileMaterial>,
+    ) -> u32 {
+        let def = match self.get(material) {
+            Some(def) => def,
+            None => return MISSING_ATLAS_INDEX,
+        };
+        let rules = match &def.autotile {
+            Some(rules) => rules,
+            None => return def.atlas_index,
+        };
+
+        let mask =
+            neighbor_mask(|dx, dy| neighbor(dx, dy).is_some_and(|other| self.connects(def, other)));
+        rules.resolve(mask).unwrap_or(def.atlas_index)
+    }
+
+    /**
+     * The atlas index for a tile of a grid
+     */
+    pub fn grid_tile_index(&self, tiles: &Grid<TileMaterial>, pos: &TilePos) -> u32 {
+        match tiles.get(pos) {
+            Some(material) => self.tile_index(material, |dx, dy| {
+                tiles.get_i(pos.x as i32 + dx, pos.y as i32 + dy)
+            }),
+            None => MISSING_ATLAS_INDEX,
+        }
+    }
+}
+
+/**
+ * Access to the currently loaded material set. The set is reloaded when the file changes.
+ * Without the asset resources, e.g. when running headless, there is no set.
+ */
+#[derive(SystemParam)]
+pub struct Materials<'w> {
+    data: Option<Res<'w, DataAssets>>,
+    sets: Option<Res<'w, Assets<MaterialSet>>>,
+}
+
+impl Materials<'_> {
+    pub fn set(&self) -> Option<&MaterialSet> {
+        self.sets.as_ref()?.get(&self.data.as_ref()?.materials)
+    }
+}
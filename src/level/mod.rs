@@ -0,0 +1,239 @@
+use crate::editor::prelude::TileMaterial;
+use bevy::{
+    prelude::Resource,
+    reflect::{TypePath, TypeUuid},
+};
+use bevy_ecs_tilemap::tiles::TilePos;
+use materials::MaterialSet;
+use objects::ObjectPlacement;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{fs, io, path::Path};
+
+pub mod autotile;
+pub mod dungeon;
+pub mod format;
+pub mod fov;
+pub mod generate;
+mod grid;
+pub use grid::*;
+pub mod items;
+pub mod materials;
+pub mod monsters;
+pub mod objects;
+pub mod pathfinding;
+pub mod tiled;
+pub mod tilemap;
+pub mod validate;
+
+/// Level files are loaded by `bevy_common_assets` based on this extension
+pub const LEVEL_EXTENSION: &str = "level.json";
+/// Material definitions are loaded by `bevy_common_assets` based on this extension
+pub const MATERIALS_EXTENSION: &str = "materials.json";
+/// Item definitions are loaded by `bevy_common_assets` based on this extension
+pub const ITEMS_EXTENSION: &str = "items.json";
+/// Monster definitions are loaded by `bevy_common_assets` based on this extension
+pub const MONSTERS_EXTENSION: &str = "monsters.json";
+/// Levels are stored relative to the asset folder so they can be loaded by the asset server
+pub const ASSET_ROOT: &str = "assets";
+
+#[derive(Serialize, Deserialize, TypeUuid, TypePath, Clone, Debug, PartialEq)]
+#[uuid = "6a4f3d2e-8b1c-4f5a-9d7e-2c3b4a5f6e70"]
+pub struct Level {
+    pub name: String,
+    pub author: String,
+    pub tiles: Grid<TileMaterial>,
+    #[serde(default)]
+    pub monsters: Vec<Placement>,
+    #[serde(default)]
+    pub items: Vec<Placement>,
+    #[serde(default)]
+    pub objects: Vec<ObjectPlacement>,
+}
+
+/// Something placed on a tile by name, like a monster or an item
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Placement {
+    pub name: String,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl Placement {
+    pub fn new(name: &str, tile_pos: &TilePos) -> Self {
+        Self {
+            name: name.to_string(),
+            x: tile_pos.x,
+            y: tile_pos.y,
+        }
+    }
+
+    pub fn tile_pos(&self) -> TilePos {
+        TilePos {
+            x: self.x,
+            y: self.y,
+        }
+    }
+}
+
+/// The level the game is played on
+#[derive(Resource, Clone, Debug)]
+pub struct ActiveLevel(pub Level);
+
+impl Level {
+    pub fn blank(width: u32, height: u32) -> Self {
+        Self {
+            name: "Untitled".to_string(),
+            author: String::new(),
+            tiles: Grid::new(width, height, TileMaterial::Floor),
+            monsters: Vec::new(),
+            items: Vec::new(),
+            objects: Vec::new(),
+        }
+    }
+
+    /**
+     * The position of the level's one and only player spawn
+     */
+    pub fn player_spawn(&self) -> Result<TilePos, &'static str> {
+        let mut spawns = self
+            .tiles
+            .iter()
+            .filter(|(_, material)| **material == TileMaterial::PlayerSpawn)
+            .map(|(tile_pos, _)| tile_pos);
+
+        match (spawns.next(), spawns.next()) {
+            (Some(tile_pos), None) => Ok(tile_pos),
+            (None, _) => Err("The level has no player spawn"),
+            _ => Err("The level has more than one player spawn"),
+        }
+    }
+
+    /**
+     * The position of the first tile of a material, in row order
+     */
+    pub fn find(&self, material: &TileMaterial) -> Option<TilePos> {
+        self.tiles
+            .iter()
+            .find(|(_, other)| *other == material)
+            .map(|(tile_pos, _)| tile_pos)
+    }
+
+    /**
+     * Whether an actor can stand on a tile. Everything outside the level is blocked.
+     */
+    pub fn passable(&self, x: i32, y: i32, materials: Option<&MaterialSet>) -> bool {
+        match (self.tiles.get_i(x, y), materials) {
+            (None, _) => false,
+            (Some(material), Some(set)) => set.passable(material),
+            (Some(material), None) => *material != TileMaterial::Wall,
+        }
+    }
+
+    /**
+     * Whether a tile blocks sight. Everything outside the level is opaque.
+     */
+    pub fn opaque(&self, x: i32, y: i32, materials: Option<&MaterialSet>) -> bool {
+        match (self.tiles.get_i(x, y), materials) {
+            (None, _) => true,
+            (Some(material), Some(set)) => set.opaque(material),
+            (Some(material), None) => *material == TileMaterial::Wall,
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    /**
+     * Write the level to an asset path, e.g. `levels/first.level.json`
+     */
+    pub fn save(&self, asset_path: &str) -> io::Result<()> {
+        let path = Path::new(ASSET_ROOT).join(asset_path);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let json = self
+            .to_json()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        fs::write(path, json)
+    }
+}
+
+/**
+ * Read a JSON asset, like a level or the material definitions, from an asset path without going
+ * through the asset server
+ */
+pub fn load_asset_json<T: DeserializeOwned>(asset_path: &str) -> io::Result<T> {
+    let json = fs::read_to_string(Path::new(ASSET_ROOT).join(asset_path))?;
+    serde_json::from_str(&json).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        objects::{KeyColor, TileObject},
+        *,
+    };
+
+    fn sample_level() -> Level {
+        let mut level = Level::blank(4, 3);
+        level.name = "Sample".to_string();
+        level.author = "Tester".to_string();
+        level.tiles.set(&TilePos { x: 0, y: 0 }, TileMaterial::Wall);
+        level
+            .tiles
+            .set(&TilePos { x: 1, y: 1 }, TileMaterial::PlayerSpawn);
+        level
+            .tiles
+            .set(&TilePos { x: 3, y: 2 }, TileMaterial::StairsDown);
+        level.tiles.set(
+            &TilePos { x: 2, y: 0 },
+            TileMaterial::Custom("Water".to_string()),
+        );
+        level
+            .monsters
+            .push(Placement::new("Rat", &TilePos { x: 2, y: 1 }));
+        level
+            .items
+            .push(Placement::new("Potion", &TilePos { x: 3, y: 0 }));
+        level.objects.push(ObjectPlacement::new(
+            &TileObject::Door {
+                open: false,
+                lock: Some(KeyColor::Red),
+            },
+            &TilePos { x: 1, y: 2 },
+        ));
+        level.objects.push(ObjectPlacement::new(
+            &TileObject::Chest {
+                loot: vec!["Key".to_string(), "Gold".to_string()],
+                open: true,
+            },
+            &TilePos { x: 0, y: 1 },
+        ));
+        level
+    }
+
+    #[test]
+    fn json_round_trip() -> serde_json::Result<()> {
+        let level = sample_level();
+        assert_eq!(Level::from_json(&level.to_json()?)?, level);
+        Ok(())
+    }
+
+    #[test]
+    fn malformed_tile_count() {
+        let json = r#"{
+            "name": "Broken",
+            "author": "",
+            "tiles": { "width": 3, "height": 2, "cells": ["Wall", "Floor", "Wall"] }
+        }"#;
+        let level = Level::from_json(json).unwrap();
+        assert!(!level.tiles.is_valid());
+        assert_eq!(level.tiles.get(&TilePos { x: 0, y: 1 }), None);
+    }
+}
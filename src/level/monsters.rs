@@ -0,0 +1,79 @@
+use crate::DataAssets;
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+};
+use serde::Deserialize;
+
+/// How a monster behaves before it flees
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Behaviour {
+    /// Wanders around until it notices the player, then hunts them down
+    Hunter,
+    /// Stays put until it notices the player
+    Guard,
+    /// Never pays the player any attention
+    Wanderer,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct MonsterDef {
+    pub name: String,
+    /// Index in the `1BitRogueSet` atlas
+    pub sprite_index: usize,
+    pub hp: i32,
+    /// 100 is as fast as the player
+    pub speed: i32,
+    pub behaviour: Behaviour,
+    #[serde(default = "default_attack")]
+    pub attack: i32,
+    #[serde(default)]
+    pub defense: i32,
+    /// Names of the items left behind on death
+    #[serde(default)]
+    pub drops: Vec<String>,
+    #[serde(default = "default_sight_radius")]
+    pub sight_radius: u32,
+    /// Flee once health drops to this fraction of the maximum
+    #[serde(default)]
+    pub flee_below: f32,
+}
+
+fn default_attack() -> i32 {
+    2
+}
+
+fn default_sight_radius() -> u32 {
+    6
+}
+
+/**
+ * Every kind of monster, loaded from `bestiary.monsters.json`
+ */
+#[derive(Deserialize, TypeUuid, TypePath, Clone, Debug, PartialEq)]
+#[uuid = "2f8d6c1a-3e4b-4c5d-9a7f-1b2c3d4e5f60"]
+pub struct MonsterSet {
+    pub monsters: Vec<MonsterDef>,
+}
+
+impl MonsterSet {
+    pub fn get(&self, name: &str) -> Option<&MonsterDef> {
+        self.monsters.iter().find(|def| def.name == name)
+    }
+}
+
+/**
+ * Access to the currently loaded monster definitions
+ */
+#[derive(SystemParam)]
+pub struct Monsters<'w> {
+    data: Option<Res<'w, DataAssets>>,
+    sets: Option<Res<'w, Assets<MonsterSet>>>,
+}
+
+impl Monsters<'_> {
+    pub fn set(&self) -> Option<&MonsterSet> {
+        self.sets.as_ref()?.get(&self.data.as_ref()?.monsters)
+    }
+}
@@ -0,0 +1,126 @@
+use bevy::prelude::Color;
+use bevy_ecs_tilemap::tiles::TilePos;
+use serde::{Deserialize, Serialize};
+
+/// Indices in the `1BitRogueSet` atlas
+pub const DOOR_CLOSED_SPRITE_INDEX: usize = 88;
+pub const DOOR_OPEN_SPRITE_INDEX: usize = 89;
+pub const CHEST_CLOSED_SPRITE_INDEX: usize = 121;
+pub const CHEST_OPEN_SPRITE_INDEX: usize = 120;
+
+/// Locked doors open with a key of the same color
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum KeyColor {
+    Red,
+    Green,
+    Blue,
+    Yellow,
+}
+
+impl KeyColor {
+    pub const ALL: [KeyColor; 4] = [
+        KeyColor::Red,
+        KeyColor::Green,
+        KeyColor::Blue,
+        KeyColor::Yellow,
+    ];
+
+    /**
+     * Tint for the sprites of keys and the doors they open
+     */
+    pub fn color(&self) -> Color {
+        match self {
+            KeyColor::Red => Color::rgb(0.85, 0.3, 0.25),
+            KeyColor::Green => Color::rgb(0.35, 0.75, 0.35),
+            KeyColor::Blue => Color::rgb(0.35, 0.55, 0.9),
+            KeyColor::Yellow => Color::rgb(0.9, 0.8, 0.25),
+        }
+    }
+}
+
+/// Something on a tile the player interacts with by walking into it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type")]
+pub enum TileObject {
+    /// Blocks movement and sight while closed
+    Door {
+        #[serde(default)]
+        open: bool,
+        #[serde(default)]
+        lock: Option<KeyColor>,
+    },
+    /// Hands out its loot, by item name, when opened
+    Chest {
+        #[serde(default)]
+        loot: Vec<String>,
+        #[serde(default)]
+        open: bool,
+    },
+}
+
+impl TileObject {
+    pub fn door() -> Self {
+        TileObject::Door {
+            open: false,
+            lock: None,
+        }
+    }
+
+    pub fn chest() -> Self {
+        TileObject::Chest {
+            loot: Vec::new(),
+            open: false,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TileObject::Door { .. } => "Door",
+            TileObject::Chest { .. } => "Chest",
+        }
+    }
+
+    pub fn sprite_index(&self) -> usize {
+        match self {
+            TileObject::Door { open: true, .. } => DOOR_OPEN_SPRITE_INDEX,
+            TileObject::Door { open: false, .. } => DOOR_CLOSED_SPRITE_INDEX,
+            TileObject::Chest { open: true, .. } => CHEST_OPEN_SPRITE_INDEX,
+            TileObject::Chest { open: false, .. } => CHEST_CLOSED_SPRITE_INDEX,
+        }
+    }
+
+    pub fn tint(&self) -> Color {
+        match self {
+            TileObject::Door {
+                lock: Some(color), ..
+            } => color.color(),
+            _ => Color::WHITE,
+        }
+    }
+}
+
+/// An object placed on a tile of a level
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ObjectPlacement {
+    pub x: u32,
+    pub y: u32,
+    #[serde(flatten)]
+    pub object: TileObject,
+}
+
+impl ObjectPlacement {
+    pub fn new(object: &TileObject, tile_pos: &TilePos) -> Self {
+        Self {
+            x: tile_pos.x,
+            y: tile_pos.y,
+            object: object.clone(),
+        }
+    }
+
+    pub fn tile_pos(&self) -> TilePos {
+        TilePos {
+            x: self.x,
+            y: self.y,
+        }
+    }
+}
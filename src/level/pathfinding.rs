@@ -0,0 +1,296 @@
+use bevy_ecs_tilemap::prelude::{TilePos, TilemapSize};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+use super::Grid;
+
+/// Cost of one step. Diagonal steps cost the same, like they do for actors.
+pub const STEP: i32 = 10;
+/// Fleeing maps scale distances by this (in tenths) so actors prefer running past the threat
+/// into open space over cowering in the nearest corner
+const FLEE_FACTOR: i32 = -12;
+
+const OFFSETS: [(i32, i32); 8] = [
+    (0, 1),
+    (1, 0),
+    (0, -1),
+    (-1, 0),
+    (1, 1),
+    (1, -1),
+    (-1, -1),
+    (-1, 1),
+];
+
+fn neighbors<'a>(pos: &TilePos, size: &'a TilemapSize) -> impl Iterator<Item = TilePos> + 'a {
+    let (x, y) = (pos.x as i32, pos.y as i32);
+    OFFSETS.iter().filter_map(move |(dx, dy)| {
+        let (nx, ny) = (x + dx, y + dy);
+        (nx >= 0 && ny >= 0 && (nx as u32) < size.x && (ny as u32) < size.y).then_some(TilePos {
+            x: nx as u32,
+            y: ny as u32,
+        })
+    })
+}
+
+fn chebyshev(a: &TilePos, b: &TilePos) -> i32 {
+    (a.x as i32 - b.x as i32)
+        .abs()
+        .max((a.y as i32 - b.y as i32).abs())
+}
+
+/**
+ * The shortest path from `start` to `goal` over passable tiles, excluding `start`. The goal
+ * itself doesn't have to be passable, so actors can path towards whoever stands on it.
+ */
+pub fn astar(
+    start: &TilePos,
+    goal: &TilePos,
+    size: &TilemapSize,
+    passable: impl Fn(&TilePos) -> bool,
+) -> Option<Vec<TilePos>> {
+    let mut open = BinaryHeap::new();
+    let mut came_from: HashMap<TilePos, TilePos> = HashMap::new();
+    let mut cost: HashMap<TilePos, i32> = HashMap::new();
+
+    cost.insert(*start, 0);
+    open.push(Reverse((
+        chebyshev(start, goal) * STEP,
+        0,
+        start.x,
+        start.y,
+    )));
+
+    while let Some(Reverse((_, current_cost, x, y))) = open.pop() {
+        let current = TilePos { x, y };
+        if current == *goal {
+            let mut path = vec![current];
+            while let Some(previous) = came_from.get(path.last()?) {
+                if previous == start {
+                    break;
+                }
+                path.push(*previous);
+            }
+            path.reverse();
+            return Some(path);
+        }
+        if cost.get(&current).is_some_and(|best| current_cost > *best) {
+            continue;
+        }
+
+        for next in neighbors(&current, size) {
+            if next != *goal && !passable(&next) {
+                continue;
+            }
+            let next_cost = current_cost + STEP;
+            if cost.get(&next).is_none_or(|best| next_cost < *best) {
+                cost.insert(next, next_cost);
+                came_from.insert(next, current);
+                let estimate = next_cost + chebyshev(&next, goal) * STEP;
+                open.push(Reverse((estimate, next_cost, next.x, next.y)));
+            }
+        }
+    }
+
+    None
+}
+
+/**
+ * Distances from every tile to the nearest goal. Rolling downhill leads to a goal, and the
+ * flee map made from it leads away from them.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct DijkstraMap {
+    distances: Grid<Option<i32>>,
+}
+
+impl DijkstraMap {
+    pub fn new(goals: &[TilePos], size: &TilemapSize, passable: impl Fn(&TilePos) -> bool) -> Self {
+        let sources: Vec<(TilePos, i32)> = goals.iter().map(|goal| (*goal, 0)).collect();
+        Self::from_sources(&sources, size, passable)
+    }
+
+    /**
+     * Scan from tiles with given starting values. Goals are always included, passable or not.
+     */
+    pub fn from_sources(
+        sources: &[(TilePos, i32)],
+        size: &TilemapSize,
+        passable: impl Fn(&TilePos) -> bool,
+    ) -> Self {
+        let mut distances = Grid::new(size.x, size.y, None);
+        let mut open = BinaryHeap::new();
+        for (pos, value) in sources {
+            distances.set(pos, Some(*value));
+            open.push(Reverse((*value, pos.x, pos.y)));
+        }
+
+        while let Some(Reverse((distance, x, y))) = open.pop() {
+            let current = TilePos { x, y };
+            if distances.get(&current).copied().flatten() != Some(distance) {
+                continue;
+            }
+
+            for next in neighbors(&current, size) {
+                if !passable(&next) {
+                    continue;
+                }
+                let next_distance = distance + STEP;
+                if distances
+                    .get(&next)
+                    .copied()
+                    .flatten()
+                    .is_none_or(|best| next_distance < best)
+                {
+                    distances.set(&next, Some(next_distance));
+                    open.push(Reverse((next_distance, next.x, next.y)));
+                }
+            }
+        }
+
+        Self { distances }
+    }
+
+    /**
+     * A map that leads away from the goals of this one
+     */
+    pub fn flee_map(&self, passable: impl Fn(&TilePos) -> bool) -> Self {
+        let sources: Vec<(TilePos, i32)> = self
+            .distances
+            .iter()
+            .filter_map(|(pos, distance)| Some((pos, (*distance)? * FLEE_FACTOR / 10)))
+            .collect();
+        Self::from_sources(&sources, &self.distances.size(), passable)
+    }
+
+    /// `None` for tiles that can't reach a goal
+    pub fn distance(&self, pos: &TilePos) -> Option<i32> {
+        self.distances.get(pos).copied().flatten()
+    }
+
+    /**
+     * The neighbor with the lowest value that's lower than `pos`, if any. `blocked` tiles are
+     * skipped, e.g. ones taken by other actors.
+     */
+    pub fn downhill(&self, pos: &TilePos, blocked: impl Fn(&TilePos) -> bool) -> Option<TilePos> {
+        let current = self.distance(pos)?;
+        neighbors(pos, &self.distances.size())
+            .filter(|next| !blocked(next))
+            .filter_map(|next| Some((self.distance(&next)?, next)))
+            .filter(|(distance, _)| *distance < current)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, next)| next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    /**
+     * Wall positions and size of a map drawn top row first, with `#` for walls
+     */
+    fn map(rows: &[&str]) -> (HashSet<TilePos>, TilemapSize) {
+        let height = rows.len() as u32;
+        let walls = rows
+            .iter()
+            .enumerate()
+            .flat_map(|(row, line)| {
+                line.chars()
+                    .enumerate()
+                    .filter(|(_, cell)| *cell == '#')
+                    .map(move |(x, _)| TilePos {
+                        x: x as u32,
+                        y: height - 1 - row as u32,
+                    })
+            })
+            .collect();
+        let size = TilemapSize {
+            x: rows[0].len() as u32,
+            y: height,
+        };
+        (walls, size)
+    }
+
+    #[test]
+    fn shortest_path_through_maze() {
+        let (walls, size) = map(&[
+            "###########",
+            "#S..#.....#",
+            "###.#.###.#",
+            "#...#...#.#",
+            "#.#####.#.#",
+            "#.......#G#",
+            "###########",
+        ]);
+        let (start, goal) = (TilePos { x: 1, y: 5 }, TilePos { x: 9, y: 1 });
+        let path = astar(&start, &goal, &size, |pos| !walls.contains(pos)).unwrap();
+
+        assert_eq!(path.len(), 19);
+        assert_eq!(path.last(), Some(&goal));
+        let mut previous = start;
+        for step in path.iter() {
+            assert_eq!(chebyshev(&previous, step), 1);
+            assert!(!walls.contains(step));
+            previous = *step;
+        }
+
+        let distances = DijkstraMap::new(&[goal], &size, |pos| !walls.contains(pos));
+        assert_eq!(distances.distance(&start), Some(19 * STEP));
+    }
+
+    #[test]
+    fn walled_off_goal() {
+        let (walls, size) = map(&[
+            "#########",
+            "#...#...#",
+            "#...#...#",
+            "#...#...#",
+            "#########",
+        ]);
+        let passable = |pos: &TilePos| !walls.contains(pos);
+        let (start, goal) = (TilePos { x: 1, y: 1 }, TilePos { x: 6, y: 2 });
+
+        assert_eq!(astar(&start, &goal, &size, passable), None);
+        let distances = DijkstraMap::new(&[goal], &size, passable);
+        assert_eq!(distances.distance(&start), None);
+        assert_eq!(distances.downhill(&start, |_| false), None);
+    }
+
+    #[test]
+    fn flee_map_leads_away() {
+        let size = TilemapSize { x: 9, y: 9 };
+        let threat = TilePos { x: 2, y: 4 };
+        let flee = DijkstraMap::new(&[threat], &size, |_| true).flee_map(|_| true);
+
+        let mut pos = TilePos { x: 3, y: 4 };
+        for _ in 0..4 {
+            let next = flee.downhill(&pos, |_| false).unwrap();
+            assert!(chebyshev(&next, &threat) > chebyshev(&pos, &threat));
+            pos = next;
+        }
+    }
+
+    #[test]
+    fn downhill_on_flat_tie() {
+        let size = TilemapSize { x: 3, y: 3 };
+        let center = TilePos { x: 1, y: 1 };
+
+        // Nothing is lower than where the actor stands
+        let everywhere: Vec<TilePos> = (0..3)
+            .flat_map(|x| (0..3).map(move |y| TilePos { x, y }))
+            .collect();
+        let flat = DijkstraMap::new(&everywhere, &size, |_| true);
+        assert_eq!(flat.downhill(&center, |_| false), None);
+
+        // Two equally good ways down, of which one is taken
+        let (left, right) = (TilePos { x: 0, y: 1 }, TilePos { x: 2, y: 1 });
+        let tie = DijkstraMap::new(&[left, right], &size, |_| true);
+        let next = tie.downhill(&center, |_| false).unwrap();
+        assert!(next == left || next == right);
+        let other = if next == left { right } else { left };
+        assert_eq!(tie.downhill(&center, |pos| *pos == next), Some(other));
+    }
+}
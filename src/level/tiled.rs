@@ -0,0 +1,814 @@
+use super::{
+    objects::{KeyColor, ObjectPlacement, TileObject},
+    Level, Placement,
+};
+use crate::editor::prelude::TileMaterial;
+use bevy::prelude::Resource;
+use bevy_ecs_tilemap::tiles::TilePos;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use xml::{
+    reader::{EventReader, XmlEvent as ReadEvent},
+    writer::{EmitterConfig, EventWriter, XmlEvent as WriteEvent},
+};
+
+/// Asset path of the table the editor and the command line use
+pub const TILED_TABLE_PATH: &str = "tiled.table.json";
+
+/// Tiled keeps flipping and rotation in the top bits of a tile's global id
+const FLIP_FLAGS: u32 = 0xF000_0000;
+/// Object types used for what isn't a tile
+const SPAWN_TYPE: &str = "PlayerSpawn";
+const MONSTER_TYPE: &str = "Monster";
+const ITEM_TYPE: &str = "Item";
+
+/**
+ * Which tiles of the Tiled tileset stand for which material, e.g. `tiled.table.json`
+ */
+#[derive(Serialize, Deserialize, Resource, Clone, Debug, Default, PartialEq)]
+pub struct TiledTable {
+    /// Name of the tileset in exported maps
+    pub tileset: String,
+    /// The tileset image as seen from exported maps
+    pub image: String,
+    pub image_width: u32,
+    pub image_height: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub tiles: Vec<TiledTile>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TiledTile {
+    pub material: TileMaterial,
+    /// Tile ids in the tileset, the first of which is used for export
+    pub ids: Vec<u32>,
+}
+
+impl TiledTable {
+    pub fn id(&self, material: &TileMaterial) -> Option<u32> {
+        self.tiles
+            .iter()
+            .find(|tile| tile.material == *material)
+            .and_then(|tile| tile.ids.first().copied())
+    }
+
+    pub fn material(&self, id: u32) -> Option<&TileMaterial> {
+        self.tiles
+            .iter()
+            .find(|tile| tile.ids.contains(&id))
+            .map(|tile| &tile.material)
+    }
+}
+
+/// A Tiled map, as much of it as levels use. The field names follow the JSON format.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+struct TiledMap {
+    #[serde(rename = "type")]
+    kind: String,
+    version: String,
+    orientation: String,
+    renderorder: String,
+    infinite: bool,
+    width: u32,
+    height: u32,
+    tilewidth: u32,
+    tileheight: u32,
+    nextlayerid: u32,
+    nextobjectid: u32,
+    properties: Vec<Property>,
+    tilesets: Vec<Tileset>,
+    layers: Vec<Layer>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+struct Tileset {
+    /// Global id of the first tile. A tile belongs to the tileset with the largest first id not
+    /// above its own.
+    firstgid: u32,
+    /// Set for tilesets in their own file, which are assumed to be the table's
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<String>,
+    name: String,
+    tilewidth: u32,
+    tileheight: u32,
+    tilecount: u32,
+    columns: u32,
+    image: String,
+    imagewidth: u32,
+    imageheight: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+struct Layer {
+    id: u32,
+    name: String,
+    /// `tilelayer` or `objectgroup`. Other layers are skipped.
+    #[serde(rename = "type")]
+    kind: String,
+    x: i32,
+    y: i32,
+    opacity: f32,
+    visible: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    height: Option<u32>,
+    /// Global tile ids in rows from the top, 0 for no tile
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Vec<u32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    draworder: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    objects: Option<Vec<Object>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+struct Object {
+    id: u32,
+    name: String,
+    #[serde(rename = "type", alias = "class")]
+    kind: String,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    rotation: f32,
+    visible: bool,
+    /// Tile objects are anchored at their bottom left corner rather than the top left
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gid: Option<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    properties: Vec<Property>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+struct Property {
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+    value: Value,
+}
+
+impl Property {
+    fn new(name: &str, value: Value) -> Self {
+        let kind = match value {
+            Value::Bool(_) => "bool",
+            Value::Number(_) => "int",
+            _ => "string",
+        };
+        Self {
+            name: name.to_string(),
+            kind: kind.to_string(),
+            value,
+        }
+    }
+
+    /**
+     * The value as text, the way TMX stores it
+     */
+    fn text(&self) -> String {
+        match &self.value {
+            Value::String(text) => text.clone(),
+            other => other.to_string(),
+        }
+    }
+}
+
+fn property<'p>(properties: &'p [Property], name: &str) -> Option<&'p Value> {
+    properties
+        .iter()
+        .find(|property| property.name == name)
+        .map(|property| &property.value)
+}
+
+/**
+ * Read a Tiled JSON map (`.tmj`)
+ */
+pub fn from_tmj(json: &str, table: &TiledTable) -> Result<Level, String> {
+    let map: TiledMap = serde_json::from_str(json).map_err(|err| err.to_string())?;
+    from_map(&map, table)
+}
+
+/**
+ * Write a level as a Tiled JSON map (`.tmj`)
+ */
+pub fn to_tmj(level: &Level, table: &TiledTable) -> Result<String, String> {
+    serde_json::to_string_pretty(&to_map(level, table)?).map_err(|err| err.to_string())
+}
+
+/**
+ * Read a Tiled XML map (`.tmx`) with CSV or XML tile data. Compressed data isn't supported.
+ */
+pub fn from_tmx(xml: &str, table: &TiledTable) -> Result<Level, String> {
+    let mut map = TiledMap::default();
+    let mut stack: Vec<String> = Vec::new();
+    let mut csv = String::new();
+
+    for event in EventReader::new(xml.as_bytes()) {
+        match event.map_err(|err| err.to_string())? {
+            ReadEvent::StartElement {
+                name, attributes, ..
+            } => {
+                let attr = |key: &str| {
+                    attributes
+                        .iter()
+                        .find(|attribute| attribute.name.local_name == key)
+                        .map(|attribute| attribute.value.clone())
+                };
+                let number = |key: &str| attr(key).and_then(|value| value.parse::<f32>().ok());
+                let count = |key: &str| attr(key).and_then(|value| value.parse().ok()).unwrap_or(0);
+                let parent = stack.last().map(|parent| parent.as_str());
+
+                match (name.local_name.as_str(), parent) {
+                    ("map", _) => {
+                        map.width = count("width");
+                        map.height = count("height");
+                        map.tilewidth = count("tilewidth");
+                        map.tileheight = count("tileheight");
+                        map.infinite = attr("infinite").as_deref() == Some("1");
+                    }
+                    ("tileset", Some("map")) => map.tilesets.push(Tileset {
+                        firstgid: count("firstgid"),
+                        source: attr("source"),
+                        name: attr("name").unwrap_or_default(),
+                        ..Default::default()
+                    }),
+                    ("layer", Some("map")) => map.layers.push(Layer {
+                        kind: "tilelayer".to_string(),
+                        name: attr("name").unwrap_or_default(),
+                        data: Some(Vec::new()),
+                        ..Default::default()
+                    }),
+                    ("data", Some("layer")) => {
+                        if attr("compression").is_some()
+                            || !matches!(attr("encoding").as_deref(), None | Some("csv"))
+                        {
+                            return Err("Only CSV and XML tile data can be read".to_string());
+                        }
+                        csv.clear();
+                    }
+                    ("tile", Some("data")) => {
+                        if let Some(data) = map.layers.last_mut().and_then(|l| l.data.as_mut()) {
+                            data.push(count("gid"));
+                        }
+                    }
+                    ("objectgroup", Some("map")) => map.layers.push(Layer {
+                        kind: "objectgroup".to_string(),
+                        name: attr("name").unwrap_or_default(),
+                        objects: Some(Vec::new()),
+                        ..Default::default()
+                    }),
+                    ("object", Some("objectgroup")) => {
+                        let object = Object {
+                            id: count("id"),
+                            name: attr("name").unwrap_or_default(),
+                            kind: attr("type").or_else(|| attr("class")).unwrap_or_default(),
+                            x: number("x").unwrap_or(0.0),
+                            y: number("y").unwrap_or(0.0),
+                            width: number("width").unwrap_or(0.0),
+                            height: number("height").unwrap_or(0.0),
+                            gid: attr("gid").and_then(|gid| gid.parse().ok()),
+                            ..Default::default()
+                        };
+                        if let Some(objects) =
+                            map.layers.last_mut().and_then(|l| l.objects.as_mut())
+                        {
+                            objects.push(object);
+                        }
+                    }
+                    ("property", Some("properties")) => {
+                        let text = attr("value").unwrap_or_default();
+                        let value = match attr("type").as_deref() {
+                            Some("bool") => Value::Bool(text == "true"),
+                            Some("int") => text.parse::<i64>().map_or(Value::Null, Value::from),
+                            Some("float") => text.parse::<f64>().map_or(Value::Null, Value::from),
+                            _ => Value::String(text),
+                        };
+                        let property = Property::new(&attr("name").unwrap_or_default(), value);
+                        // Properties belong to the element around the properties element
+                        match stack.iter().rev().nth(1).map(|owner| owner.as_str()) {
+                            Some("map") => map.properties.push(property),
+                            Some("object") => {
+                                let object = map
+                                    .layers
+                                    .last_mut()
+                                    .and_then(|layer| layer.objects.as_mut())
+                                    .and_then(|objects| objects.last_mut());
+                                if let Some(object) = object {
+                                    object.properties.push(property);
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    _ => {}
+                }
+                stack.push(name.local_name);
+            }
+            ReadEvent::Characters(text) if stack.last().map(|s| s.as_str()) == Some("data") => {
+                csv.push_str(&text);
+            }
+            ReadEvent::EndElement { name } => {
+                stack.pop();
+                if name.local_name == "data" && !csv.trim().is_empty() {
+                    let gids = csv
+                        .split(',')
+                        .map(|gid| gid.trim())
+                        .filter(|gid| !gid.is_empty())
+                        .map(|gid| gid.parse::<u32>().map_err(|err| err.to_string()))
+                        .collect::<Result<Vec<u32>, String>>()?;
+                    if let Some(data) = map.layers.last_mut().and_then(|l| l.data.as_mut()) {
+                        data.extend(gids);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    from_map(&map, table)
+}
+
+/**
+ * Write a level as a Tiled XML map (`.tmx`) with CSV tile data
+ */
+pub fn to_tmx(level: &Level, table: &TiledTable) -> Result<String, String> {
+    let map = to_map(level, table)?;
+    let mut writer = EmitterConfig::new()
+        .perform_indent(true)
+        .create_writer(Vec::new());
+
+    let (width, height) = (map.width.to_string(), map.height.to_string());
+    let (tile_width, tile_height) = (map.tilewidth.to_string(), map.tileheight.to_string());
+    let (next_layer, next_object) = (map.nextlayerid.to_string(), map.nextobjectid.to_string());
+    emit(
+        &mut writer,
+        WriteEvent::start_element("map")
+            .attr("version", &map.version)
+            .attr("orientation", &map.orientation)
+            .attr("renderorder", &map.renderorder)
+            .attr("width", &width)
+            .attr("height", &height)
+            .attr("tilewidth", &tile_width)
+            .attr("tileheight", &tile_height)
+            .attr("infinite", "0")
+            .attr("nextlayerid", &next_layer)
+            .attr("nextobjectid", &next_object),
+    )?;
+    write_properties(&mut writer, &map.properties)?;
+
+    for tileset in map.tilesets.iter() {
+        let first_gid = tileset.firstgid.to_string();
+        let (tile_width, tile_height) = (
+            tileset.tilewidth.to_string(),
+            tileset.tileheight.to_string(),
+        );
+        let (tile_count, columns) = (tileset.tilecount.to_string(), tileset.columns.to_string());
+        let (image_width, image_height) = (
+            tileset.imagewidth.to_string(),
+            tileset.imageheight.to_string(),
+        );
+        emit(
+            &mut writer,
+            WriteEvent::start_element("tileset")
+                .attr("firstgid", &first_gid)
+                .attr("name", &tileset.name)
+                .attr("tilewidth", &tile_width)
+                .attr("tileheight", &tile_height)
+                .attr("tilecount", &tile_count)
+                .attr("columns", &columns),
+        )?;
+        emit(
+            &mut writer,
+            WriteEvent::start_element("image")
+                .attr("source", &tileset.image)
+                .attr("width", &image_width)
+                .attr("height", &image_height),
+        )?;
+        emit(&mut writer, WriteEvent::end_element())?;
+        emit(&mut writer, WriteEvent::end_element())?;
+    }
+
+    for layer in map.layers.iter() {
+        let id = layer.id.to_string();
+        if let Some(data) = &layer.data {
+            emit(
+                &mut writer,
+                WriteEvent::start_element("layer")
+                    .attr("id", &id)
+                    .attr("name", &layer.name)
+                    .attr("width", &width)
+                    .attr("height", &height),
+            )?;
+            emit(
+                &mut writer,
+                WriteEvent::start_element("data").attr("encoding", "csv"),
+            )?;
+            let rows: Vec<String> = data
+                .chunks(map.width.max(1) as usize)
+                .map(|row| {
+                    let gids: Vec<String> = row.iter().map(|gid| gid.to_string()).collect();
+                    gids.join(",")
+                })
+                .collect();
+            emit(
+                &mut writer,
+                WriteEvent::characters(&format!("\n{}\n", rows.join(",\n"))),
+            )?;
+            emit(&mut writer, WriteEvent::end_element())?;
+            emit(&mut writer, WriteEvent::end_element())?;
+        }
+
+        if let Some(objects) = &layer.objects {
+            emit(
+                &mut writer,
+                WriteEvent::start_element("objectgroup")
+                    .attr("id", &id)
+                    .attr("name", &layer.name),
+            )?;
+            for object in objects.iter() {
+                let id = object.id.to_string();
+                let (x, y) = (object.x.to_string(), object.y.to_string());
+                let (width, height) = (object.width.to_string(), object.height.to_string());
+                emit(
+                    &mut writer,
+                    WriteEvent::start_element("object")
+                        .attr("id", &id)
+                        .attr("name", &object.name)
+                        .attr("type", &object.kind)
+                        .attr("x", &x)
+                        .attr("y", &y)
+                        .attr("width", &width)
+                        .attr("height", &height),
+                )?;
+                write_properties(&mut writer, &object.properties)?;
+                emit(&mut writer, WriteEvent::end_element())?;
+            }
+            emit(&mut writer, WriteEvent::end_element())?;
+        }
+    }
+    emit(&mut writer, WriteEvent::end_element())?;
+
+    String::from_utf8(writer.into_inner()).map_err(|err| err.to_string())
+}
+
+fn write_properties(
+    writer: &mut EventWriter<Vec<u8>>,
+    properties: &[Property],
+) -> Result<(), String> {
+    if properties.is_empty() {
+        return Ok(());
+    }
+
+    emit(writer, WriteEvent::start_element("properties"))?;
+    for property in properties.iter() {
+        let value = property.text();
+        let element = WriteEvent::start_element("property").attr("name", &property.name);
+        let element = match property.kind.as_str() {
+            "string" => element,
+            kind => element.attr("type", kind),
+        };
+        emit(writer, element.attr("value", &value))?;
+        emit(writer, WriteEvent::end_element())?;
+    }
+    emit(writer, WriteEvent::end_element())
+}
+
+fn emit<'a>(
+    writer: &mut EventWriter<Vec<u8>>,
+    event: impl Into<WriteEvent<'a>>,
+) -> Result<(), String> {
+    writer.write(event).map_err(|err| err.to_string())
+}
+
+/**
+ * The map of a level: one tile layer and one object layer with the player spawn, monsters,
+ * items, doors and chests. The spawn tile is exported as floor, since the spawn is an object.
+ */
+fn to_map(level: &Level, table: &TiledTable) -> Result<TiledMap, String> {
+    let tiles = &level.tiles;
+    let (width, height) = (tiles.width(), tiles.height());
+    let first_gid = 1;
+
+    let mut data = Vec::with_capacity((width * height) as usize);
+    for row in 0..height {
+        for x in 0..width {
+            let pos = TilePos {
+                x,
+                y: height - 1 - row,
+            };
+            let material = match tiles.get(&pos) {
+                Some(TileMaterial::PlayerSpawn) | None => &TileMaterial::Floor,
+                Some(material) => material,
+            };
+            let id = table
+                .id(material)
+                .ok_or_else(|| format!("{} has no tile in the Tiled table", material.name()))?;
+            data.push(first_gid + id);
+        }
+    }
+
+    let mut objects = Vec::new();
+    let mut add_object = |kind: &str, name: &str, pos: &TilePos, properties: Vec<Property>| {
+        objects.push(Object {
+            id: objects.len() as u32 + 1,
+            name: name.to_string(),
+            kind: kind.to_string(),
+            x: (pos.x * table.tile_width) as f32,
+            y: ((height - 1 - pos.y) * table.tile_height) as f32,
+            width: table.tile_width as f32,
+            height: table.tile_height as f32,
+            visible: true,
+            properties,
+            ..Default::default()
+        })
+    };
+    for (pos, _) in tiles
+        .iter()
+        .filter(|(_, material)| **material == TileMaterial::PlayerSpawn)
+    {
+        add_object(SPAWN_TYPE, "Player", &pos, Vec::new());
+    }
+    for monster in level.monsters.iter() {
+        add_object(MONSTER_TYPE, &monster.name, &monster.tile_pos(), Vec::new());
+    }
+    for item in level.items.iter() {
+        add_object(ITEM_TYPE, &item.name, &item.tile_pos(), Vec::new());
+    }
+    for placement in level.objects.iter() {
+        let properties = match &placement.object {
+            TileObject::Door { open, lock } => {
+                let mut properties = vec![Property::new("open", Value::Bool(*open))];
+                if let Some(color) = lock {
+                    properties.push(Property::new("lock", Value::from(format!("{:?}", color))));
+                }
+                properties
+            }
+            // Item names can hold commas, so the loot is a JSON array rather than a list
+            TileObject::Chest { loot, open } => vec![
+                Property::new("open", Value::Bool(*open)),
+                Property::new("loot", Value::from(Value::from(loot.clone()).to_string())),
+            ],
+        };
+        let name = placement.object.name();
+        add_object(name, name, &placement.tile_pos(), properties);
+    }
+
+    let columns = table.image_width / table.tile_width.max(1);
+    let rows = table.image_height / table.tile_height.max(1);
+    Ok(TiledMap {
+        kind: "map".to_string(),
+        version: "1.10".to_string(),
+        orientation: "orthogonal".to_string(),
+        renderorder: "right-down".to_string(),
+        infinite: false,
+        width,
+        height,
+        tilewidth: table.tile_width,
+        tileheight: table.tile_height,
+        nextlayerid: 3,
+        nextobjectid: objects.len() as u32 + 1,
+        properties: vec![
+            Property::new("name", Value::from(level.name.clone())),
+            Property::new("author", Value::from(level.author.clone())),
+        ],
+        tilesets: vec![Tileset {
+            firstgid: first_gid,
+            source: None,
+            name: table.tileset.clone(),
+            tilewidth: table.tile_width,
+            tileheight: table.tile_height,
+            tilecount: columns * rows,
+            columns,
+            image: table.image.clone(),
+            imagewidth: table.image_width,
+            imageheight: table.image_height,
+        }],
+        layers: vec![
+            Layer {
+                id: 1,
+                name: "Tiles".to_string(),
+                kind: "tilelayer".to_string(),
+                opacity: 1.0,
+                visible: true,
+                width: Some(width),
+                height: Some(height),
+                data: Some(data),
+                ..Default::default()
+            },
+            Layer {
+                id: 2,
+                name: "Objects".to_string(),
+                kind: "objectgroup".to_string(),
+                opacity: 1.0,
+                visible: true,
+                draworder: Some("topdown".to_string()),
+                objects: Some(objects),
+                ..Default::default()
+            },
+        ],
+    })
+}
+
+/**
+ * A level from a map. Tile layers are stacked in order, with empty tiles left as they are, and
+ * objects are placed on the tile under their center. Objects of unknown types are skipped.
+ * Tiles are looked up in the table when they come from the map's only tileset, or from one
+ * named like the table's or kept in its own file.
+ */
+fn from_map(map: &TiledMap, table: &TiledTable) -> Result<Level, String> {
+    if map.infinite {
+        return Err("Infinite maps can't be read".to_string());
+    }
+    let (width, height) = (map.width, map.height);
+
+    let mut level = Level::blank(width, height);
+    if let Some(Value::String(name)) = property(&map.properties, "name") {
+        level.name = name.clone();
+    }
+    if let Some(Value::String(author)) = property(&map.properties, "author") {
+        level.author = author.clone();
+    }
+
+    for layer in map.layers.iter().filter(|layer| layer.kind == "tilelayer") {
+        let data = layer.data.as_deref().unwrap_or_default();
+        if data.len() != (width * height) as usize {
+            return Err(format!("Layer {} doesn't match the map size", layer.name));
+        }
+        for (idx, gid) in data.iter().enumerate() {
+            let gid = gid & !FLIP_FLAGS;
+            if gid == 0 {
+                continue;
+            }
+            let tileset = map
+                .tilesets
+                .iter()
+                .filter(|tileset| tileset.firstgid <= gid)
+                .max_by_key(|tileset| tileset.firstgid)
+                .ok_or_else(|| format!("Tile {} isn't in any tileset", gid))?;
+            let in_table = map.tilesets.len() == 1
+                || tileset.source.is_some()
+                || tileset.name == table.tileset;
+            if !in_table {
+                return Err(format!("Tileset {} isn't the Tiled table's", tileset.name));
+            }
+            let material = table
+                .material(gid - tileset.firstgid)
+                .ok_or_else(|| format!("Tile {} has no material in the Tiled table", gid))?;
+            let pos = TilePos {
+                x: idx as u32 % width,
+                y: height - 1 - idx as u32 / width,
+            };
+            level.tiles.set(&pos, material.clone());
+        }
+    }
+
+    let objects = map
+        .layers
+        .iter()
+        .filter(|layer| layer.kind == "objectgroup")
+        .flat_map(|layer| layer.objects.iter().flatten());
+    for object in objects {
+        let center_x = object.x + object.width / 2.0;
+        let center_y = match object.gid {
+            Some(_) => object.y - object.height / 2.0,
+            None => object.y + object.height / 2.0,
+        };
+        let (x, row) = (
+            (center_x / map.tilewidth.max(1) as f32).floor(),
+            (center_y / map.tileheight.max(1) as f32).floor(),
+        );
+        if x < 0.0 || row < 0.0 || x >= width as f32 || row >= height as f32 {
+            return Err(format!("Object {} is outside the map", object.name));
+        }
+        let pos = TilePos {
+            x: x as u32,
+            y: height - 1 - row as u32,
+        };
+
+        let open = property(&object.properties, "open") == Some(&Value::Bool(true));
+        match object.kind.as_str() {
+            SPAWN_TYPE => level.tiles.set(&pos, TileMaterial::PlayerSpawn),
+            MONSTER_TYPE => level.monsters.push(Placement::new(&object.name, &pos)),
+            ITEM_TYPE => level.items.push(Placement::new(&object.name, &pos)),
+            "Door" => {
+                let lock = match property(&object.properties, "lock") {
+                    Some(Value::String(lock)) if !lock.is_empty() => Some(
+                        KeyColor::ALL
+                            .into_iter()
+                            .find(|color| format!("{:?}", color) == *lock)
+                            .ok_or_else(|| format!("Unknown lock color {}", lock))?,
+                    ),
+                    _ => None,
+                };
+                let door = TileObject::Door { open, lock };
+                level.objects.push(ObjectPlacement::new(&door, &pos));
+            }
+            "Chest" => {
+                let loot = match property(&object.properties, "loot") {
+                    Some(Value::String(loot)) if !loot.trim().is_empty() => {
+                        serde_json::from_str(loot).map_err(|_| {
+                            format!("The loot of {} isn't a JSON array of items", object.name)
+                        })?
+                    }
+                    _ => Vec::new(),
+                };
+                let chest = TileObject::Chest { loot, open };
+                level.objects.push(ObjectPlacement::new(&chest, &pos));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(level)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> TiledTable {
+        TiledTable {
+            tileset: "tilemap".to_string(),
+            tile_width: 8,
+            tile_height: 8,
+            tiles: vec![
+                TiledTile {
+                    material: TileMaterial::Floor,
+                    ids: vec![0],
+                },
+                TiledTile {
+                    material: TileMaterial::Wall,
+                    ids: vec![1],
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    /**
+     * A map one row of tiles high
+     */
+    fn row(tilesets: &[(&str, u32)], gids: Vec<u32>) -> TiledMap {
+        TiledMap {
+            width: gids.len() as u32,
+            height: 1,
+            tilewidth: 8,
+            tileheight: 8,
+            tilesets: tilesets
+                .iter()
+                .map(|(name, firstgid)| Tileset {
+                    firstgid: *firstgid,
+                    name: name.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            layers: vec![Layer {
+                kind: "tilelayer".to_string(),
+                data: Some(gids),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn tiles_resolve_against_their_tileset() {
+        let tilesets = [("tilemap", 11), ("decor", 1), ("items", 21)];
+        let level = from_map(&row(&tilesets, vec![11, 12, 11]), &table()).unwrap();
+        let materials: Vec<_> = level
+            .tiles
+            .iter()
+            .map(|(_, material)| material.clone())
+            .collect();
+        let expected = [TileMaterial::Floor, TileMaterial::Wall, TileMaterial::Floor];
+        assert_eq!(materials, expected);
+
+        assert!(from_map(&row(&tilesets, vec![11, 1]), &table()).is_err());
+        assert!(from_map(&row(&tilesets, vec![11, 21]), &table()).is_err());
+    }
+
+    #[test]
+    fn loot_names_keep_their_commas() {
+        let mut level = Level::blank(2, 1);
+        let chest = TileObject::Chest {
+            loot: vec!["Bread, stale".to_string(), "Key".to_string()],
+            open: false,
+        };
+        level
+            .objects
+            .push(ObjectPlacement::new(&chest, &TilePos { x: 1, y: 0 }));
+
+        let table = table();
+        let tmj = to_tmj(&level, &table).unwrap();
+        assert_eq!(from_tmj(&tmj, &table), Ok(level.clone()));
+        let tmx = to_tmx(&level, &table).unwrap();
+        assert_eq!(from_tmx(&tmx, &table), Ok(level));
+    }
+}
@@ -0,0 +1,116 @@
+use super::{materials::MaterialSet, Grid, Level};
+use crate::{editor::prelude::TileMaterial, ImageAssets};
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+pub const TILE_SIZE: TilemapTileSize = TilemapTileSize { x: 8.0, y: 8.0 };
+
+/**
+ * Spawn a tilemap and its tile entities from a level
+ */
+pub fn spawn_level(
+    commands: &mut Commands,
+    images: &ImageAssets,
+    level: &Level,
+    material_set: Option<&MaterialSet>,
+) -> Entity {
+    let map_size = level.tiles.size();
+    let tilemap_entity = commands.spawn_empty().id();
+    let tile_storage = spawn_tiles(commands, tilemap_entity, &level.tiles, material_set);
+
+    commands.entity(tilemap_entity).insert((
+        Name::new("Tilemap"),
+        TilemapBundle {
+            grid_size: TILE_SIZE.into(),
+            map_type: TilemapType::default(),
+            size: map_size,
+            storage: tile_storage,
+            texture: TilemapTexture::Single(images.tilemap_image.clone()),
+            tile_size: TILE_SIZE,
+            transform: center_transform(&map_size),
+            ..Default::default()
+        },
+    ));
+
+    tilemap_entity
+}
+
+/**
+ * Swap out every tile of a tilemap spawned by `spawn_level`, which may change its size. The map
+ * is centered again like a freshly spawned one.
+ */
+pub fn respawn_tiles(
+    commands: &mut Commands,
+    tilemap_entity: Entity,
+    storage: &TileStorage,
+    tiles: &Grid<TileMaterial>,
+    material_set: Option<&MaterialSet>,
+) {
+    for tile_entity in storage.iter().flatten() {
+        commands.entity(*tile_entity).despawn();
+    }
+
+    let map_size = tiles.size();
+    let tile_storage = spawn_tiles(commands, tilemap_entity, tiles, material_set);
+    commands
+        .entity(tilemap_entity)
+        .insert((map_size, tile_storage, center_transform(&map_size)));
+}
+
+fn center_transform(map_size: &TilemapSize) -> Transform {
+    get_tilemap_center_transform(map_size, &TILE_SIZE.into(), &TilemapType::default(), 0.0)
+}
+
+/**
+ * Spawn one entity per tile. Without a material set the texture is left for the editor board
+ * to draw.
+ */
+pub fn spawn_tiles(
+    commands: &mut Commands,
+    tilemap_entity: Entity,
+    tiles: &Grid<TileMaterial>,
+    material_set: Option<&MaterialSet>,
+) -> TileStorage {
+    let mut tile_storage = TileStorage::empty(tiles.size());
+
+    for (tile_pos, material) in tiles.iter() {
+        let (texture_index, color) = material_set.map_or((32, Color::WHITE), |set| {
+            (set.grid_tile_index(tiles, &tile_pos), set.tint(material))
+        });
+
+        let tile_entity = commands
+            .spawn((
+                Name::new("Tile"),
+                TileBundle {
+                    position: tile_pos,
+                    tilemap_id: TilemapId(tilemap_entity),
+                    texture_index: TileTextureIndex(texture_index),
+                    color: TileColor(color),
+                    ..default()
+                },
+                material.clone(),
+            ))
+            .id();
+        tile_storage.set(&tile_pos, tile_entity);
+    }
+
+    tile_storage
+}
+
+/**
+ * Despawn a tilemap together with all of its tile entities
+ */
+pub fn despawn_level(commands: &mut Commands, tilemap_entity: Entity, storage: &TileStorage) {
+    for tile_entity in storage.iter().flatten() {
+        commands.entity(*tile_entity).despawn();
+    }
+    commands.entity(tilemap_entity).despawn();
+}
+
+/**
+ * World position of the center of a tile on a tilemap spawned by `spawn_level`
+ */
+pub fn tile_to_world(tile_pos: &TilePos, tilemap_transform: &Transform) -> Vec3 {
+    let center = tile_pos.center_in_world(&TILE_SIZE.into(), &TilemapType::default());
+    tilemap_transform.transform_point(center.extend(0.0))
+}
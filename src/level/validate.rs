@@ -0,0 +1,299 @@
+use super::{materials::MaterialSet, objects::TileObject, pathfinding::DijkstraMap, Grid, Level};
+use crate::editor::prelude::TileMaterial;
+use bevy_ecs_tilemap::tiles::TilePos;
+use std::{collections::HashSet, fmt};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Playable, but probably not what was meant
+    Warning,
+    /// The level can't be played properly
+    Error,
+}
+
+/// Something wrong with a level, at a tile if it can be pinned to one
+#[derive(Clone, Debug, PartialEq)]
+pub struct Problem {
+    pub severity: Severity,
+    pub message: String,
+    pub tile_pos: Option<TilePos>,
+}
+
+impl Problem {
+    fn error(message: impl Into<String>, tile_pos: Option<TilePos>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+            tile_pos,
+        }
+    }
+
+    fn warning(message: impl Into<String>, tile_pos: Option<TilePos>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message: message.into(),
+            tile_pos,
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        match self.tile_pos {
+            Some(pos) => write!(f, "{} at {},{}: {}", severity, pos.x, pos.y, self.message),
+            None => write!(f, "{}: {}", severity, self.message),
+        }
+    }
+}
+
+/**
+ * Check a level for things that make it unplayable: the player spawn, tiles open to the edge of
+ * the map, and floor, stairs, items and monsters the player can't walk to. Dungeon floors
+ * without a spawn are walked from their stairs up, where the player arrives. Chests block the
+ * way like they do in game, while doors don't since they can be opened. Errors come first.
+ */
+pub fn validate(level: &Level, materials: Option<&MaterialSet>) -> Vec<Problem> {
+    let tiles = &level.tiles;
+    if !tiles.is_valid() {
+        return vec![Problem::error(
+            "The tiles don't match the size of the level",
+            None,
+        )];
+    }
+    if tiles.width() == 0 || tiles.height() == 0 {
+        return vec![Problem::error("The level has no tiles", None)];
+    }
+    let passable = |pos: &TilePos| level.passable(pos.x as i32, pos.y as i32, materials);
+    let mut problems = Vec::new();
+
+    let spawns: Vec<TilePos> = tiles
+        .iter()
+        .filter(|(_, material)| **material == TileMaterial::PlayerSpawn)
+        .map(|(pos, _)| pos)
+        .collect();
+    let stairs_up = level.find(&TileMaterial::StairsUp);
+    match spawns.len() {
+        0 if stairs_up.is_some() => {}
+        0 => problems.push(Problem::error("The level has no player spawn", None)),
+        1 => {}
+        count => {
+            for pos in spawns.iter() {
+                let message = format!("One of {} player spawns", count);
+                problems.push(Problem::error(message, Some(*pos)));
+            }
+        }
+    }
+
+    let (width, height) = (tiles.width(), tiles.height());
+    let edges = [
+        (
+            "bottom",
+            (0..width).map(|x| TilePos { x, y: 0 }).collect::<Vec<_>>(),
+        ),
+        (
+            "top",
+            (0..width).map(|x| TilePos { x, y: height - 1 }).collect(),
+        ),
+        ("left", (0..height).map(|y| TilePos { x: 0, y }).collect()),
+        (
+            "right",
+            (0..height).map(|y| TilePos { x: width - 1, y }).collect(),
+        ),
+    ];
+    for (edge, positions) in edges.iter() {
+        let open: Vec<&TilePos> = positions.iter().filter(|pos| passable(pos)).collect();
+        if let Some(first) = open.first() {
+            let message = format!("{} tiles open to the {} edge of the map", open.len(), edge);
+            problems.push(Problem::error(message, Some(**first)));
+        }
+    }
+
+    let placed = [("Item", &level.items), ("Monster", &level.monsters)];
+    for (kind, placements) in placed.iter() {
+        for placement in placements.iter() {
+            if tiles.get(&placement.tile_pos()).is_none() {
+                let message = format!("{} {} is outside the map", kind, placement.name);
+                problems.push(Problem::error(message, None));
+            } else if !passable(&placement.tile_pos()) {
+                let message = format!("{} {} is inside a wall", kind, placement.name);
+                problems.push(Problem::error(message, Some(placement.tile_pos())));
+            }
+        }
+    }
+    for object in level.objects.iter() {
+        if tiles.get(&object.tile_pos()).is_none() {
+            let message = format!("{} is outside the map", object.object.name());
+            problems.push(Problem::error(message, None));
+        }
+    }
+
+    // Reachability only makes sense from a single entrance
+    let entrance = match spawns.as_slice() {
+        [spawn] => Some(*spawn),
+        [] => stairs_up,
+        _ => None,
+    };
+    if let Some(entrance) = entrance {
+        let chests: HashSet<TilePos> = level
+            .objects
+            .iter()
+            .filter(|object| matches!(object.object, TileObject::Chest { .. }))
+            .map(|object| object.tile_pos())
+            .collect();
+        let walkable = |pos: &TilePos| passable(pos) && !chests.contains(pos);
+        let reach = DijkstraMap::new(&[entrance], &tiles.size(), walkable);
+        let reachable = |pos: &TilePos| reach.distance(pos).is_some();
+
+        for material in [TileMaterial::StairsDown, TileMaterial::StairsUp] {
+            for (pos, _) in tiles.iter().filter(|(_, other)| **other == material) {
+                if !reachable(&pos) {
+                    let message = format!("The {} can't be reached", stairs_name(&material));
+                    problems.push(Problem::error(message, Some(pos)));
+                }
+            }
+        }
+
+        for (kind, placements) in placed.iter() {
+            for placement in placements.iter() {
+                let pos = placement.tile_pos();
+                // Placements in walls are reported above
+                if !passable(&pos) || reachable(&pos) {
+                    continue;
+                }
+                let message = format!("{} {} can't be reached", kind, placement.name);
+                problems.push(Problem::warning(message, Some(pos)));
+            }
+        }
+
+        // Report cut off areas once each rather than tile by tile
+        let mut cut_off = Grid::new(width, height, false);
+        for (pos, _) in tiles.iter() {
+            cut_off.set(&pos, walkable(&pos) && !reachable(&pos));
+        }
+        let mut reported = Grid::new(width, height, false);
+        for (pos, _) in tiles.iter() {
+            if cut_off.get(&pos) != Some(&true) || reported.get(&pos) == Some(&true) {
+                continue;
+            }
+            let region = cut_off.region(&pos, true);
+            for pos in region.iter() {
+                reported.set(pos, true);
+            }
+            let message = format!("{} tiles can't be reached from the entrance", region.len());
+            problems.push(Problem::warning(message, Some(pos)));
+        }
+    }
+
+    problems.sort_by_key(|problem| std::cmp::Reverse(problem.severity));
+    problems
+}
+
+fn stairs_name(material: &TileMaterial) -> &'static str {
+    match material {
+        TileMaterial::StairsUp => "stairs up",
+        _ => "stairs down",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::{load_asset_json, objects::ObjectPlacement, ASSET_ROOT, LEVEL_EXTENSION};
+    use std::{fs, path::Path};
+
+    /**
+     * A corridor from the spawn to the stairs down, walled in all around
+     */
+    fn corridor() -> Level {
+        let mut level = Level::blank(5, 3);
+        for y in 0..3 {
+            for x in 0..5 {
+                if x == 0 || x == 4 || y != 1 {
+                    level.tiles.set(&TilePos { x, y }, TileMaterial::Wall);
+                }
+            }
+        }
+        level
+            .tiles
+            .set(&TilePos { x: 1, y: 1 }, TileMaterial::PlayerSpawn);
+        level
+            .tiles
+            .set(&TilePos { x: 3, y: 1 }, TileMaterial::StairsDown);
+        level
+    }
+
+    fn errors(level: &Level) -> Vec<Problem> {
+        validate(level, None)
+            .into_iter()
+            .filter(|problem| problem.severity == Severity::Error)
+            .collect()
+    }
+
+    #[test]
+    fn lower_floors_start_on_their_stairs_up() {
+        let mut level = corridor();
+        level
+            .tiles
+            .set(&TilePos { x: 1, y: 1 }, TileMaterial::StairsUp);
+        assert_eq!(errors(&level), vec![]);
+
+        level
+            .tiles
+            .set(&TilePos { x: 1, y: 1 }, TileMaterial::Floor);
+        let no_spawn = Problem::error("The level has no player spawn", None);
+        assert_eq!(errors(&level), vec![no_spawn]);
+    }
+
+    #[test]
+    fn doors_leave_the_way_open() {
+        let mut level = corridor();
+        level.objects.push(ObjectPlacement::new(
+            &TileObject::door(),
+            &TilePos { x: 2, y: 1 },
+        ));
+        assert_eq!(errors(&level), vec![]);
+    }
+
+    #[test]
+    fn chests_block_the_way() {
+        let mut level = corridor();
+        let chest = TileObject::Chest {
+            loot: Vec::new(),
+            open: true,
+        };
+        level
+            .objects
+            .push(ObjectPlacement::new(&chest, &TilePos { x: 2, y: 1 }));
+        let unreachable = Problem::error(
+            "The stairs down can't be reached",
+            Some(TilePos { x: 3, y: 1 }),
+        );
+        assert_eq!(errors(&level), vec![unreachable]);
+    }
+
+    #[test]
+    fn asset_levels_have_no_errors() {
+        let materials: MaterialSet = load_asset_json("tiles.materials.json").unwrap();
+        let dir = Path::new(ASSET_ROOT).join("levels");
+        let mut checked = 0;
+        for entry in fs::read_dir(dir).unwrap() {
+            let file_name = entry.unwrap().file_name().to_string_lossy().to_string();
+            if !file_name.ends_with(LEVEL_EXTENSION) {
+                continue;
+            }
+            let level: Level = load_asset_json(&format!("levels/{}", file_name)).unwrap();
+            let problems: Vec<String> = validate(&level, Some(&materials))
+                .iter()
+                .filter(|problem| problem.severity == Severity::Error)
+                .map(|problem| problem.to_string())
+                .collect();
+            assert!(problems.is_empty(), "{}: {:?}", file_name, problems);
+            checked += 1;
+        }
+        assert!(checked > 0, "No levels in the assets");
+    }
+}
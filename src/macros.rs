@@ -8,6 +8,16 @@ macro_rules! get_single {
     };
 }
 
+#[macro_export]
+macro_rules! get_single_mut {
+    ($q:expr) => {
+        match $q.get_single_mut() {
+            Ok(m) => m,
+            _ => return,
+        }
+    };
+}
+
 #[macro_export]
 macro_rules! get_some {
     ($q:expr) => {
@@ -3,12 +3,13 @@ use bevy::{
     core_pipeline::clear_color::ClearColorConfig,
     diagnostic::FrameTimeDiagnosticsPlugin,
     input::common_conditions::input_toggle_active,
-    log::{Level, LogPlugin},
+    log::{Level as LogLevel, LogPlugin},
     prelude::*,
     window::PresentMode,
     DefaultPlugins,
 };
 use bevy_asset_loader::prelude::{AssetCollection, LoadingState, LoadingStateAppExt};
+use bevy_common_assets::json::JsonAssetPlugin;
 use bevy_ecs_tilemap::TilemapPlugin;
 use bevy_egui::EguiSettings;
 use bevy_egui::{
@@ -19,15 +20,28 @@ use bevy_inspector_egui::quick::WorldInspectorPlugin;
 use bevy_turborand::prelude::RngPlugin;
 use config::Debug;
 use editor::EditorPlugin;
-use game::{prelude::MainCamera, GamePlugin};
+use effects::EffectsPlugin;
+use game::{
+    prelude::{Dungeon, MainCamera},
+    GamePlugin,
+};
+use level::{
+    dungeon::DungeonDef, items::ItemSet, load_asset_json, materials::MaterialSet,
+    monsters::MonsterSet, Level, ITEMS_EXTENSION, LEVEL_EXTENSION, MATERIALS_EXTENSION,
+    MONSTERS_EXTENSION,
+};
 use main_menu::*;
 use std::{env, process, time::Duration};
 
+mod bench;
 mod config;
 mod editor;
+mod effects;
 mod game;
+mod level;
 mod macros;
 mod main_menu;
+mod tools;
 
 pub const SCREEN: Vec2 = Vec2::from_array([640.0, 480.0]);
 pub const DARK: Color = Color::rgb(0.191, 0.184, 0.156);
@@ -45,6 +59,16 @@ pub struct ImageAssets {
     pub tilemap_image: Handle<Image>,
 }
 
+#[derive(AssetCollection, Resource)]
+pub struct DataAssets {
+    #[asset(path = "tiles.materials.json")]
+    pub materials: Handle<MaterialSet>,
+    #[asset(path = "bestiary.monsters.json")]
+    pub monsters: Handle<MonsterSet>,
+    #[asset(path = "armory.items.json")]
+    pub items: Handle<ItemSet>,
+}
+
 #[derive(States, Hash, Clone, PartialEq, Eq, Debug, Default)]
 pub enum GameState {
     #[default]
@@ -52,6 +76,8 @@ pub enum GameState {
     MainMenu,
     InGame,
     InEditor,
+    /// Passed through between tearing down one floor of a dungeon and setting up the next
+    ChangingFloor,
 }
 
 /**
@@ -61,10 +87,45 @@ fn main() {
     // Possibility for program args
     let args: Vec<String> = env::args().skip(1).collect();
     let cfg = config::ProgramConfig::build(&args).unwrap_or_else(|err| {
-        println!("A problem occured when parsing args: {err}");
+        println!(
+            "A problem occured when parsing args: {err}\n{}",
+            config::USAGE
+        );
         process::exit(1);
     });
 
+    // Benchmarks run headless and exit
+    if cfg.bench_board {
+        if let Err(err) = bench::board_update() {
+            println!("Benchmark failed: {err}");
+            process::exit(1);
+        }
+        return;
+    }
+
+    // Level tooling runs headless and exits, failing so it can be scripted
+    if let Some(command) = &cfg.command {
+        if let Err(err) = tools::run(command) {
+            println!("{err}");
+            process::exit(1);
+        }
+        return;
+    }
+
+    // Playing a dungeon skips the editor and starts at the main menu
+    let dungeon = cfg.dungeon.as_deref().map(|path| {
+        load_asset_json::<DungeonDef>(path)
+            .and_then(|def| Dungeon::from_def(&def))
+            .unwrap_or_else(|err| {
+                println!("Could not load dungeon {path}: {err}");
+                process::exit(1);
+            })
+    });
+    let first_state = match dungeon {
+        Some(_) => GameState::MainMenu,
+        None => GameState::InEditor,
+    };
+
     let mut app = App::new();
     app.add_plugins(
         DefaultPlugins
@@ -82,7 +143,7 @@ fn main() {
                 ..default()
             })
             .set(LogPlugin {
-                level: Level::DEBUG,
+                level: LogLevel::DEBUG,
                 filter: "wgpu=error,bevy_render=info,bevy_ecs=trace".to_string(),
             })
             .set(ImagePlugin::default_nearest())
@@ -95,22 +156,34 @@ fn main() {
     )
     .add_state::<GameState>()
     .insert_resource(Debug(cfg.debug))
-    .add_loading_state(
-        LoadingState::new(GameState::AssetLoading).continue_to_state(GameState::InEditor),
-    )
+    .add_loading_state(LoadingState::new(GameState::AssetLoading).continue_to_state(first_state))
     .add_collection_to_loading_state::<_, ImageAssets>(GameState::AssetLoading)
+    .add_collection_to_loading_state::<_, DataAssets>(GameState::AssetLoading)
     .add_plugins((
         FrameTimeDiagnosticsPlugin::default(),
         RngPlugin::new().with_rng_seed(220718),
         EguiPlugin,
+        JsonAssetPlugin::<Level>::new(&[LEVEL_EXTENSION]),
+        JsonAssetPlugin::<MaterialSet>::new(&[MATERIALS_EXTENSION]),
+        JsonAssetPlugin::<MonsterSet>::new(&[MONSTERS_EXTENSION]),
+        JsonAssetPlugin::<ItemSet>::new(&[ITEMS_EXTENSION]),
         WorldInspectorPlugin::new().run_if(input_toggle_active(false, KeyCode::Escape)),
         MainMenuPlugin,
         GamePlugin,
         EditorPlugin,
+        EffectsPlugin,
         TilemapPlugin,
     ))
     .add_systems(Startup, (spawn_camera, setup_fonts))
-    .add_systems(Update, (zoom, move_camera));
+    .add_systems(
+        Update,
+        // The camera follows the player in game
+        (zoom, move_camera.run_if(not(in_state(GameState::InGame)))),
+    );
+
+    if let Some(dungeon) = dungeon {
+        app.insert_resource(dungeon);
+    }
 
     app.run();
 }
@@ -1,6 +1,6 @@
 use bevy::prelude::*;
 
-use crate::GameState;
+use crate::{game::prelude::GameOver, GameState};
 
 use super::components::MainMenuText;
 
@@ -8,17 +8,26 @@ pub fn transition_to_game(
     mut next_state: ResMut<NextState<GameState>>,
     keyboard: Res<Input<KeyCode>>,
 ) {
-    if keyboard.pressed(KeyCode::Space) {
+    if keyboard.just_released(KeyCode::Space) {
         next_state.set(GameState::InGame);
     }
 }
 
-pub fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+pub fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    game_over: Option<Res<GameOver>>,
+) {
+    let title = match game_over {
+        Some(game_over) => format!("YOU DIED ON TURN {}", game_over.turn),
+        None => "GAME TITLE!".to_string(),
+    };
+
     // Text with multiple sections
     commands.spawn((
         // Create a TextBundle that has a Text with a list of sections.
         TextBundle::from_sections([TextSection::new(
-            "GAME TITLE!",
+            title,
             TextStyle {
                 font: asset_server.load("fonts/visitor.ttf"),
                 font_size: 40.0,
@@ -65,4 +74,5 @@ pub fn teardown(mut commands: Commands, texts: Query<(Entity, With<MainMenuText>
     for (entity, _) in texts.iter() {
         commands.entity(entity).despawn();
     }
+    commands.remove_resource::<GameOver>();
 }
@@ -0,0 +1,184 @@
+use crate::{
+    config::Command,
+    level::{
+        format::{read_level, write_level, LevelFormat},
+        generate::Generator,
+        load_asset_json,
+        materials::MaterialSet,
+        tiled::{TiledTable, TILED_TABLE_PATH},
+        validate::{validate, Severity},
+        Level, ASSET_ROOT, LEVEL_EXTENSION,
+    },
+    DARK,
+};
+use image::{Rgba, RgbaImage};
+use std::{fs, path::Path};
+
+/// Tile atlas used for rendering, the same one the tilemap uses
+const ATLAS_PATH: &str = "textures/tilemap.png";
+const TILE_SIZE: u32 = 8;
+
+/**
+ * Run a headless command. Errors are returned for the caller to report, including levels that
+ * fail validation.
+ */
+pub fn run(command: &Command) -> Result<(), String> {
+    let materials: MaterialSet =
+        load_asset_json("tiles.materials.json").map_err(|err| err.to_string())?;
+    let table: TiledTable = load_asset_json(TILED_TABLE_PATH).map_err(|err| err.to_string())?;
+    match command {
+        Command::Validate { paths } => validate_levels(paths, &materials, &table),
+        Command::Render {
+            input,
+            output,
+            scale,
+        } => render(&read_level(input, &table)?, output, *scale, &materials),
+        Command::Convert { input, output } => {
+            let level = read_level(input, &table)?;
+            let keeps_placements =
+                LevelFormat::from_path(output).is_none_or(|format| format.keeps_placements());
+            if !keeps_placements && has_placements(&level) {
+                println!("{}: monsters, items and objects are left out", output);
+            }
+            write_level(&level, output, Some(&materials), &table)
+        }
+        Command::Generate {
+            generator,
+            width,
+            height,
+            seed,
+            output,
+        } => {
+            let generator = Generator::from_kind(generator)
+                .ok_or_else(|| format!("Unknown generator: {}", generator))?;
+            let mut level = Level::blank(*width, *height);
+            level.name = format!("{} {}", generator.name(), seed);
+            level.tiles = generator.generate(*width, *height, *seed)?;
+            write_level(&level, output, Some(&materials), &table)
+        }
+    }
+}
+
+fn has_placements(level: &Level) -> bool {
+    !level.monsters.is_empty() || !level.items.is_empty() || !level.objects.is_empty()
+}
+
+/**
+ * Print every problem of every level, with folders searched for level files
+ */
+fn validate_levels(
+    paths: &[String],
+    materials: &MaterialSet,
+    table: &TiledTable,
+) -> Result<(), String> {
+    let mut files = Vec::new();
+    for path in paths {
+        collect_levels(Path::new(path), &mut files)?;
+    }
+
+    let mut failed = 0;
+    for file in files.iter() {
+        let problems = match read_level(file, table) {
+            Ok(level) => validate(&level, Some(materials)),
+            Err(err) => {
+                println!("{}", err);
+                failed += 1;
+                continue;
+            }
+        };
+        for problem in problems.iter() {
+            println!("{}: {}", file, problem);
+        }
+        if problems
+            .iter()
+            .any(|problem| problem.severity == Severity::Error)
+        {
+            failed += 1;
+        }
+    }
+
+    println!("Checked {} levels, {} failed", files.len(), failed);
+    match failed {
+        0 => Ok(()),
+        _ => Err(format!("{} of {} levels failed", failed, files.len())),
+    }
+}
+
+fn collect_levels(path: &Path, files: &mut Vec<String>) -> Result<(), String> {
+    if !path.is_dir() {
+        files.push(path.to_string_lossy().to_string());
+        return Ok(());
+    }
+
+    let mut entries: Vec<_> = fs::read_dir(path)
+        .map_err(|err| format!("{}: {}", path.display(), err))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    entries.sort();
+    for entry in entries {
+        let is_level = entry.to_string_lossy().ends_with(LEVEL_EXTENSION);
+        if entry.is_dir() || is_level {
+            collect_levels(&entry, files)?;
+        }
+    }
+    Ok(())
+}
+
+/**
+ * Draw the tiles of a level like the tilemap does, autotiled and tinted over the background,
+ * `scale` pixels per atlas pixel
+ */
+fn render(level: &Level, output: &str, scale: u32, materials: &MaterialSet) -> Result<(), String> {
+    let atlas = image::open(Path::new(ASSET_ROOT).join(ATLAS_PATH))
+        .map_err(|err| format!("{}: {}", ATLAS_PATH, err))?
+        .to_rgba8();
+    let columns = atlas.width() / TILE_SIZE;
+    let tiles = &level.tiles;
+    let scale = scale.max(1);
+    let tile_pixels = TILE_SIZE * scale;
+
+    let [r, g, b, _] = DARK.as_rgba_f32();
+    let background = [r, g, b].map(|channel| (channel * 255.0) as u8);
+    let mut image = RgbaImage::from_pixel(
+        tiles.width() * tile_pixels,
+        tiles.height() * tile_pixels,
+        Rgba([background[0], background[1], background[2], 255]),
+    );
+
+    for (pos, material) in tiles.iter() {
+        let index = materials.grid_tile_index(tiles, &pos);
+        let [tint_r, tint_g, tint_b, _] = materials.tint(material).as_rgba_f32();
+        let (atlas_x, atlas_y) = ((index % columns) * TILE_SIZE, (index / columns) * TILE_SIZE);
+        // Images have their first row at the top, the tilemap at the bottom
+        let (left, top) = (
+            pos.x * tile_pixels,
+            (tiles.height() - 1 - pos.y) * tile_pixels,
+        );
+
+        for y in 0..tile_pixels {
+            for x in 0..tile_pixels {
+                let (src_x, src_y) = (atlas_x + x / scale, atlas_y + y / scale);
+                if src_x >= atlas.width() || src_y >= atlas.height() {
+                    continue;
+                }
+                let Rgba([sr, sg, sb, sa]) = *atlas.get_pixel(src_x, src_y);
+                let alpha = sa as f32 / 255.0;
+                let pixel = image.get_pixel_mut(left + x, top + y);
+                for (channel, (source, tint)) in [(sr, tint_r), (sg, tint_g), (sb, tint_b)]
+                    .into_iter()
+                    .enumerate()
+                {
+                    let blended =
+                        source as f32 * tint * alpha + pixel.0[channel] as f32 * (1.0 - alpha);
+                    pixel.0[channel] = blended.round().clamp(0.0, 255.0) as u8;
+                }
+            }
+        }
+    }
+
+    image
+        .save(output)
+        .map_err(|err| format!("{}: {}", output, err))?;
+    println!("Rendered {} to {}", level.name, output);
+    Ok(())
+}